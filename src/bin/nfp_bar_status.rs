@@ -0,0 +1,78 @@
+use clap::Parser;
+
+use nfp_debug_tools::libs::bar_lock::{lock_holder, reclaim_stale_lock};
+use nfp_debug_tools::libs::common::validate_nfp_bdf;
+use nfp_debug_tools::libs::expansion_bar::{
+    default_lock_dir, exp_bar_lock_path, read_exp_bar_config, CPP_MAX_NUM_EXPANSION_BARS,
+    CPP_NUM_PHYSICAL_BARS,
+};
+use nfp_debug_tools::libs::explicit_bar::{expl_bar_lock_path, NUM_EXPL_BARS};
+use std::path::{Path, PathBuf};
+
+/// Argument parser for CLI arguments.
+#[derive(Parser, Debug)]
+#[command(
+    about = "Show which process holds each expansion and explicit BAR of an NFP and how the expansion BARs are configured.",
+    long_about = None,
+    after_help = "Example usage: nfp-bar-status -Z 0000:65:00.0"
+)]
+struct Cli {
+    #[arg(short = 'Z', long = "pci-bdf", required = true, value_parser = validate_nfp_bdf)]
+    pci_bdf: String,
+
+    /// Directory of the BAR lock files (default /var/run/nfp_tools/<bdf>).
+    #[arg(long = "lock-dir")]
+    lock_dir: Option<PathBuf>,
+
+    /// Remove the lock files of stale locks, held by processes that no
+    /// longer exist and not open in any other process.
+    #[arg(long = "reclaim")]
+    reclaim: bool,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let lock_dir = cli
+        .lock_dir
+        .unwrap_or_else(|| default_lock_dir(&cli.pci_bdf));
+
+    // The device is not initialized here: this is a diagnostic tool and must
+    // not write the PCI command register.
+    println!("{:<5} {:<36} Configuration", "BAR", "Holder");
+    for phys_bar in 0..CPP_NUM_PHYSICAL_BARS as u8 {
+        for exp_bar in 0..CPP_MAX_NUM_EXPANSION_BARS as u8 {
+            let lock_path = exp_bar_lock_path(&lock_dir, phys_bar, exp_bar);
+            println!(
+                "{:<5} {:<36} {}",
+                format!("{}.{}", phys_bar, exp_bar),
+                holder(&lock_path, cli.reclaim),
+                read_exp_bar_config(&cli.pci_bdf, phys_bar, exp_bar)
+            );
+        }
+    }
+    for expl_bar in 0..NUM_EXPL_BARS {
+        let lock_path = expl_bar_lock_path(&lock_dir, expl_bar);
+        println!(
+            "{:<5} {:<36} explicit",
+            format!("E{}", expl_bar),
+            holder(&lock_path, cli.reclaim)
+        );
+    }
+}
+
+// Describes the holder of a BAR lock file, reclaiming a stale lock first if
+// requested.
+fn holder(lock_path: &Path, reclaim: bool) -> String {
+    if reclaim {
+        match reclaim_stale_lock(lock_path) {
+            Ok(Some(holder)) => println!("Reclaimed stale lock of {}", holder),
+            Ok(None) => (),
+            Err(e) => eprintln!("Failed to reclaim {}: {}", lock_path.display(), e),
+        }
+    }
+
+    match lock_holder(lock_path) {
+        Some(holder) => holder.to_string(),
+        None => "-".to_string(),
+    }
+}
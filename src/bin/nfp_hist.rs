@@ -0,0 +1,138 @@
+use clap::{Parser, ValueEnum};
+
+use nfp_debug_tools::libs::common::validate_nfp_bdf;
+use nfp_debug_tools::libs::cpp_bus::CppIsland;
+use nfp_debug_tools::libs::elf_symbolizer::ElfSymbolizer;
+use nfp_debug_tools::libs::expansion_bar::{init_device_bars, ExpansionBar};
+use nfp_debug_tools::libs::pa_histogram::{capture_histogram, histogram_max_buckets};
+use nfp_debug_tools::libs::rfpc::Rfpc;
+use nfp_debug_tools::libs::rfpc_trace::pa_histogram_on_rfpc_pc;
+use std::process::exit;
+use std::time::Duration;
+
+/// Argument parser for CLI arguments.
+#[derive(Parser, Debug)]
+#[command(
+    about = "Profile the PC of an RFPC core with a Performance Analyzer histogram.",
+    long_about = None,
+    after_help = "Example usage: nfp-hist -Z 0000:65:00.0 -i rfpc0 -c 0 -d 500 --top 20"
+)]
+struct Cli {
+    #[arg(short = 'Z', long = "pci-bdf", required = true, value_parser = validate_nfp_bdf)]
+    pci_bdf: String,
+
+    #[arg(short = 'i', long = "island", default_value = "rfpc0")]
+    island: CppIsland,
+
+    #[arg(short = 'u', long = "cluster", default_value_t = 0)]
+    cluster: u8,
+
+    #[arg(short = 'g', long = "group", default_value_t = 0)]
+    group: u8,
+
+    #[arg(short = 'c', long = "core", default_value_t = 0)]
+    core: u8,
+
+    /// Capture duration in milliseconds.
+    #[arg(short = 'd', long = "duration", default_value_t = 1000)]
+    duration: u64,
+
+    /// Each bucket covers 2^shift bytes of code (0-7).
+    #[arg(short = 's', long = "shift", default_value_t = 2,
+          value_parser = clap::value_parser!(u8).range(0..=7))]
+    shift: u8,
+
+    /// Number of histogram buckets to read (at most 16384, the size of the
+    /// histogram SRAM).
+    #[arg(short = 'n', long = "buckets", default_value_t = 4096,
+          value_parser = clap::value_parser!(u32).range(1..=histogram_max_buckets(false) as i64))]
+    buckets: u32,
+
+    /// Only show the given number of hottest buckets.
+    #[arg(long = "top")]
+    top: Option<usize>,
+
+    /// Width of the longest bar.
+    #[arg(short = 'w', long = "width", default_value_t = 40)]
+    width: usize,
+
+    /// Firmware ELF file used to label the buckets with symbols.
+    #[arg(short = 'e', long = "elf")]
+    elf: Option<String>,
+
+    /// Output format.
+    #[arg(short = 'f', long = "format", default_value = "chart")]
+    format: OutputFormat,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum OutputFormat {
+    Chart,
+    Csv,
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let symbolizer = cli.elf.as_ref().map(|elf| {
+        ElfSymbolizer::new(elf).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        })
+    });
+
+    // Bucket i counts the PCs from i << shift, so code outside the buckets
+    // read is not profiled.
+    let histogram_end = (cli.buckets as u64) << cli.shift;
+    if let Some((start, end)) = symbolizer.as_ref().and_then(|s| s.code_range()) {
+        if end > histogram_end {
+            eprintln!(
+                "Warning: the code of the ELF file spans {:#x}-{:#x}, but the {} buckets \
+                 only cover the PCs below {:#x}",
+                start, end, cli.buckets, histogram_end
+            );
+        }
+    }
+
+    let rfpc = Rfpc {
+        island: cli.island,
+        cluster: cli.cluster,
+        group: cli.group,
+        core: cli.core,
+    };
+
+    // Initialize the PCIe BARs in the PCIe config space.
+    init_device_bars(&cli.pci_bdf);
+
+    // Allocate a new expansion BAR for the PCIe device.
+    let mut exp_bar = ExpansionBar::new(&cli.pci_bdf, None);
+
+    let mut pa = pa_histogram_on_rfpc_pc(&mut exp_bar, &rfpc, cli.shift, cli.buckets);
+    let histogram = capture_histogram(&mut pa, Duration::from_millis(cli.duration), cli.buckets);
+
+    let label = |pc: u64| match &symbolizer {
+        Some(symbolizer) => format!("{:#010x} {}", pc, symbolizer.symbol_string(pc)),
+        None => format!("{:#010x}", pc),
+    };
+
+    match cli.format {
+        OutputFormat::Chart => {
+            println!(
+                "{} PC samples of {} in {} byte buckets",
+                histogram.total(0),
+                rfpc,
+                histogram.bucket_size()
+            );
+            for line in histogram.format_bar_chart(0, cli.width, cli.top, &label) {
+                println!("{}", line);
+            }
+        }
+        OutputFormat::Csv => {
+            println!("pc,count");
+            let buckets = histogram.hottest(0, cli.top.unwrap_or(usize::MAX));
+            for bucket in buckets {
+                println!("{:#010x},{}", bucket.start, bucket.counts[0]);
+            }
+        }
+    }
+}
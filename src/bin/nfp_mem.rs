@@ -0,0 +1,290 @@
+use clap::{Parser, Subcommand, ValueEnum};
+
+use nfp_debug_tools::libs::common::{hex_parser, hex_parser_u64, hexdump_lines, validate_nfp_bdf};
+use nfp_debug_tools::libs::cpp_bus::CppIsland;
+use nfp_debug_tools::libs::expansion_bar::{init_device_bars, ExpansionBar};
+use nfp_debug_tools::libs::mem_access::{
+    mem_read_chunked, mem_write_chunked, MemoryType, MuMemoryEngine,
+};
+use std::fs;
+use std::io::Write;
+use std::process::exit;
+
+// Maximum number of mismatches printed by the compare command.
+const MAX_REPORTED_MISMATCHES: usize = 16;
+
+/// Argument parser for CLI arguments.
+#[derive(Parser, Debug)]
+#[command(
+    about = "Dump, fill, load and compare NFP CTM, EMEM and CLS memory.",
+    long_about = None,
+    after_help = "Example usage: nfp-mem -Z 0000:65:00.0 -m ctm -i rfpc0 dump 0x0 256"
+)]
+struct Cli {
+    #[arg(short = 'Z', long = "pci-bdf", required = true, value_parser = validate_nfp_bdf)]
+    pci_bdf: String,
+
+    /// Memory type to access.
+    #[arg(short = 'm', long = "mem-type", default_value = "ctm")]
+    mem_type: MemoryType,
+
+    /// Island of the memory (defaults to emu0 for EMEM and rfpc0 otherwise).
+    #[arg(short = 'i', long = "island")]
+    island: Option<CppIsland>,
+
+    /// MU memory engine used for EMEM and CTM accesses.
+    #[arg(short = 'e', long = "engine", default_value = "bulk32")]
+    engine: MuMemoryEngine,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Dump a range of memory.
+    Dump {
+        #[arg(value_parser = hex_parser_u64)]
+        address: u64,
+
+        /// Number of bytes to dump.
+        #[arg(value_parser = hex_parser_u64)]
+        length: u64,
+
+        /// Output format.
+        #[arg(short = 'f', long = "format", default_value = "hexdump")]
+        format: DumpFormat,
+
+        /// Write the dump to a file instead of stdout.
+        #[arg(short = 'o', long = "output")]
+        output: Option<String>,
+    },
+    /// Fill a range of memory with a repeating 32-bit pattern.
+    Fill {
+        #[arg(value_parser = hex_parser_u64)]
+        address: u64,
+
+        /// Number of bytes to fill (multiple of 4, or of 8 for bulk64).
+        #[arg(value_parser = hex_parser_u64)]
+        length: u64,
+
+        #[arg(short = 'p', long = "pattern", value_parser = hex_parser, default_value = "0")]
+        pattern: u32,
+    },
+    /// Load the contents of a file into memory.
+    Load {
+        #[arg(value_parser = hex_parser_u64)]
+        address: u64,
+
+        file: String,
+    },
+    /// Compare memory with the contents of a file.
+    Compare {
+        #[arg(value_parser = hex_parser_u64)]
+        address: u64,
+
+        file: String,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum DumpFormat {
+    Hexdump,
+    Raw,
+    U32,
+    U64,
+}
+
+/// Returns the number of bytes the MU memory engine accesses at once, which
+/// addresses and lengths must be aligned to.
+fn access_size(engine: MuMemoryEngine) -> u64 {
+    if engine == MuMemoryEngine::Bulk64 {
+        8
+    } else {
+        4
+    }
+}
+
+/// Reads `length` bytes of memory, rounding the access up to whole words (or
+/// 64-bit words for bulk64).
+fn read_bytes(
+    exp_bar: &mut ExpansionBar,
+    cli: &Cli,
+    island: CppIsland,
+    address: u64,
+    length: u64,
+) -> Vec<u8> {
+    let access_size = access_size(cli.engine);
+    let words = mem_read_chunked(
+        exp_bar,
+        island,
+        cli.mem_type,
+        cli.engine,
+        address,
+        length.div_ceil(access_size) * access_size / 4,
+    );
+    let mut bytes: Vec<u8> = bytemuck::cast_slice(&words).to_vec();
+    bytes.truncate(length as usize);
+    bytes
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let island = cli.island.unwrap_or(match cli.mem_type {
+        MemoryType::Emem => CppIsland::Emu0,
+        MemoryType::Ctm | MemoryType::Cls => CppIsland::Rfpc0,
+    });
+
+    let address = match &cli.command {
+        Command::Dump { address, .. }
+        | Command::Fill { address, .. }
+        | Command::Load { address, .. }
+        | Command::Compare { address, .. } => *address,
+    };
+    let alignment = access_size(cli.engine);
+    if address % alignment != 0 {
+        eprintln!(
+            "Address {:#x} must be {}-byte aligned for {}",
+            address, alignment, cli.engine
+        );
+        exit(1);
+    }
+
+    // Initialize the PCIe BARs in the PCIe config space.
+    init_device_bars(&cli.pci_bdf);
+
+    // Allocate a new expansion BAR for the PCIe device.
+    let mut exp_bar = ExpansionBar::new(&cli.pci_bdf, None);
+
+    match &cli.command {
+        Command::Dump {
+            address,
+            length,
+            format,
+            output,
+        } => {
+            let bytes = read_bytes(&mut exp_bar, &cli, island, *address, *length);
+
+            let dump: Vec<u8> = match format {
+                DumpFormat::Raw => bytes,
+                DumpFormat::Hexdump => hexdump_lines(*address, &bytes)
+                    .iter()
+                    .map(|line| format!("{}\n", line))
+                    .collect::<String>()
+                    .into_bytes(),
+                DumpFormat::U32 | DumpFormat::U64 => {
+                    let width = if *format == DumpFormat::U32 { 4 } else { 8 };
+                    bytes
+                        .chunks(width)
+                        .enumerate()
+                        .map(|(i, chunk)| {
+                            let mut value = [0u8; 8];
+                            value[..chunk.len()].copy_from_slice(chunk);
+                            format!(
+                                "{:#014x}: {:#0w$x}\n",
+                                address + (i * width) as u64,
+                                u64::from_le_bytes(value),
+                                w = 2 + 2 * width
+                            )
+                        })
+                        .collect::<String>()
+                        .into_bytes()
+                }
+            };
+
+            match output {
+                Some(path) => {
+                    fs::write(path, &dump).expect(&format!("Failed to write {}", path));
+                }
+                None => {
+                    std::io::stdout()
+                        .write_all(&dump)
+                        .expect("Failed to write to stdout");
+                }
+            }
+        }
+        Command::Fill {
+            address,
+            length,
+            pattern,
+        } => {
+            if length % alignment != 0 {
+                eprintln!(
+                    "Fill length {:#x} must be a multiple of {} bytes for {}",
+                    length, alignment, cli.engine
+                );
+                exit(1);
+            }
+            let values = vec![*pattern; (length / 4) as usize];
+            mem_write_chunked(
+                &mut exp_bar,
+                island,
+                cli.mem_type,
+                cli.engine,
+                *address,
+                &values,
+            );
+            println!(
+                "Filled {:#x} bytes at {:#x} with {:#010x}",
+                length, address, pattern
+            );
+        }
+        Command::Load { address, file } => {
+            let mut bytes = fs::read(file).expect(&format!("Failed to read {}", file));
+            let length = bytes.len();
+
+            // Preserve the memory behind a partial last word (or 64-bit word
+            // for bulk64).
+            let tail = length % alignment as usize;
+            if tail != 0 {
+                let last_word_addr = address + (length - tail) as u64;
+                let last_word = read_bytes(&mut exp_bar, &cli, island, last_word_addr, alignment);
+                bytes.extend_from_slice(&last_word[tail..]);
+            }
+
+            let values: Vec<u32> = bytes
+                .chunks(4)
+                .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect();
+            mem_write_chunked(
+                &mut exp_bar,
+                island,
+                cli.mem_type,
+                cli.engine,
+                *address,
+                &values,
+            );
+            println!("Loaded {} bytes from {} at {:#x}", length, file, address);
+        }
+        Command::Compare { address, file } => {
+            let expected = fs::read(file).expect(&format!("Failed to read {}", file));
+            let actual = read_bytes(&mut exp_bar, &cli, island, *address, expected.len() as u64);
+
+            let mismatches: Vec<usize> = (0..expected.len())
+                .filter(|&i| expected[i] != actual[i])
+                .collect();
+
+            for &i in mismatches.iter().take(MAX_REPORTED_MISMATCHES) {
+                println!(
+                    "{:#014x}: expected {:#04x}, read {:#04x}",
+                    address + i as u64,
+                    expected[i],
+                    actual[i]
+                );
+            }
+
+            if mismatches.is_empty() {
+                println!("Memory matches {} ({} bytes)", file, expected.len());
+            } else {
+                println!(
+                    "{} of {} bytes differ from {}",
+                    mismatches.len(),
+                    expected.len(),
+                    file
+                );
+                exit(1);
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use clap::{Parser, ValueEnum};
+
+use nfp_debug_tools::libs::common::validate_nfp_bdf;
+use nfp_debug_tools::libs::cpp_bus::CppIsland;
+use nfp_debug_tools::libs::expansion_bar::{init_device_bars, ExpansionBar};
+use nfp_debug_tools::libs::pa_recipe::PARecipe;
+use nfp_debug_tools::libs::pa_sampler::{CounterSample, SamplingSession};
+use nfp_debug_tools::libs::performance_analyzer::PerformanceAnalyzer;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Argument parser for CLI arguments.
+#[derive(Parser, Debug)]
+#[command(
+    about = "Sample the Performance Analyzer counters of an island over time.",
+    long_about = None,
+    after_help = "Example usage: nfp-perf -Z 0000:65:00.0 -i rfpc0 --recipe counters.recipe -I 100 -o perf.csv"
+)]
+struct Cli {
+    #[arg(short = 'Z', long = "pci-bdf", required = true, value_parser = validate_nfp_bdf)]
+    pci_bdf: String,
+
+    /// Island of the Performance Analyzer.
+    #[arg(short = 'i', long = "island", default_value = "rfpc0")]
+    island: CppIsland,
+
+    /// Configure and start the Performance Analyzer with a recipe first.
+    /// Without a recipe, the counters of the running configuration are
+    /// sampled.
+    #[arg(long = "recipe")]
+    recipe: Option<String>,
+
+    /// Sampling interval in milliseconds.
+    #[arg(short = 'I', long = "interval", default_value_t = 1000)]
+    interval: u64,
+
+    /// Stop after the given number of samples.
+    #[arg(short = 'n', long = "samples")]
+    samples: Option<u64>,
+
+    /// Stop after the given number of seconds.
+    #[arg(short = 'd', long = "duration")]
+    duration: Option<u64>,
+
+    /// Output format.
+    #[arg(short = 'f', long = "format", default_value = "table")]
+    format: OutputFormat,
+
+    /// Write the samples to a CSV file instead of stdout.
+    #[arg(short = 'o', long = "output")]
+    output: Option<String>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum OutputFormat {
+    Table,
+    Csv,
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let recipe = cli.recipe.as_ref().map(|path| {
+        PARecipe::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1)
+        })
+    });
+
+    // Initialize the PCIe BARs in the PCIe config space.
+    init_device_bars(&cli.pci_bdf);
+
+    // Allocate a new expansion BAR for the PCIe device.
+    let mut exp_bar = ExpansionBar::new(&cli.pci_bdf, None);
+
+    // Use an atomic flag to stop sampling on ctrl+c.
+    let running = Arc::new(AtomicBool::new(true));
+    ctrlc::set_handler({
+        let running = running.clone();
+        move || {
+            running.store(false, Ordering::SeqCst);
+        }
+    })
+    .expect("Error setting Ctrl-C handler");
+
+    let mut pa = PerformanceAnalyzer::new(&mut exp_bar, cli.island);
+    if let Some(recipe) = &recipe {
+        pa = pa.apply_recipe(recipe).start_pa();
+    }
+
+    // With an output file the samples go to it as CSV, and only a summary is
+    // printed to stdout. Otherwise they go to stdout in the chosen format.
+    let mut csv_file = cli.output.as_ref().map(|path| {
+        BufWriter::new(File::create(path).unwrap_or_else(|e| {
+            eprintln!("Failed to create {}: {}", path, e);
+            exit(1)
+        }))
+    });
+    let format = if csv_file.is_some() {
+        OutputFormat::Csv
+    } else {
+        cli.format
+    };
+
+    let header = match format {
+        OutputFormat::Table => CounterSample::line_header(),
+        OutputFormat::Csv => CounterSample::csv_header(),
+    };
+    let mut write_line = |line: String| match &mut csv_file {
+        Some(file) => {
+            writeln!(file, "{}", line).expect("Failed to write the output file");
+            file.flush().expect("Failed to write the output file");
+        }
+        None => println!("{}", line),
+    };
+    write_line(header);
+
+    let mut session = SamplingSession::new(&mut pa, Duration::from_millis(cli.interval));
+    let taken = session.run(
+        cli.samples,
+        cli.duration.map(Duration::from_secs),
+        running,
+        |sample| {
+            write_line(match format {
+                OutputFormat::Table => sample.format_line(),
+                OutputFormat::Csv => sample.to_csv(),
+            })
+        },
+    );
+
+    if let Some(path) = &cli.output {
+        println!("Wrote {} samples to {}", taken, path);
+    }
+}
@@ -1,20 +1,29 @@
 use clap::Parser;
 
 use ctrlc;
-use nfp_debug_tools::libs::common::validate_nfp_bdf;
+use nfp_debug_tools::libs::common::{validate_listen_addr, validate_nfp_bdf};
 use nfp_debug_tools::libs::cpp_bus::CppIsland;
 use nfp_debug_tools::libs::expansion_bar::{init_device_bars, ExpansionBar};
-use nfp_debug_tools::libs::rsp_server_stub::RspServer;
+use nfp_debug_tools::libs::rfpc::Rfpc;
+use nfp_debug_tools::libs::rfpc_debugger::DebugConfig;
+use nfp_debug_tools::libs::rsp_server_stub::{
+    run_supervisor, KillPolicy, RspListenAddr, RspServer, RspSession, LOCAL_HOST_IP, PORT,
+};
 use nfp_debug_tools::libs::xpb_bus::xpb_write;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::process::exit;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Argument parser for CLI arguments.
 #[derive(Parser, Debug)]
 #[command(
     about = "Start an RSP debug server to connect to an NFP RISC-V debugger.",
     long_about = None,
-    after_help = "Example usage: nfp-rsp -Z 0000:65:00.0 -i rfpc0 -u 0 -g 0 -c 0"
+    after_help = "Example usage: nfp-rsp -Z 0000:65:00.0 -i rfpc0 -u 0 -g 0 -c 0 --listen 0.0.0.0:0\n\
+                  Supervisor mode: nfp-rsp -Z 0000:65:00.0 -i rfpc0 -u 0 --cores 0.0,0.1,1.0 --listen 0.0.0.0:13000"
 )]
 struct Cli {
     #[arg(short = 'Z', long = "pci-bdf", required = true, value_parser = validate_nfp_bdf)]
@@ -31,6 +40,79 @@ struct Cli {
 
     #[arg(short = 'c', long = "core")]
     core: Option<u8>,
+
+    /// Timeout in milliseconds for halt, resume, step and abstract commands.
+    #[arg(long = "dm-timeout", default_value_t = 10000)]
+    dm_timeout_ms: u64,
+
+    /// Timeout in seconds to wait for a breakpoint after continue (0 waits
+    /// until the server is stopped).
+    #[arg(long = "continue-timeout", default_value_t = 40)]
+    continue_timeout_s: u64,
+
+    /// Number of back-to-back debug module polls before sleeping.
+    #[arg(long = "spin-polls", default_value_t = 64)]
+    spin_polls: u32,
+
+    /// Maximum sleep between debug module polls in milliseconds.
+    #[arg(long = "max-poll-interval", default_value_t = 100)]
+    max_poll_interval_ms: u64,
+
+    /// TCP address to accept GDB connections on, as host:port. Port 0 picks
+    /// a free port, which is printed. May be given more than once. Defaults
+    /// to 127.0.0.1:12727 unless a Unix socket is given.
+    #[arg(long = "listen", value_parser = validate_listen_addr)]
+    listen: Vec<String>,
+
+    /// Unix domain socket path to accept GDB connections on, e.g. for
+    /// `target remote /tmp/rfpc0.sock`.
+    #[arg(long = "unix")]
+    unix: Option<PathBuf>,
+
+    /// What to do with the core when GDB kills the program (`kill`).
+    #[arg(long = "on-kill", default_value = "none")]
+    on_kill: KillPolicy,
+
+    /// Supervisor mode: serve several cores of the cluster at once, given as
+    /// a comma separated list of group.core pairs, or `all`. Each core gets
+    /// its own GDB session on consecutive ports starting at the --listen
+    /// port, or on a Unix socket named <--unix path>.<core> if given.
+    #[arg(long = "cores", value_parser = parse_core_list, conflicts_with_all = ["group", "core"])]
+    cores: Option<CoreList>,
+}
+
+/// Group and core numbers of the cores served in supervisor mode.
+#[derive(Clone, Debug)]
+struct CoreList(Vec<(u8, u8)>);
+
+fn parse_core_list(list: &str) -> Result<CoreList, String> {
+    if list == "all" {
+        return Ok(CoreList(
+            (0..4)
+                .flat_map(|group| (0..8).map(move |core| (group, core)))
+                .collect(),
+        ));
+    }
+
+    let mut cores = Vec::new();
+    for item in list.split(',') {
+        let (group, core) = item
+            .split_once('.')
+            .ok_or(format!("Expected group.core, got '{}'", item))?;
+        let group: u8 = group
+            .parse()
+            .map_err(|e| format!("Invalid group '{}': {}", group, e))?;
+        let core: u8 = core
+            .parse()
+            .map_err(|e| format!("Invalid core '{}': {}", core, e))?;
+        if group > 3 || core > 7 {
+            return Err(format!("Core {}.{} out of range (0-3.0-7)", group, core));
+        }
+        if !cores.contains(&(group, core)) {
+            cores.push((group, core));
+        }
+    }
+    Ok(CoreList(cores))
 }
 
 fn main() {
@@ -76,21 +158,98 @@ fn main() {
 
     let core = if let Some(core) = cli.core { core } else { 0 };
 
-    // Disable memory access control for specified RFPC group.
-    let grp_base_addr = 0x280000 + (0xE0000 * cluster as u32) + (0x100 * group as u32);
-    xpb_write(&mut exp_bar, &island, grp_base_addr, vec![0x7], true);
-    xpb_write(&mut exp_bar, &island, grp_base_addr + 0x40, vec![0], true);
-    xpb_write(
-        &mut exp_bar,
-        &island,
-        grp_base_addr + 0x44,
-        vec![0xFF0159],
-        true,
-    );
+    // Disable memory access control for the RFPC groups of the debugged cores.
+    let groups: Vec<u8> = match &cli.cores {
+        Some(CoreList(cores)) => {
+            let mut groups: Vec<u8> = cores.iter().map(|(group, _)| *group).collect();
+            groups.sort();
+            groups.dedup();
+            groups
+        }
+        None => vec![group],
+    };
+    for group in groups {
+        let grp_base_addr = 0x280000 + (0xE0000 * cluster as u32) + (0x100 * group as u32);
+        xpb_write(&mut exp_bar, &island, grp_base_addr, vec![0x7], true);
+        xpb_write(&mut exp_bar, &island, grp_base_addr + 0x40, vec![0], true);
+        xpb_write(
+            &mut exp_bar,
+            &island,
+            grp_base_addr + 0x44,
+            vec![0xFF0159],
+            true,
+        );
+    }
+
+    // Debugger polling settings, cancelled through the same run flag.
+    let mut dbg_config = DebugConfig::new(running.clone());
+    dbg_config.halt_timeout = Duration::from_millis(cli.dm_timeout_ms);
+    dbg_config.resume_timeout = Duration::from_millis(cli.dm_timeout_ms);
+    dbg_config.step_timeout = Duration::from_millis(cli.dm_timeout_ms);
+    dbg_config.abstract_cmd_timeout = Duration::from_millis(cli.dm_timeout_ms);
+    dbg_config.continue_timeout = match cli.continue_timeout_s {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+    dbg_config.spin_polls = cli.spin_polls;
+    dbg_config.max_poll_interval = Duration::from_millis(cli.max_poll_interval_ms);
+
+    if let Some(CoreList(cores)) = cli.cores {
+        if cli.listen.len() > 1 {
+            eprintln!("Supervisor mode takes a single --listen base address.");
+            exit(1);
+        }
+
+        // Consecutive ports (or one socket per core) for the sessions.
+        let base_addr = match cli.listen.first() {
+            Some(addr) => addr.to_socket_addrs().unwrap().next().unwrap(),
+            None => format!("{}:{}", LOCAL_HOST_IP, PORT).parse().unwrap(),
+        };
+        let sessions: Vec<RspSession> = cores
+            .iter()
+            .enumerate()
+            .map(|(i, (group, core))| {
+                let rfpc = Rfpc::new(island, cluster, *group, *core);
+                let listen_addr = match &cli.unix {
+                    Some(path) => {
+                        RspListenAddr::Unix(PathBuf::from(format!("{}.{}", path.display(), rfpc)))
+                    }
+                    None => {
+                        let mut addr = base_addr;
+                        if addr.port() != 0 {
+                            let port = addr.port().checked_add(i as u16).unwrap_or_else(|| {
+                                eprintln!("Not enough ports above {} for all cores.", base_addr);
+                                exit(1)
+                            });
+                            addr.set_port(port);
+                        }
+                        RspListenAddr::Tcp(addr.to_string())
+                    }
+                };
+                RspSession { rfpc, listen_addr }
+            })
+            .collect();
+
+        // All sessions share the expansion BAR.
+        let exp_bar = Mutex::new(exp_bar);
+        run_supervisor(&exp_bar, &sessions, &dbg_config, cli.on_kill, running);
+        return;
+    }
+
+    // Listen on the default TCP address unless told otherwise.
+    let mut listen_addrs: Vec<RspListenAddr> =
+        cli.listen.into_iter().map(RspListenAddr::Tcp).collect();
+    if let Some(path) = cli.unix {
+        listen_addrs.push(RspListenAddr::Unix(path));
+    }
+    if listen_addrs.is_empty() {
+        listen_addrs.push(RspListenAddr::default());
+    }
 
     // Create an instance of RspServer.
-    let mut rsp_server = RspServer::new(&mut exp_bar, island, cluster, group, core);
+    let mut rsp_server = RspServer::new(&mut exp_bar, island, cluster, group, core, dbg_config);
+    rsp_server.set_kill_policy(cli.on_kill);
 
     // Run the server in the main thread.
-    rsp_server.run(running);
+    rsp_server.run(&listen_addrs, running);
 }
@@ -0,0 +1,397 @@
+use clap::{Parser, ValueEnum};
+
+use nfp_debug_tools::libs::common::validate_nfp_bdf;
+use nfp_debug_tools::libs::cpp_bus::CppIsland;
+use nfp_debug_tools::libs::elf_symbolizer::{
+    format_annotated_trace, function_hit_counts, ElfSymbolizer,
+};
+use nfp_debug_tools::libs::expansion_bar::{init_device_bars, ExpansionBar};
+use nfp_debug_tools::libs::pa_recipe::PARecipe;
+use nfp_debug_tools::libs::pa_stream::{
+    read_gap_file, split_at_gaps, stream_fifo, write_gap_file, StreamLimits,
+};
+use nfp_debug_tools::libs::performance_analyzer::PerformanceAnalyzer;
+use nfp_debug_tools::libs::rfpc::Rfpc;
+use nfp_debug_tools::libs::rfpc_trace::{
+    decode_uncomp_trace, format_uncomp_trace, format_uncomp_trace_csv, pa_trigger_on_recipe_trace,
+    pa_trigger_on_uncomp_trace, read_trace, recipe_trace_format, trace_has_timestamp,
+    trace_words_per_sample,
+};
+use std::fs;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// Size of the buffer between the PA FIFO and the stream file, in words.
+const STREAM_BUFFER_WORDS: usize = 64 * 1024;
+
+/// Argument parser for CLI arguments.
+#[derive(Parser, Debug)]
+#[command(
+    about = "Capture, stream and decode RFPC instruction traces with the Performance Analyzer.",
+    long_about = None,
+    after_help = "Example usage: nfp-trace -Z 0000:65:00.0 -i rfpc0 -u 0 -g 0 -c 0 --pc -n 64"
+)]
+struct Cli {
+    #[arg(short = 'Z', long = "pci-bdf", required_unless_present = "input",
+          value_parser = validate_nfp_bdf)]
+    pci_bdf: Option<String>,
+
+    #[arg(short = 'i', long = "island", default_value = "rfpc0")]
+    island: CppIsland,
+
+    #[arg(short = 'u', long = "cluster", default_value_t = 0)]
+    cluster: u8,
+
+    #[arg(short = 'g', long = "group", default_value_t = 0)]
+    group: u8,
+
+    #[arg(short = 'c', long = "core", default_value_t = 0)]
+    core: u8,
+
+    /// Trace the program counter.
+    #[arg(long = "pc")]
+    trace_pc: bool,
+
+    /// Trace sequential instruction execution.
+    #[arg(long = "seq")]
+    trace_seq: bool,
+
+    /// Trace breakpoints.
+    #[arg(long = "bp")]
+    trace_bp: bool,
+
+    /// Trace register file writes.
+    #[arg(long = "reg")]
+    trace_reg: bool,
+
+    /// Number of performance bus words to sample (1-3).
+    #[arg(short = 'b', long = "bus-words", default_value_t = 3, conflicts_with = "recipe",
+          value_parser = clap::value_parser!(u32).range(1..=3))]
+    bus_words: u32,
+
+    /// Performance bus word that is sampled first (0-2).
+    #[arg(short = 'w', long = "word-index", default_value_t = 0, conflicts_with = "recipe",
+          value_parser = clap::value_parser!(u32).range(0..=2))]
+    word_index: u32,
+
+    /// Capture a timestamp with every sample.
+    #[arg(short = 't', long = "timestamp", conflicts_with = "recipe")]
+    timestamp: bool,
+
+    /// Number of samples to capture.
+    #[arg(short = 'n', long = "samples", default_value_t = 256)]
+    samples: u32,
+
+    /// Capture with a saved Performance Analyzer recipe instead of the
+    /// built-in trace configuration. The bus words, word index and timestamp
+    /// are taken from the recipe's capture settings.
+    #[arg(long = "recipe", conflicts_with = "input")]
+    recipe: Option<String>,
+
+    /// Save the Performance Analyzer configuration used for the capture as a
+    /// recipe file.
+    #[arg(long = "save-recipe", conflicts_with = "input")]
+    save_recipe: Option<String>,
+
+    /// Output format.
+    #[arg(short = 'f', long = "format", default_value = "table")]
+    format: OutputFormat,
+
+    /// Write the trace to a file instead of stdout.
+    #[arg(short = 'o', long = "output")]
+    output: Option<String>,
+
+    /// Decode a trace previously saved with `-f raw` instead of capturing one.
+    /// The capture options must match the ones used for the saved trace.
+    #[arg(long = "input")]
+    input: Option<String>,
+
+    /// Firmware ELF file used to symbolize the trace (`-f annotated` and
+    /// `--hits`).
+    #[arg(short = 'e', long = "elf")]
+    elf: Option<String>,
+
+    /// Print the number of instructions executed per function.
+    #[arg(long = "hits", requires = "elf")]
+    hits: bool,
+
+    /// Stream the trace to a file in the `-f raw` format until `-n` samples
+    /// (0 for no limit), `--duration`, a trigger halt or Ctrl-C. The FIFO
+    /// journals, so the capture never stops on a full FIFO.
+    #[arg(long = "stream", conflicts_with_all = ["input", "output", "hits"])]
+    stream: Option<String>,
+
+    /// Stop streaming after the given number of seconds.
+    #[arg(long = "duration", requires = "stream")]
+    duration: Option<u64>,
+
+    /// Seconds to wait for the requested samples (0 to wait forever). A
+    /// capture that times out shows the samples read until then.
+    #[arg(long = "timeout", default_value_t = 10, conflicts_with = "stream")]
+    timeout: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+enum OutputFormat {
+    Table,
+    Csv,
+    Raw,
+    Events,
+    Annotated,
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    // Trace everything if no trace source was selected.
+    let trace_all = !(cli.trace_pc || cli.trace_seq || cli.trace_bp || cli.trace_reg);
+
+    let symbolizer = cli.elf.as_ref().map(|elf| {
+        ElfSymbolizer::new(elf).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1);
+        })
+    });
+    if cli.format == OutputFormat::Annotated && symbolizer.is_none() {
+        eprintln!("The annotated format needs the firmware ELF file (--elf)");
+        exit(1);
+    }
+
+    let recipe = cli.recipe.as_ref().map(|path| {
+        PARecipe::load(path).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1)
+        })
+    });
+
+    let rfpc = Rfpc {
+        island: cli.island,
+        cluster: cli.cluster,
+        group: cli.group,
+        core: cli.core,
+    };
+
+    let (bus_words, word_index, timestamp) = match &recipe {
+        Some(recipe) => recipe_trace_format(recipe).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            exit(1)
+        }),
+        None => (cli.bus_words, cli.word_index, cli.timestamp),
+    };
+    let words_per_sample = trace_words_per_sample(bus_words, timestamp);
+
+    // Offsets of the gaps left by FIFO overflows in a streamed input.
+    let mut gaps: Vec<u64> = Vec::new();
+
+    let fifo_words: Vec<u32> = match (&cli.input, &cli.pci_bdf) {
+        (Some(input), _) => {
+            let bytes = fs::read(input).expect(&format!("Failed to read {}", input));
+            gaps = read_gap_file(Path::new(input)).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                exit(1)
+            });
+            bytes
+                .chunks_exact(4)
+                .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+                .collect()
+        }
+        (None, Some(pci_bdf)) => {
+            // Initialize the PCIe BARs in the PCIe config space.
+            init_device_bars(pci_bdf);
+
+            // Allocate a new expansion BAR for the PCIe device.
+            let mut exp_bar = ExpansionBar::new(pci_bdf, None);
+
+            let mut pa = match &recipe {
+                Some(recipe) => pa_trigger_on_recipe_trace(
+                    &mut exp_bar,
+                    &rfpc,
+                    recipe,
+                    cli.trace_pc || trace_all,
+                    cli.trace_seq || trace_all,
+                    cli.trace_bp || trace_all,
+                    cli.trace_reg || trace_all,
+                ),
+                None => pa_trigger_on_uncomp_trace(
+                    &mut exp_bar,
+                    &rfpc,
+                    cli.trace_pc || trace_all,
+                    cli.trace_seq || trace_all,
+                    cli.trace_bp || trace_all,
+                    cli.trace_reg || trace_all,
+                    cli.bus_words,
+                    cli.word_index,
+                    cli.timestamp,
+                ),
+            };
+
+            if let Some(path) = &cli.save_recipe {
+                let name = Path::new(path)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                pa.recipe(&name).save(path).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    exit(1)
+                });
+            }
+
+            let num_words = cli
+                .samples
+                .checked_mul(words_per_sample as u32)
+                .unwrap_or_else(|| {
+                    eprintln!(
+                        "{} samples of {} words do not fit in a 32-bit FIFO word count",
+                        cli.samples, words_per_sample
+                    );
+                    exit(1)
+                });
+
+            if let Some(path) = &cli.stream {
+                stream_trace(&mut pa, path, num_words, cli.duration);
+                return;
+            }
+
+            let fifo_words = read_trace(
+                &mut pa,
+                num_words,
+                (cli.timeout != 0).then(|| Duration::from_secs(cli.timeout)),
+            );
+            if fifo_words.len() < num_words as usize {
+                eprintln!(
+                    "Timed out after {}s with {} of {} FIFO words",
+                    cli.timeout,
+                    fifo_words.len(),
+                    num_words
+                );
+            }
+            fifo_words
+        }
+        (None, None) => unreachable!("clap requires --pci-bdf without --input"),
+    };
+
+    // Segments between overflow gaps start at a sample boundary and are
+    // decoded separately; partial samples before a gap are dropped.
+    let samples: Vec<u32> = split_at_gaps(&fifo_words, &gaps)
+        .into_iter()
+        .flat_map(|segment| {
+            let whole_samples = segment.len() - segment.len() % words_per_sample;
+            segment[..whole_samples].to_vec()
+        })
+        .collect();
+
+    let records = decode_uncomp_trace(&samples, bus_words, word_index, timestamp);
+    let num_samples = samples.len() / words_per_sample;
+
+    let output: Vec<u8> = match cli.format {
+        OutputFormat::Table => format_uncomp_trace(
+            samples,
+            bus_words,
+            word_index,
+            trace_has_timestamp(bus_words, timestamp),
+            words_per_sample,
+        )
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect::<String>()
+        .into_bytes(),
+        OutputFormat::Csv => format_uncomp_trace_csv(&samples, bus_words, timestamp)
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect::<String>()
+            .into_bytes(),
+        // Raw output keeps the FIFO words as captured, so it can be decoded
+        // again with `--input`.
+        OutputFormat::Raw => bytemuck::cast_slice(&fifo_words).to_vec(),
+        OutputFormat::Events => records
+            .iter()
+            .map(|record| format!("{}\n", record))
+            .collect::<String>()
+            .into_bytes(),
+        OutputFormat::Annotated => format_annotated_trace(&records, symbolizer.as_ref().unwrap())
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect::<String>()
+            .into_bytes(),
+    };
+
+    match &cli.output {
+        Some(path) => {
+            fs::write(path, &output).expect(&format!("Failed to write {}", path));
+            println!("Wrote {} samples of {} to {}", num_samples, rfpc, path);
+        }
+        None => {
+            std::io::stdout()
+                .write_all(&output)
+                .expect("Failed to write to stdout");
+        }
+    }
+
+    if cli.hits {
+        let symbolizer = symbolizer.as_ref().unwrap();
+        println!("\n{:>12}  function", "instructions");
+        for (function, count) in function_hit_counts(&records, symbolizer) {
+            println!("{:>12}  {}", count, function);
+        }
+    }
+}
+
+/// Streams the PA FIFO to a file until the word limit (0 for none), the
+/// duration, a trigger halt or Ctrl-C, printing the progress to stderr.
+fn stream_trace(pa: &mut PerformanceAnalyzer, path: &str, num_words: u32, duration: Option<u64>) {
+    // Use an atomic flag to stop streaming on ctrl+c.
+    let running = Arc::new(AtomicBool::new(true));
+    ctrlc::set_handler({
+        let running = running.clone();
+        move || {
+            running.store(false, Ordering::SeqCst);
+        }
+    })
+    .expect("Error setting Ctrl-C handler");
+
+    let limits = StreamLimits {
+        max_words: (num_words != 0).then_some(num_words as u64),
+        duration: duration.map(Duration::from_secs),
+        stop_on_halt: true,
+        running,
+    };
+
+    let mut file = BufWriter::new(File::create(path).unwrap_or_else(|e| {
+        eprintln!("Failed to create {}: {}", path, e);
+        exit(1)
+    }));
+    let stats = stream_fifo(pa, &mut file, STREAM_BUFFER_WORDS, &limits, |stats| {
+        eprint!(
+            "\r{} words in {:.1}s, {} overflows",
+            stats.words,
+            stats.elapsed.as_secs_f64(),
+            stats.overflows
+        );
+    })
+    .unwrap_or_else(|e| {
+        eprintln!("\nFailed to write {}: {}", path, e);
+        exit(1)
+    });
+
+    eprintln!();
+    println!(
+        "Streamed {} words to {} ({:?}), highest FIFO fill {} words",
+        stats.words, path, stats.stop, stats.max_fill
+    );
+    write_gap_file(Path::new(path), &stats.gaps).unwrap_or_else(|e| {
+        eprintln!("Failed to write the gap file of {}: {}", path, e);
+        exit(1)
+    });
+    if stats.overflows != 0 {
+        println!(
+            "Warning: the FIFO overflowed {} times, samples were dropped. The gaps are \
+             listed in {}.gaps",
+            stats.overflows, path
+        );
+    }
+}
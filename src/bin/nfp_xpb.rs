@@ -0,0 +1,172 @@
+use clap::{Parser, Subcommand};
+
+use nfp_debug_tools::libs::common::{hex_parser, validate_nfp_bdf};
+use nfp_debug_tools::libs::cpp_bus::CppIsland;
+use nfp_debug_tools::libs::expansion_bar::{init_device_bars, ExpansionBar};
+use nfp_debug_tools::libs::explicit_bar::{ExplicitBar, NUM_EXPL_BARS};
+use nfp_debug_tools::libs::xpb_bus::{
+    xpb_explicit_read32, xpb_explicit_write32, xpb_read, xpb_write,
+};
+
+/// Explicit command BAR selected with `--explicit`.
+#[derive(Clone, Copy, Debug)]
+enum ExplBarSelect {
+    Auto,
+    Index(u32),
+}
+
+fn parse_expl_bar(value: &str) -> Result<ExplBarSelect, String> {
+    if value == "auto" {
+        return Ok(ExplBarSelect::Auto);
+    }
+    match value.parse::<u32>() {
+        Ok(index) if index < NUM_EXPL_BARS => Ok(ExplBarSelect::Index(index)),
+        _ => Err(format!(
+            "Expected an index 0-{} or 'auto', got '{}'",
+            NUM_EXPL_BARS - 1,
+            value
+        )),
+    }
+}
+
+/// Argument parser for CLI arguments.
+#[derive(Parser, Debug)]
+#[command(
+    about = "Read and write NFP XPB registers.",
+    long_about = None,
+    after_help = "Example usage: nfp-xpb -Z 0000:65:00.0 -i rfpc0 read 0x0f0000 -n 4"
+)]
+struct Cli {
+    #[arg(short = 'Z', long = "pci-bdf", required = true, value_parser = validate_nfp_bdf)]
+    pci_bdf: String,
+
+    /// Island the XPB register belongs to.
+    #[arg(short = 'i', long = "island", default_value = "rfpc0")]
+    island: CppIsland,
+
+    /// Access the register through the global XPB master (xpbm) address space.
+    #[arg(short = 'G', long = "global")]
+    global: bool,
+
+    /// Use an explicit command BAR instead of an expansion BAR: the given one
+    /// (0-3), or the first free one with "auto".
+    #[arg(short = 'E', long = "explicit", value_name = "INDEX|auto", value_parser = parse_expl_bar)]
+    explicit: Option<ExplBarSelect>,
+
+    /// Number of times to repeat the operation.
+    #[arg(short = 'r', long = "repeat", default_value_t = 1)]
+    repeat: u32,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Read one or more consecutive 32-bit XPB registers.
+    Read {
+        #[arg(value_parser = hex_parser)]
+        address: u32,
+
+        /// Number of consecutive registers to read.
+        #[arg(short = 'n', long = "count", default_value_t = 1)]
+        count: u32,
+    },
+    /// Write one or more consecutive 32-bit XPB registers.
+    Write {
+        #[arg(value_parser = hex_parser)]
+        address: u32,
+
+        #[arg(value_parser = hex_parser, required = true)]
+        values: Vec<u32>,
+    },
+    /// Read-modify-write the bits selected by a mask in an XPB register.
+    Rmw {
+        #[arg(value_parser = hex_parser)]
+        address: u32,
+
+        #[arg(value_parser = hex_parser)]
+        value: u32,
+
+        /// Bits of the register to modify.
+        #[arg(short = 'm', long = "mask", value_parser = hex_parser)]
+        mask: u32,
+    },
+}
+
+/// BAR used to reach the XPB bus.
+enum XpbAccess {
+    Expansion(ExpansionBar),
+    Explicit(ExplicitBar),
+}
+
+impl XpbAccess {
+    fn read(&mut self, island: &CppIsland, address: u32, count: u32, xpbm: bool) -> Vec<u32> {
+        match self {
+            XpbAccess::Expansion(exp_bar) => xpb_read(exp_bar, island, address, count as u64, xpbm),
+            // Explicit XPB accesses are a single word each.
+            XpbAccess::Explicit(expl_bar) => (0..count)
+                .map(|i| xpb_explicit_read32(expl_bar, island, address + 4 * i, xpbm))
+                .collect(),
+        }
+    }
+
+    fn write(&mut self, island: &CppIsland, address: u32, values: Vec<u32>, xpbm: bool) {
+        match self {
+            XpbAccess::Expansion(exp_bar) => xpb_write(exp_bar, island, address, values, xpbm),
+            XpbAccess::Explicit(expl_bar) => {
+                for (i, value) in values.iter().enumerate() {
+                    xpb_explicit_write32(
+                        expl_bar,
+                        island,
+                        address + 4 * i as u32,
+                        vec![*value],
+                        xpbm,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    // Initialize the PCIe BARs in the PCIe config space.
+    init_device_bars(&cli.pci_bdf);
+
+    let mut xpb = match cli.explicit {
+        Some(ExplBarSelect::Auto) => XpbAccess::Explicit(ExplicitBar::new(&cli.pci_bdf, None)),
+        Some(ExplBarSelect::Index(index)) => {
+            XpbAccess::Explicit(ExplicitBar::new(&cli.pci_bdf, Some(index)))
+        }
+        None => XpbAccess::Expansion(ExpansionBar::new(&cli.pci_bdf, None)),
+    };
+
+    for _ in 0..cli.repeat {
+        match &cli.command {
+            Command::Read { address, count } => {
+                let values = xpb.read(&cli.island, *address, *count, cli.global);
+                for (i, value) in values.iter().enumerate() {
+                    println!("{:#08x}: {:#010x}", address + 4 * i as u32, value);
+                }
+            }
+            Command::Write { address, values } => {
+                xpb.write(&cli.island, *address, values.clone(), cli.global);
+            }
+            Command::Rmw {
+                address,
+                value,
+                mask,
+            } => {
+                let old_value = xpb.read(&cli.island, *address, 1, cli.global)[0];
+                let new_value = (old_value & !mask) | (value & mask);
+                xpb.write(&cli.island, *address, vec![new_value], cli.global);
+                println!(
+                    "{:#08x}: {:#010x} -> {:#010x}",
+                    address, old_value, new_value
+                );
+            }
+        }
+    }
+}
@@ -1,13 +1,23 @@
 pub mod libs {
+    pub mod bar_lock;
+    pub mod bar_pool;
     pub mod common;
     pub mod cpp_bus;
+    pub mod cpp_explicit_bus;
+    pub mod elf_symbolizer;
     pub mod expansion_bar;
     pub mod explicit_bar;
     pub mod mem_access;
+    pub mod pa_histogram;
+    pub mod pa_recipe;
+    pub mod pa_sampler;
+    pub mod pa_stream;
+    pub mod pa_trigger;
     pub mod performance_analyzer;
     pub mod rfpc;
     pub mod rfpc_debugger;
     pub mod rfpc_trace;
     pub mod rsp_server_stub;
+    pub mod rv_disasm;
     pub mod xpb_bus;
 }
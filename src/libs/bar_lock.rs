@@ -0,0 +1,327 @@
+#![allow(dead_code)]
+
+use fs2::FileExt;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Owner information a process records in a BAR lock file once it holds the
+/// lock, as `key=value` lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub program: String,
+    /// Time the lock was taken, in seconds since the Unix epoch.
+    pub started: u64,
+}
+
+/// A process holding the lock of a BAR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockHolder {
+    /// Process that took the lock, as listed in `/proc/locks`.
+    pub pid: u32,
+    /// Command name of the process, or the program recorded in the lock file
+    /// if the process is gone.
+    pub program: Option<String>,
+    /// Time the lock was taken, in seconds since the Unix epoch, if recorded
+    /// in the lock file.
+    pub started: Option<u64>,
+    /// `false` if the process is known to be gone and no process that can be
+    /// seen from here has the lock file open. A process the holder spawned
+    /// keeps the lock through its inherited descriptor, so it counts as alive
+    /// while it has the file open. Holders that cannot be seen from here, or
+    /// whose descriptors cannot be inspected, count as alive.
+    pub alive: bool,
+}
+
+impl LockOwner {
+    /// Owner information of the current process.
+    pub fn current() -> Self {
+        let program = std::env::args()
+            .next()
+            .and_then(|arg0| {
+                Path::new(&arg0)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+            })
+            .unwrap_or_else(|| "unknown".to_string());
+        LockOwner {
+            pid: std::process::id(),
+            program,
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or(0),
+        }
+    }
+
+    /// Reads the owner information of a lock file.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the file does not exist or holds no owner information.
+    pub fn read(lock_path: &Path) -> Option<Self> {
+        let contents = fs::read_to_string(lock_path).ok()?;
+        let field = |key: &str| {
+            contents
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .map(|value| value.trim().to_string())
+        };
+        Some(LockOwner {
+            pid: field("pid")?.parse().ok()?,
+            program: field("program").unwrap_or_default(),
+            started: field("started").and_then(|started| started.parse().ok())?,
+        })
+    }
+
+    fn write(&self, file: &mut File) -> io::Result<()> {
+        file.set_len(0)?;
+        write!(
+            file,
+            "pid={}\nprogram={}\nstarted={}\n",
+            self.pid, self.program, self.started
+        )?;
+        file.flush()
+    }
+}
+
+impl LockHolder {
+    /// Time the lock has been held, if known.
+    pub fn held_for(&self) -> Option<Duration> {
+        let started = UNIX_EPOCH + Duration::from_secs(self.started?);
+        SystemTime::now().duration_since(started).ok()
+    }
+}
+
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pid {}", self.pid)?;
+        if let Some(program) = &self.program {
+            write!(f, " ({})", program)?;
+        }
+        if let Some(held_for) = self.held_for() {
+            write!(f, " for {}", format_duration(held_for))?;
+        }
+        if !self.alive {
+            write!(f, " [stale]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a duration as e.g. `45s`, `12m03s` or `3h07m`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, (secs % 3600) / 60)
+    }
+}
+
+/// Locks a BAR lock file and records the current process as its owner.
+///
+/// Stale locks are not reclaimed here, as a holder that looks gone may live in
+/// a PID namespace this process cannot see. Use `reclaim_stale_lock()`, e.g.
+/// through `nfp-bar-status --reclaim`.
+///
+/// # Returns
+///
+/// The locked file, which releases the lock when closed, or the error of
+/// locking it. The error is of kind `WouldBlock` if another process holds
+/// the lock.
+pub fn acquire_lock(lock_path: &Path) -> io::Result<File> {
+    let mut lock_file = try_lock(lock_path)?;
+    LockOwner::current().write(&mut lock_file)?;
+    Ok(lock_file)
+}
+
+/// Removes a BAR lock file if its lock is stale, so the next `acquire_lock`
+/// can take it. The stale holder keeps its lock on the removed file, which no
+/// longer excludes anybody.
+///
+/// Reclaimers serialize on a lock of the lock directory, and the file is only
+/// removed if it is still the one found stale, so a lock file another process
+/// has recreated and locked in the meantime is left alone.
+///
+/// # Returns
+///
+/// The stale holder if the lock was reclaimed, `None` if the lock is free or
+/// held by a live process.
+pub fn reclaim_stale_lock(lock_path: &Path) -> io::Result<Option<LockHolder>> {
+    let lock_dir = match lock_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    // Released when closed on return.
+    let dir_lock = match File::open(lock_dir) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        result => result?,
+    };
+    dir_lock.lock_exclusive()?;
+
+    match find_lock_holder(lock_path) {
+        Some((inode, holder)) if !holder.alive => {
+            if fs::metadata(lock_path)?.ino() == inode {
+                fs::remove_file(lock_path)?;
+                Ok(Some(holder))
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+// Opens and locks a lock file. The file is not truncated, so the owner
+// information of a held lock is kept. A lock file replaced by a reclaim
+// between opening and locking it is reported as locked.
+fn try_lock(lock_path: &Path) -> io::Result<File> {
+    let lock_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path)?;
+    lock_file.try_lock_exclusive()?;
+    let replaced = match fs::metadata(lock_path) {
+        Ok(metadata) => metadata.ino() != lock_file.metadata()?.ino(),
+        // Removed by a reclaim since it was opened.
+        Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+        Err(e) => return Err(e),
+    };
+    if replaced {
+        return Err(io::Error::new(
+            io::ErrorKind::WouldBlock,
+            "Lock file was replaced",
+        ));
+    }
+    Ok(lock_file)
+}
+
+/// Looks up the process holding a BAR lock file in `/proc/locks`.
+///
+/// # Returns
+///
+/// `None` if the file does not exist or is not locked.
+pub fn lock_holder(lock_path: &Path) -> Option<LockHolder> {
+    find_lock_holder(lock_path).map(|(_, holder)| holder)
+}
+
+// Looks up the holder of a lock file, along with the inode it was found for.
+fn find_lock_holder(lock_path: &Path) -> Option<(u64, LockHolder)> {
+    let metadata = fs::metadata(lock_path).ok()?;
+    let (major, minor) = dev_major_minor(metadata.dev());
+    let lock = read_proc_locks()
+        .into_iter()
+        .find(|lock| lock.major == major && lock.minor == minor && lock.inode == metadata.ino())?;
+
+    let owner = LockOwner::read(lock_path).filter(|owner| owner.pid == lock.pid);
+    let comm = fs::read_to_string(format!("/proc/{}/comm", lock.pid))
+        .ok()
+        .map(|comm| comm.trim_end().to_string());
+    Some((
+        metadata.ino(),
+        LockHolder {
+            pid: lock.pid,
+            alive: comm.is_some()
+                || !is_gone(lock.pid)
+                || has_open_descriptors(metadata.dev(), metadata.ino()),
+            program: comm.or_else(|| owner.as_ref().map(|owner| owner.program.clone())),
+            started: owner.map(|owner| owner.started),
+        },
+    ))
+}
+
+// Whether a process is known to be gone. `/proc/locks` shows pid 0 for a
+// holder in a PID namespace this process cannot see, e.g. a host tool seen
+// from a container, and `/proc` may hide other processes (`hidepid`). Such
+// holders are never reported as gone.
+fn is_gone(pid: u32) -> bool {
+    pid != 0 && Path::new("/proc/1").exists() && !Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+// Whether any process has a file open, found by scanning `/proc/*/fd`.
+// Processes whose descriptors cannot be read, e.g. those of other users when
+// not running as root, may have it open, so they count as having it open.
+fn has_open_descriptors(dev: u64, inode: u64) -> bool {
+    let Ok(processes) = fs::read_dir("/proc") else {
+        return true;
+    };
+
+    for process in processes.flatten() {
+        if !process
+            .file_name()
+            .to_string_lossy()
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+        {
+            continue;
+        }
+        let fds = match fs::read_dir(process.path().join("fd")) {
+            Ok(fds) => fds,
+            // The process exited during the scan.
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(_) => return true,
+        };
+        for fd in fds.flatten() {
+            if let Ok(metadata) = fs::metadata(fd.path()) {
+                if metadata.dev() == dev && metadata.ino() == inode {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+// A lock listed in /proc/locks.
+struct ProcLock {
+    pid: u32,
+    major: u32,
+    minor: u32,
+    inode: u64,
+}
+
+// Reads the granted locks from /proc/locks. Lines look like
+// `1: FLOCK  ADVISORY  WRITE 1234 fd:01:5678 0 EOF`; waiting locks have a
+// `->` after the number and are skipped.
+fn read_proc_locks() -> Vec<ProcLock> {
+    let Ok(locks) = fs::read_to_string("/proc/locks") else {
+        return Vec::new();
+    };
+
+    locks
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 || fields[1] == "->" {
+                return None;
+            }
+            let pid = fields[4].parse().ok()?;
+            let mut dev_inode = fields[5].split(':');
+            let major = u32::from_str_radix(dev_inode.next()?, 16).ok()?;
+            let minor = u32::from_str_radix(dev_inode.next()?, 16).ok()?;
+            let inode = dev_inode.next()?.parse().ok()?;
+            Some(ProcLock {
+                pid,
+                major,
+                minor,
+                inode,
+            })
+        })
+        .collect()
+}
+
+// Splits a Linux device number into its major and minor numbers.
+fn dev_major_minor(dev: u64) -> (u32, u32) {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    (major as u32, minor as u32)
+}
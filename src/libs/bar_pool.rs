@@ -0,0 +1,202 @@
+#![allow(dead_code)]
+
+use crate::libs::bar_lock::{lock_holder, LockHolder};
+use crate::libs::expansion_bar::{
+    default_lock_dir, exp_bar_lock_path, ExpansionBar, CPP_EXPANSION_BAR_PHYSICAL_BAR,
+    CPP_MAX_NUM_EXPANSION_BARS,
+};
+use std::fs;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// Time between attempts while waiting for a free expansion BAR.
+const LEASE_POLL_MIN_INTERVAL: Duration = Duration::from_millis(1);
+const LEASE_POLL_MAX_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Hands out the expansion BARs of a PCIe device to the tools and threads
+/// sharing it.
+///
+/// Every expansion BAR has a lock file in the lock directory, which is locked
+/// with `flock` while the BAR is leased. The locks are per open file, so they
+/// exclude other threads of the same process as well as other processes. A
+/// pool holds no state besides its configuration and can be shared between
+/// threads.
+#[derive(Debug, Clone)]
+pub struct BarPool {
+    pci_bdf: String,
+    lock_dir: PathBuf,
+    phys_bar: u8,
+    num_exp_bars: u8,
+}
+
+/// An expansion BAR leased from a `BarPool`. The BAR is released when the
+/// lease is dropped.
+pub struct BarLease {
+    bar: ExpansionBar,
+    leased_at: Instant,
+}
+
+/// The lock state of an expansion BAR of a `BarPool`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BarHolder {
+    pub phys_bar: u8,
+    pub exp_bar: u8,
+    pub lock_path: PathBuf,
+    /// `None` if the BAR is free.
+    pub holder: Option<LockHolder>,
+}
+
+impl BarPool {
+    /// Creates a pool of the expansion BARs of physical BAR 2 of a PCIe
+    /// device, locked in `/var/run/nfp_tools/<bdf>`.
+    pub fn new(pci_bdf: &str) -> Self {
+        Self::with_lock_dir(pci_bdf, default_lock_dir(pci_bdf))
+    }
+
+    /// Creates a pool like `new()`, with the lock files in `lock_dir`. All
+    /// tools sharing the device must use the same lock directory.
+    pub fn with_lock_dir(pci_bdf: &str, lock_dir: impl Into<PathBuf>) -> Self {
+        BarPool {
+            pci_bdf: pci_bdf.to_string(),
+            lock_dir: lock_dir.into(),
+            phys_bar: CPP_EXPANSION_BAR_PHYSICAL_BAR as u8,
+            num_exp_bars: CPP_MAX_NUM_EXPANSION_BARS as u8,
+        }
+    }
+
+    pub fn pci_bdf(&self) -> &str {
+        &self.pci_bdf
+    }
+
+    pub fn lock_dir(&self) -> &Path {
+        &self.lock_dir
+    }
+
+    /// Leases the first free expansion BAR without waiting.
+    ///
+    /// # Returns
+    ///
+    /// The lease, or an error message listing the holders of the BARs if all
+    /// of them are taken.
+    pub fn try_lease(&self) -> Result<BarLease, String> {
+        self.lease_free_bar()?.ok_or_else(|| self.busy_message())
+    }
+
+    /// Leases the first free expansion BAR, waiting for one to be released
+    /// if all of them are taken.
+    ///
+    /// # Parameters
+    ///
+    /// * `timeout`: Time to wait for a free BAR, or `None` to wait forever.
+    ///
+    /// # Returns
+    ///
+    /// The lease, or an error message listing the holders of the BARs if no
+    /// BAR became free in time.
+    pub fn lease(&self, timeout: Option<Duration>) -> Result<BarLease, String> {
+        let start = Instant::now();
+        let mut interval = LEASE_POLL_MIN_INTERVAL;
+        loop {
+            if let Some(lease) = self.lease_free_bar()? {
+                return Ok(lease);
+            }
+            if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+                return Err(self.busy_message());
+            }
+            sleep(interval);
+            interval = (interval * 2).min(LEASE_POLL_MAX_INTERVAL);
+        }
+    }
+
+    // Leases the first expansion BAR that is not locked by another user.
+    // Returns `None` if all of them are taken, and an error for any other
+    // failure to lock a BAR, e.g. a lock file that cannot be opened.
+    fn lease_free_bar(&self) -> Result<Option<BarLease>, String> {
+        fs::create_dir_all(&self.lock_dir)
+            .map_err(|e| format!("Failed to create dir {}: {}", self.lock_dir.display(), e))?;
+
+        for exp_bar in 0..self.num_exp_bars {
+            match ExpansionBar::try_new(&self.pci_bdf, &self.lock_dir, self.phys_bar, exp_bar) {
+                Ok(bar) => {
+                    return Ok(Some(BarLease {
+                        bar,
+                        leased_at: Instant::now(),
+                    }))
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => {
+                    return Err(format!(
+                        "Failed to lock {}: {}",
+                        exp_bar_lock_path(&self.lock_dir, self.phys_bar, exp_bar).display(),
+                        e
+                    ))
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Lists the expansion BARs of the pool and the processes holding them,
+    /// as found in `/proc/locks`.
+    pub fn holders(&self) -> Vec<BarHolder> {
+        (0..self.num_exp_bars)
+            .map(|exp_bar| {
+                let lock_path = exp_bar_lock_path(&self.lock_dir, self.phys_bar, exp_bar);
+                BarHolder {
+                    phys_bar: self.phys_bar,
+                    exp_bar,
+                    holder: lock_holder(&lock_path),
+                    lock_path,
+                }
+            })
+            .collect()
+    }
+
+    // Error message for a pool without free BARs.
+    fn busy_message(&self) -> String {
+        let holders: Vec<String> = self
+            .holders()
+            .iter()
+            .map(|bar| match &bar.holder {
+                Some(holder) => format!("{}.{}: {}", bar.phys_bar, bar.exp_bar, holder),
+                None => format!("{}.{}: unknown", bar.phys_bar, bar.exp_bar),
+            })
+            .collect();
+        format!(
+            "No expansion BARs of {} available ({})",
+            self.pci_bdf,
+            holders.join(", ")
+        )
+    }
+}
+
+impl BarLease {
+    /// Time since the BAR was leased.
+    pub fn held_for(&self) -> Duration {
+        self.leased_at.elapsed()
+    }
+
+    /// Takes the BAR out of the lease. The BAR stays locked until it is
+    /// dropped.
+    pub fn into_bar(self) -> ExpansionBar {
+        self.bar
+    }
+}
+
+impl Deref for BarLease {
+    type Target = ExpansionBar;
+
+    fn deref(&self) -> &ExpansionBar {
+        &self.bar
+    }
+}
+
+impl DerefMut for BarLease {
+    fn deref_mut(&mut self) -> &mut ExpansionBar {
+        &mut self.bar
+    }
+}
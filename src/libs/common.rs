@@ -1,4 +1,5 @@
 use std::fs;
+use std::net::ToSocketAddrs;
 use std::num::ParseIntError;
 use std::path::Path;
 use std::path::PathBuf;
@@ -69,6 +70,21 @@ pub fn validate_nfp_bdf(pci_bdf: &str) -> Result<String, String> {
     Ok(pci_bdf)
 }
 
+/// Validates a `host:port` listen address, such as `127.0.0.1:12727`,
+/// `0.0.0.0:0` or `localhost:2000`. Port 0 lets the OS pick a free port.
+///
+/// # Returns
+///
+/// Returns `Ok(String)` with the address if it resolves, or `Err(String)` with
+/// an error message otherwise.
+pub fn validate_listen_addr(addr: &str) -> Result<String, String> {
+    match addr.to_socket_addrs() {
+        Ok(addrs) if addrs.len() > 0 => Ok(addr.to_string()),
+        Ok(_) => Err(format!("{} does not resolve to any address", addr)),
+        Err(e) => Err(format!("Invalid listen address {}: {}", addr, e)),
+    }
+}
+
 /// Splits a 48-bit address into a base address and an offset.
 ///
 /// This function takes a 48-bit address and an aperture value, which specifies the
@@ -116,3 +132,63 @@ pub fn hex_parser(s: &str) -> Result<u32, ParseIntError> {
         s.parse::<u32>()
     }
 }
+
+/// Parses a string representation of a 64-bit hexadecimal or decimal number.
+///
+/// Same as `hex_parser()`, but for values wider than 32 bits such as CPP
+/// memory addresses.
+///
+/// # Parameters
+///
+/// * `s`: A string slice containing the number to be parsed.
+///
+/// # Returns
+///
+/// Returns `Ok(u64)` if the parsing is successful, or an error of type `ParseIntError`
+/// if the string cannot be parsed as a valid integer.
+pub fn hex_parser_u64(s: &str) -> Result<u64, ParseIntError> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16)
+    } else {
+        s.parse::<u64>()
+    }
+}
+
+/// Formats a byte buffer as a canonical hexdump.
+///
+/// Each line shows the address of the first byte, up to 16 bytes in hex and
+/// their printable ASCII representation.
+///
+/// # Parameters
+///
+/// * `address`: Address of the first byte in `bytes`.
+/// * `bytes`: The bytes to format.
+///
+/// # Returns
+///
+/// Returns a `Vec<String>` with one formatted line per 16 bytes.
+pub fn hexdump_lines(address: u64, bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!(
+                "{:#014x}: {:<47}  |{}|",
+                address + 16 * idx as u64,
+                hex.join(" "),
+                ascii
+            )
+        })
+        .collect()
+}
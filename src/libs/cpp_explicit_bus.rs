@@ -0,0 +1,191 @@
+#![allow(dead_code)]
+
+use crate::libs::common::split_addr48;
+use crate::libs::cpp_bus::{CppIsland, CppTarget};
+use crate::libs::explicit_bar::ExplicitBar;
+
+/// Maximum number of 32-bit words of a command, limited by the 5-bit CPP
+/// length field and the PCIe SRAM data area of an explicit BAR.
+pub const CPP_EXPLICIT_MAX_WORDS: u64 = 32;
+
+// Signal type used when the explicit BAR handles the signals of a command.
+const DEFAULT_SIGNAL_TYPE: u8 = 1;
+
+/// How the data and signals of an explicit CPP command are routed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CppSignal {
+    /// The explicit BAR acts as the master and handles the signals, with the
+    /// given signal type. Pull data is taken from the PCIe SRAM data area of
+    /// the explicit BAR.
+    Type(u8),
+    /// The command is issued on behalf of another master, which receives the
+    /// push data and the signals.
+    Master {
+        master_island: u8,
+        data_master: u8,
+        data_ref: u8,
+        signal_master: u8,
+        signal_ref: u8,
+    },
+}
+
+impl Default for CppSignal {
+    fn default() -> Self {
+        CppSignal::Type(DEFAULT_SIGNAL_TYPE)
+    }
+}
+
+/// A CPP command issued through an explicit command BAR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CppCommand {
+    pub island: CppIsland,
+    pub target: CppTarget,
+    pub action: u8,
+    pub token: u8,
+    /// 48-bit CPP address.
+    pub address: u64,
+    /// Bytes of each 64-bit word a write command updates, bit 0 for the
+    /// lowest byte.
+    pub byte_mask: u8,
+    pub signal: CppSignal,
+}
+
+impl CppCommand {
+    /// Creates a command with all bytes enabled and the signals handled by
+    /// the explicit BAR.
+    pub fn new(island: CppIsland, target: CppTarget, action: u8, token: u8, address: u64) -> Self {
+        CppCommand {
+            island,
+            target,
+            action,
+            token,
+            address,
+            byte_mask: 0xFF,
+            signal: CppSignal::default(),
+        }
+    }
+}
+
+/// CPP bus issuing arbitrary commands through an explicit command BAR.
+///
+/// Unlike `CppBus`, which maps a window of a CPP target into an expansion BAR,
+/// every access configures the full command: action, token, length, byte mask
+/// and signalling. This allows commands that have no expansion BAR mapping,
+/// such as byte-masked writes and atomics that push back data.
+pub struct CppExplicitBus<'a> {
+    pub expl_bar: &'a mut ExplicitBar,
+}
+
+impl<'a> CppExplicitBus<'a> {
+    pub fn new(expl_bar: &'a mut ExplicitBar) -> Self {
+        CppExplicitBus { expl_bar }
+    }
+
+    /// Issues a command that pushes data to the host.
+    ///
+    /// # Parameters
+    ///
+    /// * `cmd`: Command to issue.
+    /// * `length_words`: Number of 32-bit words to read, at most
+    ///   `CPP_EXPLICIT_MAX_WORDS`.
+    ///
+    /// # Returns
+    ///
+    /// The pushed words.
+    pub fn read(&mut self, cmd: &CppCommand, length_words: u64) -> Vec<u32> {
+        self.run(cmd, length_words, None, Some(length_words))
+    }
+
+    /// Issues a command that pulls data from the host. Only the bytes enabled
+    /// in the byte mask of the command are written.
+    ///
+    /// # Parameters
+    ///
+    /// * `cmd`: Command to issue.
+    /// * `write_words`: Words to write, at most `CPP_EXPLICIT_MAX_WORDS`.
+    pub fn write(&mut self, cmd: &CppCommand, write_words: Vec<u32>) {
+        let length_words = write_words.len() as u64;
+        self.run(cmd, length_words, Some(write_words), None);
+    }
+
+    /// Issues a command that pulls operands from the host and pushes back a
+    /// result, e.g. a test-and-set or another atomic memory operation.
+    ///
+    /// # Parameters
+    ///
+    /// * `cmd`: Command to issue.
+    /// * `operands`: Words pulled by the command.
+    /// * `result_words`: Number of 32-bit words pushed back.
+    ///
+    /// # Returns
+    ///
+    /// The pushed words, e.g. the previous contents of the memory.
+    pub fn atomic(&mut self, cmd: &CppCommand, operands: Vec<u32>, result_words: u64) -> Vec<u32> {
+        let length_words = (operands.len() as u64).max(result_words);
+        self.run(cmd, length_words, Some(operands), Some(result_words))
+    }
+
+    fn run(
+        &mut self,
+        cmd: &CppCommand,
+        length_words: u64,
+        pull_data: Option<Vec<u32>>,
+        push_data_len: Option<u64>,
+    ) -> Vec<u32> {
+        if !(1..=CPP_EXPLICIT_MAX_WORDS).contains(&length_words) {
+            panic!(
+                "Explicit CPP commands transfer 1 to {} words, not {}.",
+                CPP_EXPLICIT_MAX_WORDS, length_words
+            );
+        }
+
+        let (base_addr, offset) = split_addr48(cmd.address, self.expl_bar.size());
+
+        let (sig_type, master_island, data_master, data_ref, signal_master, signal_ref) =
+            match cmd.signal {
+                CppSignal::Type(sig_type) => (Some(sig_type), None, None, None, None, None),
+                CppSignal::Master {
+                    master_island,
+                    data_master,
+                    data_ref,
+                    signal_master,
+                    signal_ref,
+                } => (
+                    None,
+                    Some(master_island),
+                    Some(data_master),
+                    Some(data_ref),
+                    Some(signal_master),
+                    Some(signal_ref),
+                ),
+            };
+
+        self.expl_bar.explicit_bar_cfg(
+            cmd.island.id(),
+            cmd.target.id(),
+            cmd.action,
+            cmd.token,
+            base_addr,
+            sig_type,
+            (length_words - 1) as u8,
+            cmd.byte_mask,
+            master_island,
+            data_master,
+            data_ref,
+            signal_master,
+            signal_ref,
+        );
+
+        // Commands that pull and push return the pushed data through the PCIe
+        // SRAM, where the pulled data was written.
+        let require_push_data_from_sram = pull_data.is_some() && push_data_len.is_some();
+        self.expl_bar
+            .run_explicit_cmd(
+                offset,
+                pull_data,
+                push_data_len,
+                require_push_data_from_sram,
+            )
+            .unwrap_or_default()
+    }
+}
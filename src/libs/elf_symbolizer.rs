@@ -0,0 +1,439 @@
+#![allow(dead_code)]
+
+use crate::libs::rfpc_trace::{TraceEvent, TraceRecord};
+use crate::libs::rv_disasm::{disassemble, instruction_length};
+use object::{Object, ObjectSection, ObjectSymbol, SectionKind, SymbolKind};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::rc::Rc;
+
+type DwarfReader = gimli::EndianRcSlice<gimli::RunTimeEndian>;
+
+// A code symbol of the ELF file.
+struct ElfSymbol {
+    address: u64,
+    size: u64,
+    name: String,
+}
+
+/// Resolves RFPC code addresses to symbols, source lines and instructions
+/// using the firmware ELF file.
+pub struct ElfSymbolizer {
+    symbols: Vec<ElfSymbol>,
+    code_sections: Vec<(u64, Vec<u8>)>,
+    lines: Option<addr2line::Context<DwarfReader>>,
+}
+
+impl ElfSymbolizer {
+    /// Loads the symbols, code and (if present) DWARF line tables of an ELF
+    /// file.
+    ///
+    /// # Parameters
+    ///
+    /// * `elf_path`: Path to the firmware ELF file.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `ElfSymbolizer`, or an error message if the file cannot be
+    /// read or parsed.
+    pub fn new(elf_path: &str) -> Result<Self, String> {
+        let data = fs::read(elf_path).map_err(|e| format!("Failed to read {}: {}", elf_path, e))?;
+        let file = object::File::parse(&*data)
+            .map_err(|e| format!("Failed to parse {}: {}", elf_path, e))?;
+
+        // Keep the contents of the code sections for disassembly.
+        let mut code_sections = Vec::new();
+        for section in file.sections() {
+            if section.kind() == SectionKind::Text {
+                if let Ok(bytes) = section.data() {
+                    code_sections.push((section.address(), bytes.to_vec()));
+                }
+            }
+        }
+
+        // Collect named code symbols, skipping local assembler labels. When
+        // several symbols share an address, functions win over plain labels.
+        let mut by_address: HashMap<u64, (ElfSymbol, bool)> = HashMap::new();
+        for symbol in file.symbols() {
+            let name = match symbol.name() {
+                Ok(name)
+                    if !name.is_empty() && !name.starts_with(".L") && !name.starts_with('$') =>
+                {
+                    name
+                }
+                _ => continue,
+            };
+            let in_code = symbol
+                .section_index()
+                .and_then(|index| file.section_by_index(index).ok())
+                .map(|section| section.kind() == SectionKind::Text)
+                .unwrap_or(false);
+            if !in_code {
+                continue;
+            }
+
+            let is_function = symbol.kind() == SymbolKind::Text;
+            let replace = match by_address.get(&symbol.address()) {
+                Some((_, existing_is_function)) => is_function && !existing_is_function,
+                None => true,
+            };
+            if replace {
+                by_address.insert(
+                    symbol.address(),
+                    (
+                        ElfSymbol {
+                            address: symbol.address(),
+                            size: symbol.size(),
+                            name: name.to_string(),
+                        },
+                        is_function,
+                    ),
+                );
+            }
+        }
+        let mut symbols: Vec<ElfSymbol> = by_address.into_values().map(|(sym, _)| sym).collect();
+        symbols.sort_by_key(|symbol| symbol.address);
+
+        let lines = Self::load_line_tables(&file);
+
+        Ok(ElfSymbolizer {
+            symbols,
+            code_sections,
+            lines,
+        })
+    }
+
+    /// Builds the DWARF line lookup context, if the ELF file has debug info.
+    fn load_line_tables(file: &object::File) -> Option<addr2line::Context<DwarfReader>> {
+        file.section_by_name(".debug_info")?;
+
+        let endian = if file.is_little_endian() {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+        let load_section = |id: gimli::SectionId| -> Result<DwarfReader, gimli::Error> {
+            let data = file
+                .section_by_name(id.name())
+                .and_then(|section| section.uncompressed_data().ok())
+                .unwrap_or(Cow::Borrowed(&[]));
+            Ok(gimli::EndianRcSlice::new(Rc::from(&*data), endian))
+        };
+
+        let dwarf = gimli::Dwarf::load(load_section).ok()?;
+        addr2line::Context::from_dwarf(dwarf).ok()
+    }
+
+    /// Resolves an address to the symbol containing it.
+    ///
+    /// # Returns
+    ///
+    /// The symbol name and the offset of `address` from the start of the
+    /// symbol, or `None` if no symbol covers the address.
+    pub fn symbol(&self, address: u64) -> Option<(&str, u64)> {
+        let index = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address)
+            .checked_sub(1)?;
+        let symbol = &self.symbols[index];
+        if symbol.size != 0 && address >= symbol.address + symbol.size {
+            return None;
+        }
+        Some((&symbol.name, address - symbol.address))
+    }
+
+    /// Resolves an address to `symbol+offset`, or the bare address if no
+    /// symbol covers it.
+    pub fn symbol_string(&self, address: u64) -> String {
+        match self.symbol(address) {
+            Some((name, 0)) => name.to_string(),
+            Some((name, offset)) => format!("{}+{:#x}", name, offset),
+            None => format!("{:#x}", address),
+        }
+    }
+
+    /// Resolves an address to its source file and line from the DWARF line
+    /// tables.
+    ///
+    /// # Returns
+    ///
+    /// `file:line`, or `None` if the ELF file has no line information for the
+    /// address.
+    pub fn source_line(&self, address: u64) -> Option<String> {
+        let location = self.lines.as_ref()?.find_location(address).ok()??;
+        let file = location.file?;
+        let file = file.rsplit('/').next().unwrap_or(file);
+        match location.line {
+            Some(line) => Some(format!("{}:{}", file, line)),
+            None => Some(file.to_string()),
+        }
+    }
+
+    /// Reads the instruction at an address from the code sections.
+    ///
+    /// # Returns
+    ///
+    /// The instruction word (only the low 16 bits are valid for compressed
+    /// instructions) and its length in bytes, or `None` if the address is
+    /// not in a code section.
+    pub fn instruction(&self, address: u64) -> Option<(u32, usize)> {
+        let (base, bytes) = self
+            .code_sections
+            .iter()
+            .find(|(base, bytes)| address >= *base && address + 2 <= *base + bytes.len() as u64)?;
+        let offset = (address - base) as usize;
+        let parcel = u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        match instruction_length(parcel) {
+            2 => Some((parcel as u32, 2)),
+            _ => {
+                let word = bytes.get(offset..offset + 4)?;
+                Some((u32::from_le_bytes(word.try_into().unwrap()), 4))
+            }
+        }
+    }
+
+    /// Returns the address range `[start, end)` spanned by the code sections,
+    /// or `None` if the ELF file has no code.
+    pub fn code_range(&self) -> Option<(u64, u64)> {
+        let start = self.code_sections.iter().map(|(base, _)| *base).min()?;
+        let end = self
+            .code_sections
+            .iter()
+            .map(|(base, bytes)| base + bytes.len() as u64)
+            .max()?;
+        Some((start, end))
+    }
+
+    /// Disassembles the instruction at an address.
+    pub fn disassemble(&self, address: u64) -> Option<String> {
+        self.instruction(address)
+            .map(|(inst, _)| disassemble(inst, address))
+    }
+}
+
+/// An instruction executed by the core, reconstructed from a decoded trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutedInstruction {
+    pub timestamp: Option<u32>,
+    pub pc: u64,
+    /// Index of the trace record the instruction was reconstructed from.
+    pub record: usize,
+}
+
+/// Returns the address of the instruction following the one at `pc`,
+/// assuming a 32-bit instruction if `pc` is not in the ELF code.
+fn next_pc(symbolizer: &ElfSymbolizer, pc: u64) -> u64 {
+    let length = symbolizer
+        .instruction(pc)
+        .map(|(_, length)| length)
+        .unwrap_or(4);
+    pc + length as u64
+}
+
+/// Reconstructs the executed instructions from decoded trace events.
+///
+/// A PC change marks the execution of the instruction at the new PC, and a
+/// sequential run of `count` instructions continues from the last executed
+/// instruction, using the instruction lengths from the ELF file. Sequential
+/// runs before the first PC change cannot be placed and are skipped.
+pub fn executed_instructions(
+    records: &[TraceRecord],
+    symbolizer: &ElfSymbolizer,
+) -> Vec<ExecutedInstruction> {
+    let mut executed = Vec::new();
+    let mut pc: Option<u64> = None;
+
+    for (index, record) in records.iter().enumerate() {
+        match record.event {
+            TraceEvent::PcChange { pc: new_pc } => {
+                pc = Some(new_pc);
+                executed.push(ExecutedInstruction {
+                    timestamp: record.timestamp,
+                    pc: new_pc,
+                    record: index,
+                });
+            }
+            TraceEvent::SequentialRun { count } => {
+                if let Some(mut cur_pc) = pc {
+                    for _ in 0..count {
+                        cur_pc = next_pc(symbolizer, cur_pc);
+                        executed.push(ExecutedInstruction {
+                            timestamp: record.timestamp,
+                            pc: cur_pc,
+                            record: index,
+                        });
+                    }
+                    pc = Some(cur_pc);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    executed
+}
+
+/// Formats decoded trace events as an annotated execution listing.
+///
+/// Every instruction of `executed_instructions()` is shown with its
+/// timestamp, address, `symbol+offset`, source line (when DWARF is present)
+/// and disassembly. Register writes and breakpoints are listed in between,
+/// in trace order.
+///
+/// # Returns
+///
+/// Returns a `Vec<String>` with one line per instruction or event.
+pub fn format_annotated_trace(records: &[TraceRecord], symbolizer: &ElfSymbolizer) -> Vec<String> {
+    let instruction_line = |inst: &ExecutedInstruction| {
+        let ts = match inst.timestamp {
+            Some(ts) => ts.to_string(),
+            None => "-".to_string(),
+        };
+        format!(
+            "{:>10}  {:#014x}  {:<32} {:<24} {}",
+            ts,
+            inst.pc,
+            symbolizer.symbol_string(inst.pc),
+            symbolizer.source_line(inst.pc).unwrap_or_default(),
+            symbolizer.disassemble(inst.pc).unwrap_or_default()
+        )
+    };
+
+    let executed = executed_instructions(records, symbolizer);
+    let mut executed = executed.iter().peekable();
+    let mut lines = Vec::new();
+
+    for (index, record) in records.iter().enumerate() {
+        while let Some(inst) = executed.next_if(|inst| inst.record == index) {
+            lines.push(instruction_line(inst));
+        }
+        match &record.event {
+            TraceEvent::RegisterWrite { reg, value } => {
+                lines.push(format!("{:>28}x{} <= {:#018x}", "", reg, value));
+            }
+            TraceEvent::Breakpoint { pc: bkpt_pc } => {
+                let location = bkpt_pc
+                    .map(|bkpt_pc| format!(" at {}", symbolizer.symbol_string(bkpt_pc)))
+                    .unwrap_or_default();
+                lines.push(format!("{:>28}breakpoint{}", "", location));
+            }
+            _ => (),
+        }
+    }
+
+    lines
+}
+
+/// Counts the executed instructions per function.
+///
+/// # Returns
+///
+/// A list of `(function, instructions executed)` pairs, most executed first.
+/// Instructions outside any symbol are counted under `<unknown>`.
+pub fn function_hit_counts(
+    records: &[TraceRecord],
+    symbolizer: &ElfSymbolizer,
+) -> Vec<(String, u64)> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for inst in executed_instructions(records, symbolizer) {
+        let name = match symbolizer.symbol(inst.pc) {
+            Some((name, _)) => name.to_string(),
+            None => "<unknown>".to_string(),
+        };
+        *counts.entry(name).or_insert(0) += 1;
+    }
+
+    let mut counts: Vec<(String, u64)> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ELF_PATH: &str = "test_files/rfpc_perf.i9.g0";
+
+    fn record(timestamp: u32, event: TraceEvent) -> TraceRecord {
+        TraceRecord {
+            timestamp: Some(timestamp),
+            event,
+        }
+    }
+
+    #[test]
+    fn symbol_lookup() {
+        let symbolizer = ElfSymbolizer::new(ELF_PATH).unwrap();
+        assert_eq!(symbolizer.symbol(0x450), Some(("ctm_write32_arr", 0)));
+        assert_eq!(symbolizer.symbol(0x45c), Some(("ctm_write32_arr", 0xc)));
+        assert_eq!(symbolizer.symbol_string(0x450), "ctm_write32_arr");
+        assert_eq!(symbolizer.symbol_string(0x45c), "ctm_write32_arr+0xc");
+        // ctm_write32_arr is 184 bytes long.
+        assert_ne!(symbolizer.symbol(0x450 + 184).unwrap().0, "ctm_write32_arr");
+    }
+
+    #[test]
+    fn source_line_lookup() {
+        let symbolizer = ElfSymbolizer::new(ELF_PATH).unwrap();
+        assert_eq!(
+            symbolizer.source_line(0x450).as_deref(),
+            Some("rfpc_env.c:24")
+        );
+        assert_eq!(
+            symbolizer.source_line(0x468).as_deref(),
+            Some("rfpc_env.c:28")
+        );
+        assert_eq!(
+            symbolizer.source_line(0x478).as_deref(),
+            Some("rfpc_env.c:29")
+        );
+    }
+
+    #[test]
+    fn instruction_lookup() {
+        let symbolizer = ElfSymbolizer::new(ELF_PATH).unwrap();
+        assert_eq!(symbolizer.instruction(0x450), Some((0x0000_1297, 4)));
+        assert_eq!(symbolizer.instruction(0x458), Some((0x0006_8a13, 4)));
+        assert_eq!(
+            symbolizer.disassemble(0x454).as_deref(),
+            Some("jalr    t0,-928(t0)")
+        );
+        assert_eq!(
+            symbolizer.disassemble(0x458).as_deref(),
+            Some("mv      s4,a3")
+        );
+        assert_eq!(symbolizer.instruction(0x1_0000_0000), None);
+    }
+
+    #[test]
+    fn annotated_trace_interleaves_events() {
+        let symbolizer = ElfSymbolizer::new(ELF_PATH).unwrap();
+        let records = [
+            record(1, TraceEvent::SequentialRun { count: 3 }),
+            record(2, TraceEvent::PcChange { pc: 0x450 }),
+            record(
+                3,
+                TraceEvent::RegisterWrite {
+                    reg: 5,
+                    value: 0x1450,
+                },
+            ),
+            record(4, TraceEvent::SequentialRun { count: 2 }),
+            record(5, TraceEvent::Breakpoint { pc: Some(0x458) }),
+        ];
+
+        let executed = executed_instructions(&records, &symbolizer);
+        let pcs: Vec<(u64, usize)> = executed.iter().map(|inst| (inst.pc, inst.record)).collect();
+        // The run before the first PC change cannot be placed.
+        assert_eq!(pcs, [(0x450, 1), (0x454, 3), (0x458, 3)]);
+
+        let lines = format_annotated_trace(&records, &symbolizer);
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].contains("ctm_write32_arr ") && lines[0].contains("auipc"));
+        assert!(lines[1].ends_with("x5 <= 0x0000000000001450"));
+        assert!(lines[2].contains("ctm_write32_arr+0x4") && lines[2].contains("jalr"));
+        assert!(lines[3].contains("ctm_write32_arr+0x8") && lines[3].contains("mv"));
+        assert!(lines[4].ends_with("breakpoint at ctm_write32_arr+0x8"));
+    }
+}
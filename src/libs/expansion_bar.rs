@@ -1,12 +1,17 @@
 #![allow(dead_code)]
 
+use crate::libs::bar_lock::acquire_lock;
+use crate::libs::bar_pool::{BarLease, BarPool};
 use bytemuck::cast_slice;
 use fs2::FileExt;
 use memmap2::{MmapMut, MmapOptions};
+use std::collections::BTreeMap;
 use std::fmt;
 use std::fs::{self, File, OpenOptions};
 use std::hint::black_box;
 use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
 
 // Base address of PCIe2CPP BAR CSRs.
 const BAR_CONFIG_BASE_PCIE_INTERNAL: u32 = 0x30000; // When accessed by PCIe internal target.
@@ -21,9 +26,11 @@ const EXPANSION_BAR_CSR_OFFSET: u32 = 0x8;
 // Physical BAR for CPP transactions.
 // Physical BARs 0 and 1 are reserved for the NSP and application firmware.
 // For debugging, we may only configure physical BAR 2 to avoid any conflict.
-const CPP_EXPANSION_BAR_PHYSICAL_BAR: u32 = 2;
+pub const CPP_EXPANSION_BAR_PHYSICAL_BAR: u32 = 2;
 // Maximum number of expansion BARs.
-const CPP_MAX_NUM_EXPANSION_BARS: u32 = 8;
+pub const CPP_MAX_NUM_EXPANSION_BARS: u32 = 8;
+// Number of physical BARs with expansion BARs.
+pub const CPP_NUM_PHYSICAL_BARS: u32 = 3;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum MapType {
@@ -34,6 +41,339 @@ pub enum MapType {
     Explicit,
 }
 
+impl MapType {
+    /// Decodes the map type field of an expansion BAR CSR.
+    ///
+    /// # Returns
+    ///
+    /// `None` for a reserved encoding.
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            0 => Some(MapType::Fixed),
+            1 => Some(MapType::Bulk),
+            2 => Some(MapType::Target),
+            3 => Some(MapType::General),
+            4 => Some(MapType::Explicit),
+            _ => None,
+        }
+    }
+}
+
+/// Configuration of an expansion BAR, decoded from its two CSRs.
+///
+/// Depending on the map type, the CPP target, token and action fields of
+/// the CSRs hold upper base address bits instead, in which case they are
+/// `None` here and included in `base_addr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpBarConfig {
+    pub enabled: bool,
+    /// `None` for a reserved map type encoding.
+    pub map_type: Option<MapType>,
+    pub island: u8,
+    pub target: Option<u8>,
+    pub token: Option<u8>,
+    pub action: Option<u8>,
+    pub cpp_len: u8,
+    pub base_addr: u64,
+}
+
+impl ExpBarConfig {
+    /// Decodes the CSR values written by `ExpansionBar::expansion_bar_cfg`.
+    pub fn decode(cfg0: u32, cfg1: u32) -> Self {
+        let map_type = MapType::from_id((cfg0 >> 20) & 0x7);
+        let target = ((cfg0 >> 12) & 0xF) as u8;
+        let token = ((cfg0 >> 8) & 0x3) as u8;
+        let action = (cfg0 & 0x3F) as u8;
+        let cfg1 = cfg1 as u64;
+
+        let (target, token, action, base_addr) = match map_type {
+            Some(MapType::Fixed) => (Some(target), Some(token), Some(action), cfg1 << 16),
+            Some(MapType::Bulk) => (
+                Some(target),
+                Some(token),
+                None,
+                ((action as u64) << 42) | (cfg1 << 10),
+            ),
+            Some(MapType::Target) => (
+                Some(target),
+                None,
+                None,
+                ((token as u64) << 46) | ((action as u64) << 40) | (cfg1 << 8),
+            ),
+            Some(MapType::General) => (
+                None,
+                None,
+                None,
+                ((target as u64) << 44)
+                    | ((token as u64) << 42)
+                    | ((action as u64) << 36)
+                    | (cfg1 << 4),
+            ),
+            Some(MapType::Explicit) | None => (None, None, None, 0),
+        };
+
+        ExpBarConfig {
+            enabled: cfg0 & (1 << 31) != 0,
+            map_type,
+            island: ((cfg0 >> 24) & 0x7F) as u8,
+            target,
+            token,
+            action,
+            cpp_len: ((cfg0 >> 16) & 0x3) as u8,
+            base_addr,
+        }
+    }
+}
+
+impl fmt::Display for ExpBarConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.enabled {
+            return write!(f, "disabled");
+        }
+        match self.map_type {
+            Some(MapType::Explicit) => return write!(f, "Explicit"),
+            Some(map_type) => write!(f, "{:?} island {}", map_type, self.island)?,
+            None => return write!(f, "reserved map type"),
+        }
+        if let Some(target) = self.target {
+            write!(f, " target {}", target)?;
+        }
+        if let Some(action) = self.action {
+            write!(f, " action {}", action)?;
+        }
+        if let Some(token) = self.token {
+            write!(f, " token {}", token)?;
+        }
+        write!(f, " len {} base {:#014x}", self.cpp_len, self.base_addr)
+    }
+}
+
+/// Mapping of the PCIe2CPP BAR CSRs in BAR0 (`resource0`) of a device.
+///
+/// Mapping BAR0 takes several syscalls, so it is mapped once per device and
+/// shared by all expansion and explicit BARs of the process, see `get()`.
+pub struct Bar0Csr {
+    pci_bdf: String,
+    mmap: Mutex<MmapMut>,
+}
+
+// BAR0 CSR mappings of the devices used by this process. A failed mapping is
+// remembered as the kind and message of its error, so it is not retried.
+type Bar0Mapping = Result<Arc<Bar0Csr>, (io::ErrorKind, String)>;
+static BAR0_CSRS: Mutex<BTreeMap<String, Bar0Mapping>> = Mutex::new(BTreeMap::new());
+
+impl Bar0Csr {
+    /// Returns the shared BAR0 CSR mapping of a device, mapping BAR0 on first
+    /// use. Only the first use tries to map BAR0, later ones return the same
+    /// mapping or error.
+    ///
+    /// # Returns
+    ///
+    /// The mapping, or the error of mapping `resource0`.
+    pub fn get(pci_bdf: &str) -> io::Result<Arc<Self>> {
+        let mut bar0_csrs = BAR0_CSRS.lock().unwrap_or_else(PoisonError::into_inner);
+        let bar0_csr = bar0_csrs.entry(pci_bdf.to_string()).or_insert_with(|| {
+            Self::map(pci_bdf)
+                .map(Arc::new)
+                .map_err(|e| (e.kind(), e.to_string()))
+        });
+        match bar0_csr {
+            Ok(bar0_csr) => Ok(bar0_csr.clone()),
+            Err((kind, message)) => Err(io::Error::new(*kind, message.clone())),
+        }
+    }
+
+    fn map(pci_bdf: &str) -> io::Result<Self> {
+        let phys_bar_path = format!("/sys/bus/pci/devices/{}/resource0", pci_bdf);
+
+        let metadata = fs::metadata(&phys_bar_path)?;
+        let csr_size = metadata.len() / 8;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true) // Open the file in read-write mode
+            .open(&phys_bar_path)?;
+
+        // The mapping stays valid after the file is closed.
+        let mmap = unsafe {
+            MmapOptions::new()
+                .offset(0)
+                .len(csr_size as usize)
+                .map_mut(&file)?
+        };
+
+        Ok(Bar0Csr {
+            pci_bdf: pci_bdf.to_string(),
+            mmap: Mutex::new(mmap),
+        })
+    }
+
+    pub fn pci_bdf(&self) -> &str {
+        &self.pci_bdf
+    }
+
+    /// Reads consecutive 32-bit CSRs.
+    pub fn read(&self, offset: u32, count: usize) -> Vec<u32> {
+        let mmap = self.mmap.lock().unwrap_or_else(PoisonError::into_inner);
+        let offset = offset as usize;
+        cast_slice(&mmap[offset..offset + 4 * count]).to_vec()
+    }
+
+    /// Writes consecutive 32-bit CSRs in order, reading back every CSR after
+    /// writing it.
+    pub fn write(&self, offset: u32, values: &[u32]) {
+        let mut mmap = self.mmap.lock().unwrap_or_else(PoisonError::into_inner);
+        for (i, value) in values.iter().enumerate() {
+            let offset = offset as usize + 4 * i;
+
+            // Write the CSR into the mmap region.
+            mmap[offset..offset + 4].copy_from_slice(cast_slice(&[*value]));
+
+            // Read back the CSR to prevent optimization.
+            let _cfg_bytes = mmap[offset..offset + 4].to_vec();
+            black_box(_cfg_bytes);
+        }
+    }
+}
+
+/// How the PCIe2CPP BAR CSRs are accessed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BarConfigMethod {
+    /// Through the mapping of BAR0 (`resource0`), see `Bar0Csr`.
+    Bar0Csr,
+    /// Through the config snoop interface in the PCIe config space
+    /// (`config`), which works where BAR0 cannot be mapped, e.g. in
+    /// restricted containers, but takes a syscall per CSR.
+    ConfigSnoop,
+}
+
+impl fmt::Display for BarConfigMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BarConfigMethod::Bar0Csr => write!(f, "BAR0 CSR"),
+            BarConfigMethod::ConfigSnoop => write!(f, "config snoop"),
+        }
+    }
+}
+
+/// Access to the PCIe2CPP BAR CSRs of a device with a `BarConfigMethod`.
+#[derive(Clone)]
+pub enum BarCsrAccess {
+    Bar0Csr(Arc<Bar0Csr>),
+    ConfigSnoop { pci_bdf: String },
+}
+
+impl BarCsrAccess {
+    /// Opens the BAR CSRs of a device.
+    ///
+    /// # Parameters
+    ///
+    /// * `pci_bdf`: PCIe BDF of the NFP.
+    /// * `method`: Access method, or `None` to use the BAR0 mapping and fall
+    ///   back to the config snoop interface if BAR0 cannot be mapped.
+    ///
+    /// # Returns
+    ///
+    /// The access, or an error message if BAR0 was requested and cannot be
+    /// mapped.
+    pub fn open(pci_bdf: &str, method: Option<BarConfigMethod>) -> Result<Self, String> {
+        let config_snoop = || BarCsrAccess::ConfigSnoop {
+            pci_bdf: pci_bdf.to_string(),
+        };
+        if method == Some(BarConfigMethod::ConfigSnoop) {
+            return Ok(config_snoop());
+        }
+        match (method, Bar0Csr::get(pci_bdf)) {
+            (_, Ok(bar0_csr)) => Ok(BarCsrAccess::Bar0Csr(bar0_csr)),
+            (None, Err(_)) => Ok(config_snoop()),
+            (_, Err(e)) => Err(format!("Failed to map BAR0 of {}: {}", pci_bdf, e)),
+        }
+    }
+
+    pub fn method(&self) -> BarConfigMethod {
+        match self {
+            BarCsrAccess::Bar0Csr(_) => BarConfigMethod::Bar0Csr,
+            BarCsrAccess::ConfigSnoop { .. } => BarConfigMethod::ConfigSnoop,
+        }
+    }
+
+    /// Reads consecutive 32-bit CSRs at an offset in the BAR config space.
+    pub fn read(&self, offset: u32, count: usize) -> Vec<u32> {
+        match self {
+            BarCsrAccess::Bar0Csr(bar0_csr) => bar0_csr.read(offset, count),
+            BarCsrAccess::ConfigSnoop { pci_bdf } => {
+                let (mut pcie_cfg_file, pcie_cfg_path) = open_config_snoop(pci_bdf, offset);
+                let mut cfg_bytes = vec![0u8; 4 * count];
+                pcie_cfg_file
+                    .read_exact(&mut cfg_bytes)
+                    .expect(&format!("File {} read failed", &pcie_cfg_path));
+                cfg_bytes
+                    .chunks_exact(4)
+                    .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                    .collect()
+            }
+        }
+    }
+
+    /// Writes consecutive 32-bit CSRs in order at an offset in the BAR config
+    /// space.
+    pub fn write(&self, offset: u32, values: &[u32]) {
+        match self {
+            BarCsrAccess::Bar0Csr(bar0_csr) => bar0_csr.write(offset, values),
+            BarCsrAccess::ConfigSnoop { pci_bdf } => {
+                let (mut pcie_cfg_file, pcie_cfg_path) = open_config_snoop(pci_bdf, offset);
+                // Write using little-endian format
+                for value in values {
+                    pcie_cfg_file
+                        .write_all(&value.to_le_bytes())
+                        .expect(&format!("File {} write failed", &pcie_cfg_path));
+                }
+            }
+        }
+    }
+}
+
+// Opens the PCIe config space of a device, positioned at an offset in the BAR
+// config space seen through the config snoop interface.
+fn open_config_snoop(pci_bdf: &str, offset: u32) -> (File, String) {
+    let pcie_cfg_path = format!("/sys/bus/pci/devices/{}/config", pci_bdf);
+
+    let mut pcie_cfg_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&pcie_cfg_path)
+        .expect(&format!("Failed to open file {}", &pcie_cfg_path));
+
+    pcie_cfg_file
+        .seek(SeekFrom::Start(
+            (BAR_CONFIG_BASE_CONFIG_SNOOP + offset) as u64,
+        ))
+        .expect(&format!("File {} seek failed", pcie_cfg_path));
+
+    (pcie_cfg_file, pcie_cfg_path)
+}
+
+// Offset of the CSRs of an expansion BAR in the BAR config space.
+fn exp_bar_csr_offset(phys_bar: u8, exp_bar: u8) -> u32 {
+    EXPANSION_BAR_BASE_OFFSET
+        + ((phys_bar as u32) * EXPANSION_BAR_PHYS_OFFSET)
+        + ((exp_bar as u32) * EXPANSION_BAR_CSR_OFFSET)
+}
+
+/// Reads the configuration of an expansion BAR from its CSRs, through BAR0 or
+/// the config snoop interface if BAR0 cannot be mapped.
+///
+/// # Parameters
+///
+/// * `pci_bdf`: PCIe BDF of the NFP.
+/// * `phys_bar`: Physical BAR of the expansion BAR.
+/// * `exp_bar`: Index of the expansion BAR in the physical BAR.
+pub fn read_exp_bar_config(pci_bdf: &str, phys_bar: u8, exp_bar: u8) -> ExpBarConfig {
+    let csr_access = BarCsrAccess::open(pci_bdf, None).unwrap();
+    let cfg = csr_access.read(exp_bar_csr_offset(phys_bar, exp_bar), 2);
+    ExpBarConfig::decode(cfg[0], cfg[1])
+}
+
 pub fn init_device_bars(pci_bdf: &str) {
     let pcie_cfg_path = format!("/sys/bus/pci/devices/{}/config", pci_bdf);
     let mut pcie_cfg_file = OpenOptions::new()
@@ -57,6 +397,17 @@ pub fn init_device_bars(pci_bdf: &str) {
         .expect(&format!("File {} write failed", &pcie_cfg_path));
 }
 
+/// Returns the default directory of the expansion BAR lock files of a PCIe
+/// device, `/var/run/nfp_tools/<bdf>`.
+pub fn default_lock_dir(pci_bdf: &str) -> PathBuf {
+    PathBuf::from(format!("/var/run/nfp_tools/{}", pci_bdf))
+}
+
+/// Returns the path of the lock file of an expansion BAR in a lock directory.
+pub fn exp_bar_lock_path(lock_dir: &Path, phys_bar: u8, exp_bar: u8) -> PathBuf {
+    lock_dir.join(format!("exp_bar{}-{}_lock", phys_bar, exp_bar))
+}
+
 pub struct ExpansionBar {
     pci_bdf: String,
     phys_bar: u8,
@@ -69,27 +420,45 @@ pub struct ExpansionBar {
     lock_file: File,
     mmap_file: Option<File>,
     mmap_region: Option<MmapMut>,
+    csr_access: BarCsrAccess,
 }
 
 impl ExpansionBar {
     pub fn new(pci_bdf_str: &str, bar_mapping: Option<(u8, u8)>) -> Self {
-        let (phys_bar, exp_bar, lock_file) = if let Some(bar_map) = bar_mapping {
-            let lock_file_dir = format!("/var/run/nfp_tools/{}", pci_bdf_str);
-            let lock_file_name = format!("exp_bar{}-{}_lock", bar_map.0, bar_map.1);
-            let full_path = format!("{}/{}", lock_file_dir, lock_file_name);
-
-            match Self::acquire_lock_file(&full_path) {
-                Ok(file) => {
-                    let (phys_bar, exp_bar) = bar_map;
-                    (phys_bar, exp_bar, file)
-                }
+        let lock_dir = default_lock_dir(pci_bdf_str);
+        if let Some((phys_bar, exp_bar)) = bar_mapping {
+            match Self::try_new(pci_bdf_str, &lock_dir, phys_bar, exp_bar) {
+                Ok(bar) => bar,
                 Err(_) => {
-                    panic!("exp_bar{}-{} should not be locked!", bar_map.0, bar_map.1);
+                    panic!("exp_bar{}-{} should not be locked!", phys_bar, exp_bar);
                 }
             }
         } else {
-            Self::allocate_exp_bar(pci_bdf_str)
-        };
+            Self::allocate_exp_bar(pci_bdf_str, &lock_dir)
+        }
+    }
+
+    /// Locks and maps a specific expansion BAR. The BAR is configured through
+    /// BAR0, or through the config snoop interface if BAR0 cannot be mapped.
+    ///
+    /// # Parameters
+    ///
+    /// * `pci_bdf_str`: PCIe BDF of the NFP.
+    /// * `lock_dir`: Directory of the lock files, which must exist.
+    /// * `phys_bar`: Physical BAR of the expansion BAR.
+    /// * `exp_bar`: Index of the expansion BAR in the physical BAR.
+    ///
+    /// # Returns
+    ///
+    /// The expansion BAR, or the error of locking its lock file, e.g.
+    /// `WouldBlock` if it is locked by another user.
+    pub fn try_new(
+        pci_bdf_str: &str,
+        lock_dir: &Path,
+        phys_bar: u8,
+        exp_bar: u8,
+    ) -> io::Result<Self> {
+        let lock_file = acquire_lock(&exp_bar_lock_path(lock_dir, phys_bar, exp_bar))?;
 
         let phys_bar_path = format!(
             "/sys/bus/pci/devices/{}/{}",
@@ -117,7 +486,7 @@ impl ExpansionBar {
                 .expect("Failed to map expansion BAR region")
         };
 
-        ExpansionBar {
+        Ok(ExpansionBar {
             pci_bdf: pci_bdf_str.to_string(),
             phys_bar,
             phys_bar_path,
@@ -129,113 +498,68 @@ impl ExpansionBar {
             lock_file,
             mmap_file: Some(file),
             mmap_region: Some(mmap),
-        }
+            csr_access: BarCsrAccess::open(pci_bdf_str, None).unwrap(),
+        })
     }
 
-    fn acquire_lock_file(lock_path: &str) -> io::Result<File> {
-        // Attempt to create and open the file
-        let lock_file = File::create(lock_path)?;
-
-        // Attempt to lock the file (using an exclusive lock)
-        lock_file.try_lock_exclusive()?;
-
-        Ok(lock_file)
+    /// Locks and maps the first free expansion BAR of physical BAR 2.
+    ///
+    /// # Parameters
+    ///
+    /// * `pci_bdf`: PCIe BDF of the NFP.
+    /// * `lock_dir`: Directory of the lock files, created if needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics with the holders of the BARs if all of them are taken.
+    pub fn allocate_exp_bar(pci_bdf: &str, lock_dir: &Path) -> Self {
+        Self::try_allocate_exp_bar(pci_bdf, lock_dir).unwrap_or_else(|e| panic!("{}", e))
     }
 
-    fn allocate_exp_bar(pci_bdf: &str) -> (u8, u8, File) {
-        let lock_file_dir = format!("/var/run/nfp_tools/{}", pci_bdf);
-        fs::create_dir_all(&lock_file_dir)
-            .expect(&format!("Failed to create dir {}", &lock_file_dir));
-
-        let mut bar_locks: Vec<(u8, u8, String)> = Vec::new();
-        for exp_bar in 0..CPP_MAX_NUM_EXPANSION_BARS {
-            let lock_file = format!("exp_bar{}-{}_lock", CPP_EXPANSION_BAR_PHYSICAL_BAR, exp_bar);
-            let full_path = format!("{}/{}", lock_file_dir, lock_file);
-            // Attempt to create the file only if it does not already exist
-            bar_locks.push((
-                CPP_EXPANSION_BAR_PHYSICAL_BAR as u8,
-                exp_bar as u8,
-                full_path,
-            ));
-        }
-
-        for (phys_bar, exp_bar, lock_path) in bar_locks {
-            match Self::acquire_lock_file(&lock_path) {
-                Ok(file) => return (phys_bar, exp_bar, file),
-                Err(_) => {
-                    // Continue to next lock if this one fails
-                }
-            }
-        }
-
-        panic!("No expansion BARs available!");
+    /// Locks and maps the first free expansion BAR of physical BAR 2, like
+    /// `allocate_exp_bar()`.
+    ///
+    /// # Returns
+    ///
+    /// The expansion BAR, or an error message listing the holders of the BARs
+    /// if all of them are taken.
+    pub fn try_allocate_exp_bar(pci_bdf: &str, lock_dir: &Path) -> Result<Self, String> {
+        BarPool::with_lock_dir(pci_bdf, lock_dir)
+            .try_lease()
+            .map(BarLease::into_bar)
     }
 
-    fn exp_bar_config_write(&self, cfg_reg0: u32, cfg_reg1: u32) {
-        let phys_bar_path = format!("/sys/bus/pci/devices/{}/resource0", self.pci_bdf);
-
-        let metadata = fs::metadata(&phys_bar_path).expect("Error getting file metadata!");
-        let phys_bar_size = metadata.len() as u64;
-        let exp_bar_size = phys_bar_size / 8;
-
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true) // Open the file in read-write mode
-            .open(&phys_bar_path)
-            .expect("Failed to open mmap file in read-write mode");
-
-        let mut mmap = unsafe {
-            MmapOptions::new()
-                .offset(0)
-                .len(exp_bar_size as usize)
-                .map_mut(&file)
-                .expect("Failed to map expansion BAR region")
-        };
-
-        let offset = EXPANSION_BAR_BASE_OFFSET
-            + ((self.phys_bar as u32) * EXPANSION_BAR_PHYS_OFFSET)
-            + ((self.exp_bar as u32) * EXPANSION_BAR_CSR_OFFSET);
-
-        // Write cfg_reg0 into mmap region
-        mmap[offset as usize..(offset + 4) as usize].copy_from_slice(cast_slice(&[cfg_reg0]));
-
-        // Read back cfg_reg0 to prevent optimization
-        let _cfg_bytes = mmap[offset as usize..(offset + 4) as usize].to_vec();
-        black_box(_cfg_bytes);
-
-        // Write cfg_reg1 into mmap region
-        mmap[(offset + 4) as usize..(offset + 8) as usize].copy_from_slice(cast_slice(&[cfg_reg1]));
+    /// Returns the physical BAR and the expansion BAR index.
+    pub fn bar_mapping(&self) -> (u8, u8) {
+        (self.phys_bar, self.exp_bar)
+    }
 
-        // Read back cfg_reg1 to prevent optimization
-        let _cfg_bytes = mmap[(offset + 4) as usize..(offset + 8) as usize].to_vec();
-        black_box(_cfg_bytes);
+    /// Returns how the BAR CSRs are accessed.
+    pub fn config_method(&self) -> BarConfigMethod {
+        self.csr_access.method()
     }
 
-    fn exp_bar_config_write_via_snoop(&self, cfg_reg0: u32, cfg_reg1: u32) {
-        let pcie_cfg_path = format!("/sys/bus/pci/devices/{}/config", &self.pci_bdf);
+    /// Selects how the BAR CSRs are accessed.
+    ///
+    /// # Returns
+    ///
+    /// An error message if `BarConfigMethod::Bar0Csr` is selected and BAR0
+    /// cannot be mapped.
+    pub fn set_config_method(&mut self, method: BarConfigMethod) -> Result<(), String> {
+        self.csr_access = BarCsrAccess::open(&self.pci_bdf, Some(method))?;
+        Ok(())
+    }
 
-        let exp_bar_csr_addr = BAR_CONFIG_BASE_CONFIG_SNOOP
-            + EXPANSION_BAR_BASE_OFFSET
-            + (self.phys_bar as u32) * EXPANSION_BAR_PHYS_OFFSET
-            + (self.exp_bar as u32) * EXPANSION_BAR_CSR_OFFSET;
+    /// Returns the access to the BAR CSRs of the device.
+    pub fn csr_access(&self) -> &BarCsrAccess {
+        &self.csr_access
+    }
 
-        let mut pcie_cfg_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(&pcie_cfg_path)
-            .expect(&format!("Failed to open file {}", &pcie_cfg_path));
-
-        pcie_cfg_file
-            .seek(SeekFrom::Start(exp_bar_csr_addr as u64))
-            .expect(&format!("File {} seek failed", pcie_cfg_path));
-
-        // Write using little-endian format
-        pcie_cfg_file
-            .write_all(&cfg_reg0.to_le_bytes())
-            .expect(&format!("File {} write failed", &pcie_cfg_path));
-        pcie_cfg_file
-            .write_all(&cfg_reg1.to_le_bytes())
-            .expect(&format!("File {} write failed", &pcie_cfg_path));
+    fn exp_bar_config_write(&self, cfg_reg0: u32, cfg_reg1: u32) {
+        self.csr_access.write(
+            exp_bar_csr_offset(self.phys_bar, self.exp_bar),
+            &[cfg_reg0, cfg_reg1],
+        );
     }
 
     pub fn expansion_bar_cfg(
@@ -371,7 +695,7 @@ impl ExpansionBar {
 impl Drop for ExpansionBar {
     fn drop(&mut self) {
         // Unlock the file (using a blocking lock to ensure proper unlocking).
-        let _ = self.lock_file.unlock();
+        let _ = FileExt::unlock(&self.lock_file);
         self.mmap_file = None;
         self.mmap_region = None;
     }
@@ -1,14 +1,16 @@
 #![allow(dead_code)]
 
+use crate::libs::bar_lock::acquire_lock;
 use crate::libs::cpp_bus::{CppIsland, CppLength};
-use crate::libs::expansion_bar::{ExpansionBar, MapType};
+use crate::libs::expansion_bar::{default_lock_dir, ExpansionBar, MapType};
 use bytemuck::cast_slice;
-use memmap2::MmapOptions;
-use std::fs::{self, OpenOptions};
-use std::hint::black_box;
+use fs2::FileExt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
 
 // Number of explicit command BARs per PF.
-const NUM_EXPL_BARS: u32 = 4;
+pub const NUM_EXPL_BARS: u32 = 4;
 
 // Offset of explicit command BAR CSRs from PCIe BAR config base address.
 const EXPL_BAR_BASE_OFFSET: u32 = 0x180;
@@ -28,16 +30,86 @@ pub struct ExplicitBar {
     trigger_exp_bar: ExpansionBar,
     data_exp_bar: ExpansionBar,
     expl_bar_cached_cfg: [u32; 4],
+    lock_file: File,
+}
+
+/// Returns the path of the lock file of an explicit command BAR in a lock
+/// directory.
+pub fn expl_bar_lock_path(lock_dir: &Path, expl_bar_index: u32) -> PathBuf {
+    lock_dir.join(format!("expl_bar{}_lock", expl_bar_index))
 }
 
 impl ExplicitBar {
-    pub fn new(pci_bdf_str: &str, expl_bar_index: u32) -> Self {
-        let mut trigger_exp_bar = ExpansionBar::new(pci_bdf_str, None);
+    /// Locks an explicit command BAR and the two expansion BARs it needs,
+    /// with the lock files in `/var/run/nfp_tools/<bdf>`.
+    ///
+    /// # Parameters
+    ///
+    /// * `pci_bdf_str`: PCIe BDF of the NFP.
+    /// * `expl_bar_index`: Index of the explicit BAR, or `None` to use the
+    ///   first free one.
+    pub fn new(pci_bdf_str: &str, expl_bar_index: Option<u32>) -> Self {
+        let lock_dir = default_lock_dir(pci_bdf_str);
+        fs::create_dir_all(&lock_dir)
+            .unwrap_or_else(|e| panic!("Failed to create dir {}: {}", lock_dir.display(), e));
+
+        if let Some(expl_bar_index) = expl_bar_index {
+            match Self::try_new(pci_bdf_str, &lock_dir, expl_bar_index) {
+                Ok(bar) => bar,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    panic!("expl_bar{} should not be locked!", expl_bar_index);
+                }
+                Err(e) => panic!("Failed to open expl_bar{}: {}", expl_bar_index, e),
+            }
+        } else {
+            for expl_bar_index in 0..NUM_EXPL_BARS {
+                match Self::try_new(pci_bdf_str, &lock_dir, expl_bar_index) {
+                    Ok(bar) => return bar,
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                    Err(e) => panic!("Failed to open expl_bar{}: {}", expl_bar_index, e),
+                }
+            }
+            panic!("No explicit BARs available!");
+        }
+    }
+
+    /// Locks a specific explicit command BAR, then locks and configures the
+    /// two expansion BARs it needs: one to trigger commands and one to access
+    /// its data area in the PCIe SRAM.
+    ///
+    /// # Parameters
+    ///
+    /// * `pci_bdf_str`: PCIe BDF of the NFP.
+    /// * `lock_dir`: Directory of the lock files, which must exist.
+    /// * `expl_bar_index`: Index of the explicit BAR.
+    ///
+    /// # Returns
+    ///
+    /// The explicit BAR, or an error: `InvalidInput` for an index of
+    /// `NUM_EXPL_BARS` or more, the error of locking its lock file, e.g.
+    /// `WouldBlock` if it is locked by another user, or an error listing the
+    /// holders of the expansion BARs if fewer than two of them are free.
+    pub fn try_new(pci_bdf_str: &str, lock_dir: &Path, expl_bar_index: u32) -> io::Result<Self> {
+        if expl_bar_index >= NUM_EXPL_BARS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Invalid explicit BAR index {}, expected 0 to {}",
+                    expl_bar_index,
+                    NUM_EXPL_BARS - 1
+                ),
+            ));
+        }
+        let lock_file = acquire_lock(&expl_bar_lock_path(lock_dir, expl_bar_index))?;
+
+        let mut trigger_exp_bar =
+            ExpansionBar::try_allocate_exp_bar(pci_bdf_str, lock_dir).map_err(io::Error::other)?;
         trigger_exp_bar.exp_bar_map = MapType::Explicit;
         // All fields are ignored when configuring the Explicit Bar.
         // The only relevant field is the MapType.
         trigger_exp_bar.expansion_bar_cfg(0, 0, 0, 0, 0, 0);
-        let mut data_exp_bar = ExpansionBar::new(pci_bdf_str, None);
+        let mut data_exp_bar =
+            ExpansionBar::try_allocate_exp_bar(pci_bdf_str, lock_dir).map_err(io::Error::other)?;
         data_exp_bar.exp_bar_map = MapType::General;
         data_exp_bar.expansion_bar_cfg(
             CppIsland::Local.id(),
@@ -48,13 +120,19 @@ impl ExplicitBar {
             CppLength::Len32.id(),
         );
 
-        ExplicitBar {
+        Ok(ExplicitBar {
             pci_bdf: pci_bdf_str.to_string(),
             expl_bar_index,
             trigger_exp_bar,
             data_exp_bar,
             expl_bar_cached_cfg: [0; 4],
-        }
+            lock_file,
+        })
+    }
+
+    /// Returns the index of the explicit BAR.
+    pub fn index(&self) -> u32 {
+        self.expl_bar_index
     }
 
     pub fn expa_bar_offset(&self) -> u64 {
@@ -74,57 +152,10 @@ impl ExplicitBar {
     }
 
     fn expl_bar_config_write(&self, cfg_reg0: u32, cfg_reg1: u32, cfg_reg2: u32, cfg_reg3: u32) {
-        let phys_bar_path = format!("/sys/bus/pci/devices/{}/resource0", self.pci_bdf);
-
-        let metadata = fs::metadata(&phys_bar_path).expect("Error getting file metadata!");
-        let phys_bar_size = metadata.len() as u64;
-        let exp_bar_size = phys_bar_size / 8;
-
-        let file = OpenOptions::new()
-            .read(true)
-            .write(true) // Open the file in read-write mode
-            .open(&phys_bar_path)
-            .expect("Failed to open mmap file in read-write mode");
-
-        let mut mmap = unsafe {
-            MmapOptions::new()
-                .offset(0)
-                .len(exp_bar_size as usize)
-                .map_mut(&file)
-                .expect("Failed to map expansion BAR region")
-        };
-
-        let offset = self.csr_offset();
-
-        // Write cfg_reg0 into mmap region
-        mmap[offset as usize..(offset + 4) as usize].copy_from_slice(cast_slice(&[cfg_reg0]));
-
-        // Read back cfg_reg0 to prevent optimization
-        let _cfg_bytes = mmap[offset as usize..(offset + 4) as usize].to_vec();
-        black_box(_cfg_bytes);
-
-        // Write cfg_reg1 into mmap region
-        mmap[(offset + 4) as usize..(offset + 8) as usize].copy_from_slice(cast_slice(&[cfg_reg1]));
-
-        // Read back cfg_reg1 to prevent optimization
-        let _cfg_bytes = mmap[(offset + 4) as usize..(offset + 8) as usize].to_vec();
-        black_box(_cfg_bytes);
-
-        // Write cfg_reg2 into mmap region
-        mmap[(offset + 8) as usize..(offset + 12) as usize]
-            .copy_from_slice(cast_slice(&[cfg_reg2]));
-
-        // Read back cfg_reg2 to prevent optimization
-        let _cfg_bytes = mmap[(offset + 8) as usize..(offset + 12) as usize].to_vec();
-        black_box(_cfg_bytes);
-
-        // Write cfg_reg3 into mmap region
-        mmap[(offset + 12) as usize..(offset + 16) as usize]
-            .copy_from_slice(cast_slice(&[cfg_reg3]));
-
-        // Read back cfg_reg3 to prevent optimization
-        let _cfg_bytes = mmap[(offset + 12) as usize..(offset + 16) as usize].to_vec();
-        black_box(_cfg_bytes);
+        self.trigger_exp_bar.csr_access().write(
+            self.csr_offset() as u32,
+            &[cfg_reg0, cfg_reg1, cfg_reg2, cfg_reg3],
+        );
     }
 
     pub fn explicit_bar_cfg(
@@ -258,3 +289,10 @@ impl ExplicitBar {
         None
     }
 }
+
+impl Drop for ExplicitBar {
+    fn drop(&mut self) {
+        // The expansion BARs release their own locks.
+        let _ = FileExt::unlock(&self.lock_file);
+    }
+}
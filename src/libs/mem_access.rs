@@ -161,3 +161,93 @@ pub fn mem_write(
         ),
     }
 }
+
+// Largest single access made by the chunked memory functions.
+const MAX_CHUNK_BYTES: u64 = 4096;
+
+/// Splits a memory access into chunks that each fit within one expansion BAR
+/// window.
+///
+/// # Returns
+///
+/// A list of `(address, length_words)` pairs covering the access.
+fn mem_chunks(exp_bar: &ExpansionBar, address: u64, length_words: u64) -> Vec<(u64, u64)> {
+    let window = exp_bar.exp_bar_size.min(MAX_CHUNK_BYTES);
+    let mut chunks = Vec::new();
+    let mut chunk_addr = address;
+    let mut remaining = length_words;
+
+    while remaining > 0 {
+        let window_left = (window - (chunk_addr % window)) / 4;
+        let chunk_words = remaining.min(window_left);
+        chunks.push((chunk_addr, chunk_words));
+        chunk_addr += chunk_words * 4;
+        remaining -= chunk_words;
+    }
+
+    chunks
+}
+
+/// Reads an arbitrarily long range of memory, splitting it into accesses that
+/// do not cross an expansion BAR window.
+///
+/// # Parameters
+///
+/// * `exp_bar`: Expansion BAR used for the accesses.
+/// * `cpp_island`: Island of the memory.
+/// * `mem_type`: Type of memory to read.
+/// * `engine`: MU memory engine used for EMEM and CTM accesses.
+/// * `address`: Word aligned start address.
+/// * `length`: Number of 32-bit words to read.
+///
+/// # Returns
+///
+/// A `Vec<u32>` with the words read.
+pub fn mem_read_chunked(
+    exp_bar: &mut ExpansionBar,
+    cpp_island: CppIsland,
+    mem_type: MemoryType,
+    engine: MuMemoryEngine,
+    address: u64,
+    length: u64,
+) -> Vec<u32> {
+    let mut words = Vec::with_capacity(length as usize);
+    for (chunk_addr, chunk_words) in mem_chunks(exp_bar, address, length) {
+        words.extend(mem_read(
+            exp_bar,
+            cpp_island,
+            mem_type,
+            engine,
+            chunk_addr,
+            chunk_words,
+        ));
+    }
+    words
+}
+
+/// Writes an arbitrarily long range of memory, splitting it into accesses that
+/// do not cross an expansion BAR window.
+///
+/// # Parameters
+///
+/// * `exp_bar`: Expansion BAR used for the accesses.
+/// * `cpp_island`: Island of the memory.
+/// * `mem_type`: Type of memory to write.
+/// * `engine`: MU memory engine used for EMEM and CTM accesses.
+/// * `address`: Word aligned start address.
+/// * `values`: The 32-bit words to write.
+pub fn mem_write_chunked(
+    exp_bar: &mut ExpansionBar,
+    cpp_island: CppIsland,
+    mem_type: MemoryType,
+    engine: MuMemoryEngine,
+    address: u64,
+    values: &[u32],
+) {
+    let mut offset = 0;
+    for (chunk_addr, chunk_words) in mem_chunks(exp_bar, address, values.len() as u64) {
+        let chunk = values[offset..offset + chunk_words as usize].to_vec();
+        mem_write(exp_bar, cpp_island, mem_type, engine, chunk_addr, chunk);
+        offset += chunk_words as usize;
+    }
+}
@@ -0,0 +1,261 @@
+#![allow(dead_code)]
+
+use crate::libs::mem_access::{mem_read_chunked, mem_write_chunked, MemoryType, MuMemoryEngine};
+use crate::libs::performance_analyzer::PerformanceAnalyzer;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// CLS address of the histogram SRAM in a Performance Analyzer island.
+///
+/// The histogram SRAM address and size are not part of the PA register map
+/// and have not been confirmed against the island memory map or on hardware
+/// yet.
+pub const PA_HISTOGRAM_CLS_BASE: u64 = 0x10000;
+
+/// Size of the histogram SRAM in 32-bit words.
+pub const PA_HISTOGRAM_WORDS: u32 = 0x4000;
+
+/// Returns the number of buckets that fit in the histogram SRAM: one per word,
+/// or one per four words for 128-bit histograms.
+pub const fn histogram_max_buckets(histogram128: bool) -> u32 {
+    if histogram128 {
+        PA_HISTOGRAM_WORDS / 4
+    } else {
+        PA_HISTOGRAM_WORDS
+    }
+}
+
+/// A histogram bucket: the counts of all samples whose histogram source word
+/// falls in `[start, start + bucket size)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistogramBucket {
+    pub index: u32,
+    pub start: u64,
+    /// One count per performance counter: four for 128-bit histograms, only
+    /// performance counter 0 otherwise.
+    pub counts: Vec<u32>,
+}
+
+/// A histogram read back from the histogram SRAM of a Performance Analyzer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    pub histogram_shift: u32,
+    pub histogram128: bool,
+    pub buckets: Vec<HistogramBucket>,
+}
+
+impl Histogram {
+    /// Builds a histogram from the raw words of the histogram SRAM.
+    ///
+    /// Each bucket takes one word, or four words (one per performance counter)
+    /// for 128-bit histograms. The bucket index is the histogram source word
+    /// shifted right by `histogram_shift`, so every bucket covers
+    /// `1 << histogram_shift` source values. The PA applies no offset to the
+    /// source word (`PAConfig` has no histogram base), so the start of bucket
+    /// `i` is the source value `i << histogram_shift`. What that value means
+    /// depends on the histogram source, e.g. the PC for
+    /// `pa_histogram_on_rfpc_pc()`.
+    pub fn from_words(words: &[u32], histogram128: bool, histogram_shift: u32) -> Self {
+        let words_per_bucket = if histogram128 { 4 } else { 1 };
+        let buckets = words
+            .chunks_exact(words_per_bucket)
+            .enumerate()
+            .map(|(index, counts)| HistogramBucket {
+                index: index as u32,
+                start: (index as u64) << histogram_shift,
+                counts: counts.to_vec(),
+            })
+            .collect();
+
+        Histogram {
+            histogram_shift,
+            histogram128,
+            buckets,
+        }
+    }
+
+    /// Number of source values covered by each bucket.
+    pub fn bucket_size(&self) -> u64 {
+        1 << self.histogram_shift
+    }
+
+    /// Sum of the counts of a performance counter over all buckets.
+    pub fn total(&self, counter: usize) -> u64 {
+        self.buckets
+            .iter()
+            .filter_map(|bucket| bucket.counts.get(counter))
+            .map(|count| *count as u64)
+            .sum()
+    }
+
+    /// Returns the `n` buckets with the highest count of a performance
+    /// counter, highest first. Empty buckets are left out.
+    pub fn hottest(&self, counter: usize, n: usize) -> Vec<&HistogramBucket> {
+        let mut buckets: Vec<&HistogramBucket> = self
+            .buckets
+            .iter()
+            .filter(|bucket| bucket.counts.get(counter).copied().unwrap_or(0) != 0)
+            .collect();
+        buckets.sort_by(|a, b| b.counts[counter].cmp(&a.counts[counter]));
+        buckets.truncate(n);
+        buckets
+    }
+
+    /// Renders the histogram as a text bar chart.
+    ///
+    /// Empty buckets are skipped. Every line holds the bucket label, its
+    /// count, its share of the total and a bar scaled to the largest bucket.
+    ///
+    /// # Parameters
+    ///
+    /// * `counter`: Performance counter to chart (0 unless 128-bit).
+    /// * `width`: Width of the longest bar in characters.
+    /// * `top`: Only chart the `top` hottest buckets, highest first, instead
+    ///   of all of them in bucket order.
+    /// * `label`: Labels a bucket from its start value, e.g. with the symbol
+    ///   of a PC.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Vec<String>` with one line per bucket.
+    pub fn format_bar_chart(
+        &self,
+        counter: usize,
+        width: usize,
+        top: Option<usize>,
+        label: &dyn Fn(u64) -> String,
+    ) -> Vec<String> {
+        let buckets = match top {
+            Some(n) => self.hottest(counter, n),
+            None => {
+                let mut buckets = self.hottest(counter, usize::MAX);
+                buckets.sort_by_key(|bucket| bucket.index);
+                buckets
+            }
+        };
+
+        let total = self.total(counter).max(1);
+        let max = buckets
+            .iter()
+            .map(|bucket| bucket.counts[counter])
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        buckets
+            .iter()
+            .map(|bucket| {
+                let count = bucket.counts[counter];
+                let bar_len = (count as u64 * width as u64).div_ceil(max as u64) as usize;
+                format!(
+                    "{:<40} {:>10} {:>6.2}% |{}",
+                    label(bucket.start),
+                    count,
+                    100.0 * count as f64 / total as f64,
+                    "#".repeat(bar_len)
+                )
+            })
+            .collect()
+    }
+}
+
+// Number of SRAM words used by a histogram.
+fn histogram_words(pa: &PerformanceAnalyzer, num_buckets: u32) -> u32 {
+    let words_per_bucket = if pa.global_config().histogram_128() {
+        4
+    } else {
+        1
+    };
+    if num_buckets > histogram_max_buckets(words_per_bucket == 4) {
+        panic!(
+            "{} buckets do not fit in the {} word histogram SRAM",
+            num_buckets, PA_HISTOGRAM_WORDS
+        );
+    }
+    num_buckets * words_per_bucket
+}
+
+/// Clears the first `num_buckets` buckets of the histogram SRAM. Call before
+/// starting a histogram capture.
+///
+/// # Panics
+///
+/// Panics if the buckets do not fit in the histogram SRAM.
+pub fn clear_histogram(pa: &mut PerformanceAnalyzer, num_buckets: u32) {
+    let words = histogram_words(pa, num_buckets);
+    mem_write_chunked(
+        pa.exp_bar,
+        pa.cpp_island,
+        MemoryType::Cls,
+        MuMemoryEngine::Bulk32,
+        PA_HISTOGRAM_CLS_BASE,
+        &vec![0; words as usize],
+    );
+}
+
+/// Reads the first `num_buckets` buckets of the histogram SRAM, using the
+/// histogram configuration of the Performance Analyzer.
+///
+/// # Panics
+///
+/// Panics if the buckets do not fit in the histogram SRAM.
+pub fn read_histogram(pa: &mut PerformanceAnalyzer, num_buckets: u32) -> Histogram {
+    let words = histogram_words(pa, num_buckets);
+    let config = pa.global_config();
+    let words = mem_read_chunked(
+        pa.exp_bar,
+        pa.cpp_island,
+        MemoryType::Cls,
+        MuMemoryEngine::Bulk32,
+        PA_HISTOGRAM_CLS_BASE,
+        words as u64,
+    );
+    Histogram::from_words(&words, config.histogram_128(), config.histogram_shift())
+}
+
+/// Lets a started histogram capture run for `duration`, stops the
+/// Performance Analyzer and reads the histogram back.
+pub fn capture_histogram(
+    pa: &mut PerformanceAnalyzer,
+    duration: Duration,
+    num_buckets: u32,
+) -> Histogram {
+    sleep(duration);
+    pa.stop_pa();
+    read_histogram(pa, num_buckets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_words_32_bit() {
+        let histogram = Histogram::from_words(&[0, 5, 0, 7], false, 2);
+
+        assert_eq!(histogram.bucket_size(), 4);
+        assert_eq!(histogram.buckets.len(), 4);
+        assert_eq!(histogram.buckets[3].start, 12);
+        assert_eq!(histogram.total(0), 12);
+
+        let hottest: Vec<u64> = histogram.hottest(0, 10).iter().map(|b| b.start).collect();
+        assert_eq!(hottest, [12, 4]);
+    }
+
+    #[test]
+    fn from_words_128_bit() {
+        let words = [1, 2, 3, 4, 5, 6, 7, 8];
+        let histogram = Histogram::from_words(&words, true, 7);
+
+        assert_eq!(histogram.buckets.len(), 2);
+        assert_eq!(histogram.buckets[1].start, 128);
+        assert_eq!(histogram.buckets[1].counts, [5, 6, 7, 8]);
+        assert_eq!(histogram.total(3), 12);
+    }
+
+    #[test]
+    fn max_buckets() {
+        assert_eq!(histogram_max_buckets(false), PA_HISTOGRAM_WORDS);
+        assert_eq!(histogram_max_buckets(true), PA_HISTOGRAM_WORDS / 4);
+    }
+}
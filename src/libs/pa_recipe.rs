@@ -0,0 +1,492 @@
+#![allow(dead_code)]
+
+use crate::libs::performance_analyzer::{
+    PACaptureTCAM, PAConfig, PAMaskCompare, PAMaskCompareDetect, PATriggerTransitionConfig0,
+    PATriggerTransitionConfig1,
+};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Number of units of each kind in the Performance Analyzer.
+pub const PA_MASK_COMPARE_UNITS: usize = 16;
+pub const PA_MASK_COMPARE_DETECT_UNITS: usize = 8;
+pub const PA_CAPTURE_TCAM_UNITS: usize = 8;
+pub const PA_STATE_TRANSITIONS: usize = 8;
+
+// A field of a recipe section: name, register of the unit (0 or 1), msb, lsb.
+type RecipeField = (&'static str, usize, u32, u32);
+
+// Fields of the PAConfig register, without the reserved bits.
+const CONFIG_FIELDS: &[RecipeField] = &[
+    ("active", 0, 0, 0),
+    ("enable_as_valid", 0, 1, 1),
+    ("halt_on_inactive", 0, 2, 2),
+    ("journalling", 0, 4, 4),
+    ("histogram_shift", 0, 7, 5),
+    ("histogram_128", 0, 8, 8),
+    ("event_method", 0, 10, 9),
+    ("capture_trigger", 0, 15, 13),
+    ("capture_method", 0, 17, 16),
+    ("capture_start", 0, 19, 18),
+    ("pc_action", 0, 22, 20),
+    ("pc_stats", 0, 23, 23),
+    ("capture_mode", 0, 25, 24),
+    ("histogram_source", 0, 27, 26),
+    ("rv_decompress", 0, 28, 28),
+    ("rv_trace_64", 0, 29, 29),
+    ("rv_trigger_decomp", 0, 30, 30),
+    ("rv_capture_decomp", 0, 31, 31),
+];
+
+// Fields of the PAMaskCompare register. The unit number field is implied by
+// the section index.
+const MASK_COMPARE_FIELDS: &[RecipeField] = &[
+    ("select", 0, 31, 28),
+    ("mask", 0, 15, 8),
+    ("value", 0, 7, 0),
+    ("invert", 0, 24, 24),
+];
+
+const MASK_COMPARE_DETECT_FIELDS: &[RecipeField] = &[("mask", 0, 31, 16), ("value", 0, 15, 0)];
+
+const CAPTURE_TCAM_FIELDS: &[RecipeField] = &[
+    ("capture_type", 0, 26, 24),
+    ("source", 0, 17, 16),
+    ("mask", 0, 7, 0),
+    ("value", 0, 15, 8),
+    ("invert", 0, 18, 18),
+];
+
+// Fields of the PATriggerTransitionConfig0 (register 0) and
+// PATriggerTransitionConfig1 (register 1) pair.
+const TRANSITION_FIELDS: &[RecipeField] = &[
+    ("state_mask", 0, 7, 0),
+    ("mcd_mask", 0, 15, 8),
+    ("mcd_value", 0, 23, 16),
+    ("counters_zero_mask", 0, 25, 24),
+    ("counters_nonzero_mask", 0, 27, 26),
+    ("ext_mask", 0, 28, 28),
+    ("invert", 0, 29, 29),
+    ("destination_mask", 1, 7, 0),
+    ("counter_restart", 1, 17, 16),
+    ("counter_inc", 1, 19, 18),
+    ("counter_dec", 1, 21, 20),
+];
+
+/// The sections of a recipe file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecipeSection {
+    Config,
+    MaskCompare,
+    MaskCompareDetect,
+    CaptureTcam,
+    Transition,
+}
+
+impl RecipeSection {
+    const ALL: [RecipeSection; 5] = [
+        RecipeSection::Config,
+        RecipeSection::MaskCompare,
+        RecipeSection::MaskCompareDetect,
+        RecipeSection::CaptureTcam,
+        RecipeSection::Transition,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            RecipeSection::Config => "config",
+            RecipeSection::MaskCompare => "mask_compare",
+            RecipeSection::MaskCompareDetect => "mask_compare_detect",
+            RecipeSection::CaptureTcam => "capture_tcam",
+            RecipeSection::Transition => "transition",
+        }
+    }
+
+    // Number of units of the section. The global config is a single,
+    // unnumbered section.
+    fn units(self) -> usize {
+        match self {
+            RecipeSection::Config => 1,
+            RecipeSection::MaskCompare => PA_MASK_COMPARE_UNITS,
+            RecipeSection::MaskCompareDetect => PA_MASK_COMPARE_DETECT_UNITS,
+            RecipeSection::CaptureTcam => PA_CAPTURE_TCAM_UNITS,
+            RecipeSection::Transition => PA_STATE_TRANSITIONS,
+        }
+    }
+
+    fn fields(self) -> &'static [RecipeField] {
+        match self {
+            RecipeSection::Config => CONFIG_FIELDS,
+            RecipeSection::MaskCompare => MASK_COMPARE_FIELDS,
+            RecipeSection::MaskCompareDetect => MASK_COMPARE_DETECT_FIELDS,
+            RecipeSection::CaptureTcam => CAPTURE_TCAM_FIELDS,
+            RecipeSection::Transition => TRANSITION_FIELDS,
+        }
+    }
+}
+
+fn field_mask(msb: u32, lsb: u32) -> u32 {
+    (u32::MAX >> (31 - (msb - lsb))) << lsb
+}
+
+/// A named Performance Analyzer configuration that can be saved to and
+/// loaded from a human-editable recipe file.
+///
+/// A recipe holds the same registers as the local configuration of a
+/// `PerformanceAnalyzer`: the global config, the mask/compare units, the
+/// Mask Compare Detect units, the TCAM capture units and the trigger state
+/// transitions. See `PerformanceAnalyzer::recipe()` and
+/// `PerformanceAnalyzer::apply_recipe()`.
+///
+/// The file format is a list of sections with one `field = value` line per
+/// register field, using the field names of the register bitfields. Units that
+/// are not listed are left unconfigured. Values are decimal or `0x` prefixed
+/// hex, and `#` starts a comment. For example:
+///
+/// ```text
+/// name = pc_trace
+///
+/// [config]
+/// active = 1
+/// capture_method = 3
+///
+/// [mask_compare 1]
+/// select = 0
+/// mask = 0x80
+/// value = 0x80
+///
+/// [capture_tcam 0]
+/// capture_type = 2
+/// mask = 0x01
+/// value = 0x01
+/// ```
+#[derive(Debug)]
+pub struct PARecipe {
+    pub name: String,
+    pub config: PAConfig,
+    pub mask_compare: Vec<PAMaskCompare>,
+    pub mask_compare_detect: Vec<PAMaskCompareDetect>,
+    pub capture_tcam: Vec<PACaptureTCAM>,
+    pub state_transitions: Vec<(PATriggerTransitionConfig0, PATriggerTransitionConfig1)>,
+}
+
+impl PARecipe {
+    /// Creates an empty recipe, with every unit unconfigured.
+    pub fn new(name: &str) -> Self {
+        let mask_compare = (0..PA_MASK_COMPARE_UNITS)
+            .map(|unit| {
+                let mut mask_compare = PAMaskCompare(0);
+                mask_compare.set_mask_compare(unit as u32);
+                mask_compare
+            })
+            .collect();
+
+        PARecipe {
+            name: name.to_string(),
+            config: PAConfig(0),
+            mask_compare,
+            mask_compare_detect: vec![PAMaskCompareDetect(0); PA_MASK_COMPARE_DETECT_UNITS],
+            capture_tcam: vec![PACaptureTCAM(0); PA_CAPTURE_TCAM_UNITS],
+            state_transitions: vec![
+                (PATriggerTransitionConfig0(0), PATriggerTransitionConfig1(0));
+                PA_STATE_TRANSITIONS
+            ],
+        }
+    }
+
+    // Raw register values of a unit of a section.
+    fn registers(&self, section: RecipeSection, unit: usize) -> [u32; 2] {
+        match section {
+            RecipeSection::Config => [self.config.0, 0],
+            RecipeSection::MaskCompare => [self.mask_compare[unit].0, 0],
+            RecipeSection::MaskCompareDetect => [self.mask_compare_detect[unit].0, 0],
+            RecipeSection::CaptureTcam => [self.capture_tcam[unit].0, 0],
+            RecipeSection::Transition => {
+                let (config0, config1) = &self.state_transitions[unit];
+                [config0.0, config1.0]
+            }
+        }
+    }
+
+    fn set_registers(&mut self, section: RecipeSection, unit: usize, regs: [u32; 2]) {
+        match section {
+            RecipeSection::Config => self.config.0 = regs[0],
+            RecipeSection::MaskCompare => self.mask_compare[unit].0 = regs[0],
+            RecipeSection::MaskCompareDetect => self.mask_compare_detect[unit].0 = regs[0],
+            RecipeSection::CaptureTcam => self.capture_tcam[unit].0 = regs[0],
+            RecipeSection::Transition => {
+                self.state_transitions[unit].0 .0 = regs[0];
+                self.state_transitions[unit].1 .0 = regs[1];
+            }
+        }
+    }
+
+    /// Parses a recipe from the text of a recipe file.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `PARecipe`, or an error message naming the offending line
+    /// if the text is not a valid recipe.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut recipe = PARecipe::new("");
+        let mut section: Option<(RecipeSection, usize)> = None;
+
+        for (line_num, line) in text.lines().enumerate() {
+            let line_num = line_num + 1;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header
+                    .strip_suffix(']')
+                    .ok_or(format!("line {}: missing ']' in section header", line_num))?;
+                section = Some(
+                    parse_section_header(header)
+                        .map_err(|e| format!("line {}: {}", line_num, e))?,
+                );
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(format!("line {}: expected 'field = value'", line_num))?;
+            let (key, value) = (key.trim(), value.trim());
+
+            let (section, unit) = match section {
+                Some(section) => section,
+                None if key == "name" => {
+                    recipe.name = value.to_string();
+                    continue;
+                }
+                None => return Err(format!("line {}: '{}' is outside a section", line_num, key)),
+            };
+
+            let &(_, reg, msb, lsb) = section
+                .fields()
+                .iter()
+                .find(|(name, ..)| *name == key)
+                .ok_or(format!(
+                    "line {}: unknown field '{}' in [{}]",
+                    line_num,
+                    key,
+                    section.name()
+                ))?;
+            let value = parse_value(value).map_err(|e| format!("line {}: {}", line_num, e))?;
+            let mask = field_mask(msb, lsb);
+            if value > mask >> lsb {
+                return Err(format!(
+                    "line {}: {} does not fit in the {}-bit field '{}'",
+                    line_num,
+                    value,
+                    msb - lsb + 1,
+                    key
+                ));
+            }
+
+            let mut regs = recipe.registers(section, unit);
+            regs[reg] = (regs[reg] & !mask) | (value << lsb);
+            recipe.set_registers(section, unit, regs);
+        }
+
+        Ok(recipe)
+    }
+
+    /// Loads a recipe from a file.
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let mut recipe = PARecipe::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+
+        // Fall back to the file name for recipes without a name.
+        if recipe.name.is_empty() {
+            recipe.name = Path::new(path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+        }
+        Ok(recipe)
+    }
+
+    /// Saves the recipe to a file.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_string()).map_err(|e| format!("Failed to write {}: {}", path, e))
+    }
+}
+
+// Parses a section header such as `config` or `mask_compare 3`.
+fn parse_section_header(header: &str) -> Result<(RecipeSection, usize), String> {
+    let mut words = header.split_whitespace();
+    let name = words.next().ok_or("empty section header")?;
+    let section = RecipeSection::ALL
+        .into_iter()
+        .find(|section| section.name() == name)
+        .ok_or(format!("unknown section [{}]", name))?;
+
+    let unit = match (section, words.next()) {
+        (RecipeSection::Config, None) => 0,
+        (RecipeSection::Config, Some(_)) => return Err("[config] takes no unit number".into()),
+        (_, None) => return Err(format!("[{}] needs a unit number", name)),
+        (_, Some(unit)) => unit
+            .parse::<usize>()
+            .ok()
+            .filter(|unit| *unit < section.units())
+            .ok_or(format!(
+                "{} unit must be 0-{}, got '{}'",
+                name,
+                section.units() - 1,
+                unit
+            ))?,
+    };
+    if words.next().is_some() {
+        return Err(format!("unexpected text in section header [{}]", header));
+    }
+
+    Ok((section, unit))
+}
+
+// Parses a decimal or `0x` prefixed hex field value.
+fn parse_value(value: &str) -> Result<u32, String> {
+    let parsed = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse::<u32>(),
+    };
+    parsed.map_err(|_| format!("invalid value '{}'", value))
+}
+
+impl fmt::Display for PARecipe {
+    /// Formats the recipe as the text of a recipe file. The global config is
+    /// always listed, other units only if any of their fields is set.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Performance Analyzer recipe")?;
+        writeln!(f, "name = {}", self.name)?;
+
+        for section in RecipeSection::ALL {
+            for unit in 0..section.units() {
+                let regs = self.registers(section, unit);
+                let fields = section.fields();
+
+                let is_set = fields
+                    .iter()
+                    .any(|&(_, reg, msb, lsb)| regs[reg] & field_mask(msb, lsb) != 0);
+                if section != RecipeSection::Config && !is_set {
+                    continue;
+                }
+
+                writeln!(f)?;
+                match section {
+                    RecipeSection::Config => writeln!(f, "[{}]", section.name())?,
+                    _ => writeln!(f, "[{} {}]", section.name(), unit)?,
+                }
+                for &(name, reg, msb, lsb) in fields {
+                    let value = (regs[reg] & field_mask(msb, lsb)) >> lsb;
+                    let width = msb - lsb + 1;
+                    if width >= 8 {
+                        let digits = width.div_ceil(4) as usize;
+                        writeln!(f, "{} = {:#0w$x}", name, value, w = digits + 2)?;
+                    } else {
+                        writeln!(f, "{} = {}", name, value)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECIPE: &str = "
+name = pc_trace
+
+[config]
+active = 1
+capture_method = 3 # 96 bits and timestamp
+
+[mask_compare 1]
+select = 0
+mask = 0x80
+value = 0x80
+
+[capture_tcam 0]
+capture_type = 2
+mask = 0x01
+value = 0x01
+
+[transition 7]
+state_mask = 0x80
+destination_mask = 0x01
+";
+
+    // Raw register values of every unit of a recipe.
+    fn all_registers(recipe: &PARecipe) -> Vec<[u32; 2]> {
+        RecipeSection::ALL
+            .into_iter()
+            .flat_map(|section| (0..section.units()).map(move |unit| (section, unit)))
+            .map(|(section, unit)| recipe.registers(section, unit))
+            .collect()
+    }
+
+    #[test]
+    fn parse_fields() {
+        let recipe = PARecipe::parse(RECIPE).unwrap();
+        assert_eq!(recipe.name, "pc_trace");
+        assert_eq!(recipe.config.0, 0x0003_0001);
+        assert_eq!(recipe.mask_compare[1].0, 0x0001_8080);
+        assert_eq!(recipe.capture_tcam[0].0, 0x0200_0101);
+        assert_eq!(recipe.state_transitions[7].0 .0, 0x80);
+        assert_eq!(recipe.state_transitions[7].1 .0, 0x01);
+        // Unlisted mask compare units keep their unit number.
+        assert_eq!(recipe.mask_compare[2].0, 0x0002_0000);
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let recipe = PARecipe::parse(RECIPE).unwrap();
+        let text = recipe.to_string();
+        let reparsed = PARecipe::parse(&text).unwrap();
+
+        assert_eq!(reparsed.name, recipe.name);
+        assert_eq!(all_registers(&reparsed), all_registers(&recipe));
+        assert_eq!(reparsed.to_string(), text);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |text: &str| PARecipe::parse(text).unwrap_err();
+
+        assert_eq!(error("active = 1"), "line 1: 'active' is outside a section");
+        assert_eq!(error("[config"), "line 1: missing ']' in section header");
+        assert_eq!(error("[config 1]"), "line 1: [config] takes no unit number");
+        assert_eq!(
+            error("[mask_compare]"),
+            "line 1: [mask_compare] needs a unit number"
+        );
+        assert_eq!(
+            error("[capture_tcam 8]"),
+            "line 1: capture_tcam unit must be 0-7, got '8'"
+        );
+        assert_eq!(error("[trigger 0]"), "line 1: unknown section [trigger]");
+        assert_eq!(
+            error("[config]\nactive 1"),
+            "line 2: expected 'field = value'"
+        );
+        assert_eq!(
+            error("[config]\nmask = 1"),
+            "line 2: unknown field 'mask' in [config]"
+        );
+        assert_eq!(
+            error("[config]\ncapture_method = 4"),
+            "line 2: 4 does not fit in the 2-bit field 'capture_method'"
+        );
+        assert_eq!(
+            error("[config]\nactive = 0xg"),
+            "line 2: invalid value '0xg'"
+        );
+    }
+}
@@ -0,0 +1,237 @@
+#![allow(dead_code)]
+
+use crate::libs::performance_analyzer::PerformanceAnalyzer;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Number of performance and trigger counters of the Performance Analyzer.
+pub const PA_PERF_COUNTERS: usize = 4;
+pub const PA_TRIGGER_COUNTERS: usize = 2;
+
+/// Raw counter values of the Performance Analyzer at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CounterSnapshot {
+    pub timer: u32,
+    pub perf_counters: [u32; PA_PERF_COUNTERS],
+    pub trigger_counters: [u32; PA_TRIGGER_COUNTERS],
+}
+
+impl CounterSnapshot {
+    /// Reads the PA timer, the performance counters and the trigger counters.
+    pub fn read(pa: &mut PerformanceAnalyzer) -> Self {
+        let mut snapshot = CounterSnapshot {
+            timer: pa.read_pa_timer(),
+            perf_counters: [0; PA_PERF_COUNTERS],
+            trigger_counters: [0; PA_TRIGGER_COUNTERS],
+        };
+        for (i, value) in snapshot.perf_counters.iter_mut().enumerate() {
+            *value = pa.read_perf_counter(i as u8);
+        }
+        for (i, value) in snapshot.trigger_counters.iter_mut().enumerate() {
+            *value = pa.read_trigger_counter(i as u8);
+        }
+        snapshot
+    }
+}
+
+/// One sample of a `SamplingSession`.
+///
+/// Deltas are taken against the previous sample with 32-bit wraparound, so a
+/// counter that wraps between two samples still gives the right delta as long
+/// as it does not wrap more than once. Trigger counters can also count down or
+/// be restarted, in which case their delta is the wrapped difference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CounterSample {
+    /// Sample number, starting at 1.
+    pub index: u64,
+    /// Host time since the start of the session.
+    pub elapsed: Duration,
+    /// Host time since the previous sample.
+    pub interval: Duration,
+    pub values: CounterSnapshot,
+    pub timer_delta: u32,
+    pub perf_deltas: [u32; PA_PERF_COUNTERS],
+    pub trigger_deltas: [u32; PA_TRIGGER_COUNTERS],
+    /// Performance counter increments since the start of the session.
+    pub perf_totals: [u64; PA_PERF_COUNTERS],
+}
+
+impl CounterSample {
+    /// Rate of a performance counter in counts per second of host time.
+    pub fn perf_rate(&self, counter: usize) -> f64 {
+        self.perf_deltas[counter] as f64 / self.interval.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    /// Rate of a performance counter in counts per PA timer cycle.
+    pub fn perf_per_cycle(&self, counter: usize) -> f64 {
+        self.perf_deltas[counter] as f64 / self.timer_delta.max(1) as f64
+    }
+
+    /// Header line of the CSV output of `to_csv()`.
+    pub fn csv_header() -> String {
+        let mut columns = vec![
+            "sample".to_string(),
+            "elapsed_s".to_string(),
+            "timer".to_string(),
+            "timer_delta".to_string(),
+        ];
+        for i in 0..PA_PERF_COUNTERS {
+            columns.push(format!("pc{}", i));
+            columns.push(format!("pc{}_delta", i));
+            columns.push(format!("pc{}_per_s", i));
+            columns.push(format!("pc{}_total", i));
+        }
+        for i in 0..PA_TRIGGER_COUNTERS {
+            columns.push(format!("tc{}", i));
+            columns.push(format!("tc{}_delta", i));
+        }
+        columns.join(",")
+    }
+
+    /// Formats the sample as a CSV line matching `csv_header()`.
+    pub fn to_csv(&self) -> String {
+        let mut fields = vec![
+            self.index.to_string(),
+            format!("{:.6}", self.elapsed.as_secs_f64()),
+            self.values.timer.to_string(),
+            self.timer_delta.to_string(),
+        ];
+        for i in 0..PA_PERF_COUNTERS {
+            fields.push(self.values.perf_counters[i].to_string());
+            fields.push(self.perf_deltas[i].to_string());
+            fields.push(format!("{:.3}", self.perf_rate(i)));
+            fields.push(self.perf_totals[i].to_string());
+        }
+        for i in 0..PA_TRIGGER_COUNTERS {
+            fields.push(self.values.trigger_counters[i].to_string());
+            fields.push(self.trigger_deltas[i].to_string());
+        }
+        fields.join(",")
+    }
+
+    /// Header line of the terminal output of `format_line()`.
+    pub fn line_header() -> String {
+        let mut header = format!("{:>8} {:>10}", "sample", "elapsed");
+        for i in 0..PA_PERF_COUNTERS {
+            header += &format!(" {:>14}", format!("pc{}/s", i));
+        }
+        for i in 0..PA_TRIGGER_COUNTERS {
+            header += &format!(" {:>11}", format!("tc{}", i));
+        }
+        header
+    }
+
+    /// Formats the sample as a terminal line with the performance counter
+    /// rates and the trigger counter values.
+    pub fn format_line(&self) -> String {
+        let mut line = format!("{:>8} {:>9.3}s", self.index, self.elapsed.as_secs_f64());
+        for i in 0..PA_PERF_COUNTERS {
+            line += &format!(" {:>14.1}", self.perf_rate(i));
+        }
+        for i in 0..PA_TRIGGER_COUNTERS {
+            line += &format!(" {:>11}", self.values.trigger_counters[i]);
+        }
+        line
+    }
+}
+
+/// Periodically samples the counters of a Performance Analyzer.
+///
+/// The session only reads the counters; the Performance Analyzer must be
+/// configured and started by the caller.
+pub struct SamplingSession<'p, 'a> {
+    pa: &'p mut PerformanceAnalyzer<'a>,
+    interval: Duration,
+    start: Instant,
+    last_time: Instant,
+    last: CounterSnapshot,
+    perf_totals: [u64; PA_PERF_COUNTERS],
+    index: u64,
+}
+
+impl<'p, 'a> SamplingSession<'p, 'a> {
+    /// Starts a session by taking the reference snapshot of the counters.
+    ///
+    /// # Parameters
+    ///
+    /// * `pa`: Started Performance Analyzer to sample.
+    /// * `interval`: Time between two samples.
+    pub fn new(pa: &'p mut PerformanceAnalyzer<'a>, interval: Duration) -> Self {
+        let last = CounterSnapshot::read(pa);
+        let now = Instant::now();
+        SamplingSession {
+            pa,
+            interval,
+            start: now,
+            last_time: now,
+            last,
+            perf_totals: [0; PA_PERF_COUNTERS],
+            index: 0,
+        }
+    }
+
+    /// Waits for the next sampling time, then reads the counters.
+    pub fn sample(&mut self) -> CounterSample {
+        let next = self.last_time + self.interval;
+        let now = Instant::now();
+        if next > now {
+            sleep(next - now);
+        }
+
+        let values = CounterSnapshot::read(self.pa);
+        let now = Instant::now();
+
+        let mut perf_deltas = [0; PA_PERF_COUNTERS];
+        for (i, delta) in perf_deltas.iter_mut().enumerate() {
+            *delta = values.perf_counters[i].wrapping_sub(self.last.perf_counters[i]);
+            self.perf_totals[i] += *delta as u64;
+        }
+        let mut trigger_deltas = [0; PA_TRIGGER_COUNTERS];
+        for (i, delta) in trigger_deltas.iter_mut().enumerate() {
+            *delta = values.trigger_counters[i].wrapping_sub(self.last.trigger_counters[i]);
+        }
+
+        self.index += 1;
+        let sample = CounterSample {
+            index: self.index,
+            elapsed: now - self.start,
+            interval: now - self.last_time,
+            values,
+            timer_delta: values.timer.wrapping_sub(self.last.timer),
+            perf_deltas,
+            trigger_deltas,
+            perf_totals: self.perf_totals,
+        };
+
+        self.last = values;
+        self.last_time = now;
+        sample
+    }
+
+    /// Samples the counters until `count` samples were taken, `duration` has
+    /// passed or `running` is cleared (e.g. by a Ctrl-C handler), whichever
+    /// comes first, and hands every sample to `output`.
+    ///
+    /// # Returns
+    ///
+    /// The number of samples taken.
+    pub fn run(
+        &mut self,
+        count: Option<u64>,
+        duration: Option<Duration>,
+        running: Arc<AtomicBool>,
+        mut output: impl FnMut(&CounterSample),
+    ) -> u64 {
+        let mut taken = 0;
+        while running.load(Ordering::SeqCst)
+            && count.is_none_or(|count| taken < count)
+            && duration.is_none_or(|duration| self.start.elapsed() < duration)
+        {
+            output(&self.sample());
+            taken += 1;
+        }
+        taken
+    }
+}
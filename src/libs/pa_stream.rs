@@ -0,0 +1,358 @@
+#![allow(dead_code)]
+
+use crate::libs::performance_analyzer::{PAFifoControl, PerformanceAnalyzer};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// Number of entries of the PA FIFO.
+const PA_FIFO_ENTRIES: u32 = 4096;
+
+// Mask of the 15-bit FIFO read and write pointers.
+const PA_FIFO_PTR_MASK: u32 = 0x7FFF;
+
+// Value of the trigger FSM field of PATriggerStatus once the trigger halted.
+const PA_TRIGGER_FSM_HALTED: u32 = 2;
+
+// Time to wait for new data when the FIFO is empty.
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// When to stop streaming the Performance Analyzer FIFO.
+#[derive(Debug, Clone)]
+pub struct StreamLimits {
+    /// Stop after this many words.
+    pub max_words: Option<u64>,
+    /// Stop after this much time.
+    pub duration: Option<Duration>,
+    /// Stop once the trigger has halted and the FIFO is drained.
+    pub stop_on_halt: bool,
+    /// Stop when cleared, e.g. by a Ctrl-C handler.
+    pub running: Arc<AtomicBool>,
+}
+
+/// Why streaming the Performance Analyzer FIFO stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamStop {
+    Count,
+    Duration,
+    TriggerHalted,
+    Interrupted,
+}
+
+/// Statistics of a FIFO stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamStats {
+    pub stop: StreamStop,
+    /// Words written to the output.
+    pub words: u64,
+    /// Number of times the FIFO was found overflowed. The FIFO journals, so
+    /// every overflow means that unread samples were overwritten.
+    pub overflows: u64,
+    /// Offsets in the output, in words, of the gaps left by the overflows.
+    /// The words before a gap may end within a sample, the words after it
+    /// start with a new sample.
+    pub gaps: Vec<u64>,
+    /// Highest FIFO fill level seen, in words.
+    pub max_fill: u32,
+    pub elapsed: Duration,
+}
+
+/// Number of words waiting in the FIFO. The 15-bit read and write pointers
+/// wrap around, so their difference is taken modulo the pointer range.
+pub(crate) fn fifo_fill(fifo_control: &PAFifoControl) -> u32 {
+    if fifo_control.empty() {
+        return 0;
+    }
+    match fifo_control
+        .write_ptr()
+        .wrapping_sub(fifo_control.read_ptr())
+        & PA_FIFO_PTR_MASK
+    {
+        0 => PA_FIFO_ENTRIES,
+        fill => fill.min(PA_FIFO_ENTRIES),
+    }
+}
+
+/// Continuously drains the FIFO of a running Performance Analyzer into
+/// `output`, as little-endian 32-bit words (the `nfp-trace -f raw` format).
+///
+/// FIFO journalling is switched on, so the capture never stops on a full
+/// FIFO; if the host falls behind, the oldest unread samples are overwritten
+/// and counted as an overflow. The trigger is started when streaming begins
+/// and halted when it ends. Words are collected in a buffer of `buffer_words`
+/// words, which is written to `output` whenever it fills up and at the end.
+///
+/// An overflow overwrites an arbitrary number of words, so the words left in
+/// the FIFO may start in the middle of a sample. To resynchronize, the trigger
+/// is halted, the FIFO is drained and discarded and the trigger is restarted,
+/// so capturing resumes at a sample boundary. The output offset of every such
+/// gap is recorded in `StreamStats::gaps`, see `write_gap_file()`; the
+/// segments between the gaps must be decoded separately.
+///
+/// # Parameters
+///
+/// * `pa`: Started Performance Analyzer to stream from.
+/// * `output`: Destination of the FIFO words.
+/// * `buffer_words`: Size of the write buffer in words.
+/// * `limits`: When to stop streaming.
+/// * `progress`: Called with the running statistics after every write.
+///
+/// # Returns
+///
+/// The statistics of the stream, or the error of a failed write.
+///
+/// # Panics
+///
+/// Panics if `buffer_words` is zero.
+pub fn stream_fifo(
+    pa: &mut PerformanceAnalyzer,
+    output: &mut impl Write,
+    buffer_words: usize,
+    limits: &StreamLimits,
+    mut progress: impl FnMut(&StreamStats),
+) -> std::io::Result<StreamStats> {
+    if buffer_words == 0 {
+        panic!("The stream buffer must hold at least one word.");
+    }
+
+    let start = Instant::now();
+    let mut buffer: Vec<u32> = Vec::with_capacity(buffer_words);
+    let mut stats = StreamStats {
+        stop: StreamStop::Interrupted,
+        words: 0,
+        overflows: 0,
+        gaps: Vec::new(),
+        max_fill: 0,
+        elapsed: Duration::ZERO,
+    };
+    let mut overflowed = false;
+
+    let mut flush = |buffer: &mut Vec<u32>, stats: &mut StreamStats| -> std::io::Result<()> {
+        output.write_all(bytemuck::cast_slice(buffer))?;
+        output.flush()?;
+        stats.words += buffer.len() as u64;
+        stats.elapsed = start.elapsed();
+        buffer.clear();
+        progress(stats);
+        Ok(())
+    };
+
+    pa.set_journalling(true);
+    pa.trigger_idle();
+    pa.trigger_start(0, 0);
+
+    loop {
+        let buffered = stats.words + buffer.len() as u64;
+        if !limits.running.load(Ordering::SeqCst) {
+            stats.stop = StreamStop::Interrupted;
+            break;
+        }
+        if limits.max_words.is_some_and(|max| buffered >= max) {
+            stats.stop = StreamStop::Count;
+            break;
+        }
+        if limits
+            .duration
+            .is_some_and(|duration| start.elapsed() >= duration)
+        {
+            stats.stop = StreamStop::Duration;
+            break;
+        }
+
+        // Check the trigger before the FIFO, so the words captured up to the
+        // halt are drained before stopping.
+        let halted = limits.stop_on_halt && pa.read_trigger_status().fsm() == PA_TRIGGER_FSM_HALTED;
+
+        let fifo_control = pa.read_fifo_control();
+        if fifo_control.overflow() {
+            if !overflowed {
+                stats.overflows += 1;
+                stats.gaps.push(stats.words + buffer.len() as u64);
+                resync_fifo(pa, halted);
+                overflowed = true;
+                continue;
+            }
+        } else {
+            overflowed = false;
+        }
+
+        let fill = fifo_fill(&fifo_control);
+        stats.max_fill = stats.max_fill.max(fill);
+        if fill == 0 {
+            if halted {
+                stats.stop = StreamStop::TriggerHalted;
+                break;
+            }
+            sleep(STREAM_POLL_INTERVAL);
+            continue;
+        }
+
+        let mut to_read = fill.min((buffer_words - buffer.len()) as u32);
+        if let Some(max) = limits.max_words {
+            to_read = (to_read as u64).min(max - buffered) as u32;
+        }
+        buffer.extend(pa.read_fifo_data(to_read));
+        if buffer.len() >= buffer_words {
+            flush(&mut buffer, &mut stats)?;
+        }
+    }
+
+    pa.trigger_halt();
+    flush(&mut buffer, &mut stats)?;
+    Ok(stats)
+}
+
+// Discards the words left in the FIFO after an overflow, which may start in
+// the middle of a sample, and restarts the trigger so that capturing resumes
+// at a sample boundary. A trigger that has halted by itself stays halted.
+fn resync_fifo(pa: &mut PerformanceAnalyzer, halted: bool) {
+    pa.trigger_halt();
+    loop {
+        let fill = fifo_fill(&pa.read_fifo_control());
+        if fill == 0 {
+            break;
+        }
+        pa.read_fifo_data(fill);
+    }
+    if !halted {
+        pa.trigger_idle();
+        pa.trigger_start(0, 0);
+    }
+}
+
+/// Returns the path of the gap file of a stream file, `<stream>.gaps`.
+pub fn gap_file_path(stream_path: &Path) -> PathBuf {
+    let mut path = stream_path.as_os_str().to_owned();
+    path.push(".gaps");
+    PathBuf::from(path)
+}
+
+/// Writes the gaps of a stream (see `StreamStats::gaps`) to its gap file. The
+/// file holds one decimal word offset into the stream file per line. No file
+/// is written for a stream without gaps, and a stale one is removed.
+pub fn write_gap_file(stream_path: &Path, gaps: &[u64]) -> std::io::Result<()> {
+    let path = gap_file_path(stream_path);
+    if gaps.is_empty() {
+        return match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    let lines: String = gaps.iter().map(|gap| format!("{}\n", gap)).collect();
+    fs::write(path, lines)
+}
+
+/// Reads the gaps of a stream file from its gap file.
+///
+/// # Returns
+///
+/// The word offsets of the gaps, empty if there is no gap file, or an error
+/// message if the file cannot be read or parsed.
+pub fn read_gap_file(stream_path: &Path) -> Result<Vec<u64>, String> {
+    let path = gap_file_path(stream_path);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.trim()
+                .parse()
+                .map_err(|e| format!("Invalid gap '{}' in {}: {}", line, path.display(), e))
+        })
+        .collect()
+}
+
+/// Splits the words of a stream at its gaps into segments that can be
+/// decoded separately.
+pub fn split_at_gaps<'w>(words: &'w [u32], gaps: &[u64]) -> Vec<&'w [u32]> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for &gap in gaps {
+        let end = (gap as usize).clamp(start, words.len());
+        segments.push(&words[start..end]);
+        start = end;
+    }
+    segments.push(&words[start..]);
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fifo_control(read_ptr: u32, write_ptr: u32, empty: bool) -> PAFifoControl {
+        let mut fifo_control = PAFifoControl(0);
+        fifo_control.set_read_ptr(read_ptr);
+        fifo_control.set_write_ptr(write_ptr);
+        fifo_control.set_empty(empty);
+        fifo_control
+    }
+
+    #[test]
+    fn fifo_fill_levels() {
+        assert_eq!(fifo_fill(&fifo_control(100, 100, true)), 0);
+        assert_eq!(fifo_fill(&fifo_control(100, 164, false)), 64);
+        assert_eq!(fifo_fill(&fifo_control(0, 4096, false)), PA_FIFO_ENTRIES);
+        // A full FIFO whose pointers are equal modulo the pointer range.
+        assert_eq!(fifo_fill(&fifo_control(100, 100, false)), PA_FIFO_ENTRIES);
+    }
+
+    #[test]
+    fn fifo_fill_wraps_15_bit_pointers() {
+        assert_eq!(fifo_fill(&fifo_control(0x7FF0, 0x0010, false)), 0x20);
+        assert_eq!(
+            fifo_fill(&fifo_control(0x7000, 0x0000, false)),
+            PA_FIFO_ENTRIES
+        );
+        assert_eq!(fifo_fill(&fifo_control(0x7FFF, 0x0000, false)), 1);
+    }
+
+    #[test]
+    fn split_at_gaps_segments() {
+        let words: Vec<u32> = (0..10).collect();
+        assert_eq!(split_at_gaps(&words, &[]), [&words[..]]);
+        assert_eq!(
+            split_at_gaps(&words, &[3, 7]),
+            [&words[0..3], &words[3..7], &words[7..10]]
+        );
+        // Gaps at the ends and past the end give empty segments.
+        assert_eq!(
+            split_at_gaps(&words, &[0, 10, 12]),
+            [&words[0..0], &words[0..10], &words[10..10], &words[10..10]]
+        );
+    }
+
+    #[test]
+    fn gap_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("pa_stream_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let stream_path = dir.join("trace.bin");
+        assert_eq!(gap_file_path(&stream_path), dir.join("trace.bin.gaps"));
+
+        // No gap file reads as no gaps.
+        assert_eq!(read_gap_file(&stream_path), Ok(Vec::new()));
+
+        write_gap_file(&stream_path, &[16, 4096, 1 << 40]).unwrap();
+        assert_eq!(read_gap_file(&stream_path), Ok(vec![16, 4096, 1 << 40]));
+
+        // A stream without gaps removes the stale gap file.
+        write_gap_file(&stream_path, &[]).unwrap();
+        assert!(!gap_file_path(&stream_path).exists());
+        write_gap_file(&stream_path, &[]).unwrap();
+
+        fs::write(gap_file_path(&stream_path), "16\nx\n").unwrap();
+        assert!(read_gap_file(&stream_path)
+            .unwrap_err()
+            .starts_with("Invalid gap 'x'"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,718 @@
+#![allow(dead_code)]
+
+use crate::libs::pa_recipe::{
+    PA_CAPTURE_TCAM_UNITS, PA_MASK_COMPARE_DETECT_UNITS, PA_MASK_COMPARE_UNITS,
+    PA_STATE_TRANSITIONS,
+};
+use crate::libs::performance_analyzer::{PerformanceAnalyzer, TcamCaptureSource, TcamCaptureType};
+use std::collections::HashMap;
+
+// Width of the performance bus in bits.
+const PA_BUS_BITS: u32 = 96;
+
+// Number of trigger states and trigger counters.
+const PA_TRIGGER_STATES: u8 = 8;
+const PA_TRIGGER_COUNTERS: u8 = 2;
+
+/// A match of a bit range of the 96-bit performance bus against a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BusMatch {
+    msb: u32,
+    lsb: u32,
+    value: u32,
+}
+
+/// A condition on the performance bus: all of its bit ranges must match.
+///
+/// Each condition is compiled to one Mask Compare Detect unit, fed by one
+/// mask/compare unit per bus byte the bit ranges touch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TriggerCondition {
+    matches: Vec<BusMatch>,
+}
+
+impl TriggerCondition {
+    /// Matches when bus bits `[msb:lsb]` equal `value`.
+    pub fn bits(msb: u32, lsb: u32, value: u32) -> Self {
+        TriggerCondition {
+            matches: vec![BusMatch { msb, lsb, value }],
+        }
+    }
+
+    /// Matches when bus bit `bit` is set.
+    pub fn bit_set(bit: u32) -> Self {
+        TriggerCondition::bits(bit, bit, 1)
+    }
+
+    /// Additionally requires bus bits `[msb:lsb]` to equal `value`.
+    pub fn and_bits(mut self, msb: u32, lsb: u32, value: u32) -> Self {
+        self.matches.push(BusMatch { msb, lsb, value });
+        self
+    }
+}
+
+/// A transition of the trigger state machine.
+///
+/// The Performance Analyzer trigger is a nondeterministic state machine with 8
+/// states, several of which can be active at once. A transition fires in a
+/// cycle when any of its source states is active and all of its conditions
+/// hold, and then activates its destination states and updates the trigger
+/// counters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateTransition {
+    from: Vec<u8>,
+    to: Vec<u8>,
+    when: Vec<(String, bool)>,
+    counters_zero: Vec<u8>,
+    counters_nonzero: Vec<u8>,
+    restart: Vec<u8>,
+    increment: Vec<u8>,
+    decrement: Vec<u8>,
+    external: bool,
+}
+
+impl StateTransition {
+    /// Creates a transition from state `from` to state `to` (0-7).
+    pub fn new(from: u8, to: u8) -> Self {
+        StateTransition {
+            from: vec![from],
+            to: vec![to],
+            when: Vec::new(),
+            counters_zero: Vec::new(),
+            counters_nonzero: Vec::new(),
+            restart: Vec::new(),
+            increment: Vec::new(),
+            decrement: Vec::new(),
+            external: false,
+        }
+    }
+
+    /// Also fires from state `state`.
+    pub fn or_from(mut self, state: u8) -> Self {
+        self.from.push(state);
+        self
+    }
+
+    /// Also activates state `state`.
+    pub fn and_to(mut self, state: u8) -> Self {
+        self.to.push(state);
+        self
+    }
+
+    /// Only fires when the named condition holds.
+    pub fn when(mut self, condition: &str) -> Self {
+        self.when.push((condition.to_string(), true));
+        self
+    }
+
+    /// Only fires when the named condition does not hold.
+    pub fn when_not(mut self, condition: &str) -> Self {
+        self.when.push((condition.to_string(), false));
+        self
+    }
+
+    /// Only fires when trigger counter `counter` (0-1) is zero.
+    pub fn when_counter_zero(mut self, counter: u8) -> Self {
+        self.counters_zero.push(counter);
+        self
+    }
+
+    /// Only fires when trigger counter `counter` (0-1) is not zero.
+    pub fn when_counter_nonzero(mut self, counter: u8) -> Self {
+        self.counters_nonzero.push(counter);
+        self
+    }
+
+    /// Only fires when the external trigger input has fired.
+    pub fn when_external(mut self) -> Self {
+        self.external = true;
+        self
+    }
+
+    /// Reloads trigger counter `counter` with its restart value when fired.
+    pub fn restart_counter(mut self, counter: u8) -> Self {
+        self.restart.push(counter);
+        self
+    }
+
+    /// Increments trigger counter `counter` when fired.
+    pub fn increment_counter(mut self, counter: u8) -> Self {
+        self.increment.push(counter);
+        self
+    }
+
+    /// Decrements trigger counter `counter` when fired.
+    pub fn decrement_counter(mut self, counter: u8) -> Self {
+        self.decrement.push(counter);
+        self
+    }
+}
+
+/// Input of a capture rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TriggerSource {
+    /// A named bus condition holds.
+    Condition(String),
+    /// A named state transition fires.
+    Transition(String),
+}
+
+// A compiled mask/compare unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MaskCompareUnit {
+    byte_num: u8,
+    mask: u8,
+    compare: u8,
+}
+
+// A compiled state transition, with the arguments of
+// `PerformanceAnalyzer::set_state_transition()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TransitionUnit {
+    state_mask: u8,
+    mcd_mask: u8,
+    mcd_compare: u8,
+    counters_zero_mask: u8,
+    counters_nonzero_mask: u8,
+    ext_mask: bool,
+    counter_dec_mask: u8,
+    counter_inc_mask: u8,
+    counter_restart_mask: u8,
+    destination_mask: u8,
+}
+
+// A compiled TCAM capture unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CaptureUnit {
+    capture_type: TcamCaptureType,
+    source: TcamCaptureSource,
+    mask: u8,
+}
+
+/// Builds a Performance Analyzer trigger from named bus conditions, state
+/// transitions and capture rules, instead of raw unit numbers and masks.
+///
+/// `build()` checks the description, allocates the mask/compare, Mask Compare
+/// Detect, TCAM capture and state transition units, and compiles it to a
+/// `PATrigger`, which configures a `PerformanceAnalyzer` with the existing
+/// `set_*` methods.
+///
+/// # Example
+///
+/// Capture 1000 samples after bus bits `[15:8]` are `0x42`:
+///
+/// ```
+/// use nfp_debug_tools::libs::pa_trigger::{
+///     StateTransition, TriggerBuilder, TriggerCondition, TriggerSource,
+/// };
+///
+/// let trigger = TriggerBuilder::new()
+///     .condition("hit", TriggerCondition::bits(15, 8, 0x42))
+///     .counter(0, 1000)
+///     .transition("arm", StateTransition::new(0, 1).when("hit").restart_counter(0))
+///     .transition(
+///         "sample",
+///         StateTransition::new(1, 1)
+///             .when_counter_nonzero(0)
+///             .decrement_counter(0),
+///     )
+///     .transition("done", StateTransition::new(1, 2).when_counter_zero(0))
+///     .capture(TriggerSource::Transition("sample".to_string()))
+///     .start_in(0)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TriggerBuilder {
+    conditions: Vec<(String, TriggerCondition)>,
+    transitions: Vec<(String, StateTransition)>,
+    captures: Vec<(TriggerSource, TcamCaptureType)>,
+    counters: Vec<(u8, u32)>,
+    start_states: Vec<u8>,
+}
+
+impl TriggerBuilder {
+    pub fn new() -> Self {
+        TriggerBuilder::default()
+    }
+
+    /// Adds a named bus condition.
+    pub fn condition(mut self, name: &str, condition: TriggerCondition) -> Self {
+        self.conditions.push((name.to_string(), condition));
+        self
+    }
+
+    /// Adds a named state transition.
+    pub fn transition(mut self, name: &str, transition: StateTransition) -> Self {
+        self.transitions.push((name.to_string(), transition));
+        self
+    }
+
+    /// Captures the performance bus in the FIFO whenever `source` fires.
+    pub fn capture(mut self, source: TriggerSource) -> Self {
+        self.captures.push((source, TcamCaptureType::CaptureData));
+        self
+    }
+
+    /// Updates the performance counters whenever `source` fires.
+    pub fn count(mut self, source: TriggerSource) -> Self {
+        self.captures.push((source, TcamCaptureType::PerfCounting));
+        self
+    }
+
+    /// Sets the restart value of trigger counter `counter` (0-1).
+    pub fn counter(mut self, counter: u8, restart_value: u32) -> Self {
+        self.counters.push((counter, restart_value));
+        self
+    }
+
+    /// Makes state `state` (0-7) active when the trigger starts.
+    pub fn start_in(mut self, state: u8) -> Self {
+        self.start_states.push(state);
+        self
+    }
+
+    /// Checks the trigger description and compiles it to register settings.
+    ///
+    /// # Returns
+    ///
+    /// Returns the compiled `PATrigger`, or an error message if the
+    /// description is invalid or needs more units than the Performance
+    /// Analyzer has.
+    pub fn build(&self) -> Result<PATrigger, String> {
+        let start_states = bit_mask(&self.start_states, PA_TRIGGER_STATES, "state")?;
+        if start_states == 0 {
+            return Err("The trigger has no start state".to_string());
+        }
+
+        let mut counters = [None; PA_TRIGGER_COUNTERS as usize];
+        for &(counter, restart_value) in &self.counters {
+            bit_mask(&[counter], PA_TRIGGER_COUNTERS, "counter")?;
+            counters[counter as usize] = Some(restart_value);
+        }
+
+        // One Mask Compare Detect unit per condition, fed by shared
+        // mask/compare units.
+        let mut mask_compare: Vec<MaskCompareUnit> = Vec::new();
+        let mut mask_compare_detect: Vec<(u16, u16)> = Vec::new();
+        let mut condition_units: HashMap<&str, u8> = HashMap::new();
+
+        for (name, condition) in &self.conditions {
+            if condition_units.contains_key(name.as_str()) {
+                return Err(format!("Condition '{}' is defined twice", name));
+            }
+
+            let mut mc_units: u16 = 0;
+            for bus_match in &condition.matches {
+                for unit in compile_bus_match(bus_match)
+                    .map_err(|e| format!("Condition '{}': {}", name, e))?
+                {
+                    let unit_num = match mask_compare.iter().position(|mc| *mc == unit) {
+                        Some(unit_num) => unit_num,
+                        None if mask_compare.len() == PA_MASK_COMPARE_UNITS => {
+                            return Err(format!(
+                                "The conditions need more than the {} mask/compare units",
+                                PA_MASK_COMPARE_UNITS
+                            ));
+                        }
+                        None => {
+                            mask_compare.push(unit);
+                            mask_compare.len() - 1
+                        }
+                    };
+                    mc_units |= 1 << unit_num;
+                }
+            }
+
+            // All mask/compare units of the condition must match.
+            if mask_compare_detect.len() == PA_MASK_COMPARE_DETECT_UNITS {
+                return Err(format!(
+                    "At most {} conditions are supported",
+                    PA_MASK_COMPARE_DETECT_UNITS
+                ));
+            }
+            mask_compare_detect.push((mc_units, mc_units));
+            condition_units.insert(name, (mask_compare_detect.len() - 1) as u8);
+        }
+
+        let condition_unit = |name: &str| {
+            condition_units
+                .get(name)
+                .copied()
+                .ok_or(format!("Unknown condition '{}'", name))
+        };
+
+        if self.transitions.len() > PA_STATE_TRANSITIONS {
+            return Err(format!(
+                "At most {} state transitions are supported",
+                PA_STATE_TRANSITIONS
+            ));
+        }
+        let mut transitions: Vec<TransitionUnit> = Vec::new();
+        for (name, transition) in &self.transitions {
+            if self.transitions.iter().filter(|(n, _)| n == name).count() > 1 {
+                return Err(format!("Transition '{}' is defined twice", name));
+            }
+            let states = |states: &[u8]| bit_mask(states, PA_TRIGGER_STATES, "state");
+            let counters = |counters: &[u8]| bit_mask(counters, PA_TRIGGER_COUNTERS, "counter");
+            let in_transition = |e: String| format!("Transition '{}': {}", name, e);
+
+            let mut mcd_mask = 0;
+            let mut mcd_compare = 0;
+            for (condition, holds) in &transition.when {
+                let unit = condition_unit(condition).map_err(in_transition)?;
+                mcd_mask |= 1 << unit;
+                if *holds {
+                    mcd_compare |= 1 << unit;
+                }
+            }
+
+            let unit = TransitionUnit {
+                state_mask: states(&transition.from).map_err(in_transition)?,
+                mcd_mask,
+                mcd_compare,
+                counters_zero_mask: counters(&transition.counters_zero).map_err(in_transition)?,
+                counters_nonzero_mask: counters(&transition.counters_nonzero)
+                    .map_err(in_transition)?,
+                ext_mask: transition.external,
+                counter_dec_mask: counters(&transition.decrement).map_err(in_transition)?,
+                counter_inc_mask: counters(&transition.increment).map_err(in_transition)?,
+                counter_restart_mask: counters(&transition.restart).map_err(in_transition)?,
+                destination_mask: states(&transition.to).map_err(in_transition)?,
+            };
+            if unit.counters_zero_mask & unit.counters_nonzero_mask != 0 {
+                return Err(in_transition(
+                    "a counter cannot be both zero and non-zero".to_string(),
+                ));
+            }
+            transitions.push(unit);
+        }
+
+        if self.captures.is_empty() {
+            return Err("The trigger captures nothing".to_string());
+        }
+        if self.captures.len() > PA_CAPTURE_TCAM_UNITS {
+            return Err(format!(
+                "At most {} capture rules are supported",
+                PA_CAPTURE_TCAM_UNITS
+            ));
+        }
+        let mut captures: Vec<CaptureUnit> = Vec::new();
+        for (source, capture_type) in &self.captures {
+            let (source, unit) = match source {
+                TriggerSource::Condition(name) => (
+                    TcamCaptureSource::MaskCompareDetectors,
+                    condition_unit(name)?,
+                ),
+                TriggerSource::Transition(name) => (
+                    TcamCaptureSource::TriggerStateTransitions,
+                    self.transitions
+                        .iter()
+                        .position(|(n, _)| n == name)
+                        .ok_or(format!("Unknown transition '{}'", name))? as u8,
+                ),
+            };
+            captures.push(CaptureUnit {
+                capture_type: *capture_type,
+                source,
+                mask: 1 << unit,
+            });
+        }
+
+        Ok(PATrigger {
+            mask_compare,
+            mask_compare_detect,
+            transitions,
+            captures,
+            counters,
+            start_states,
+        })
+    }
+}
+
+// Builds a bit mask from a list of state or counter numbers.
+fn bit_mask(bits: &[u8], count: u8, what: &str) -> Result<u8, String> {
+    let mut mask = 0;
+    for &bit in bits {
+        if bit >= count {
+            return Err(format!("{} {} is out of range 0-{}", what, bit, count - 1));
+        }
+        mask |= 1 << bit;
+    }
+    Ok(mask)
+}
+
+// Splits a bus bit range match into one mask/compare unit per bus byte.
+fn compile_bus_match(bus_match: &BusMatch) -> Result<Vec<MaskCompareUnit>, String> {
+    let BusMatch { msb, lsb, value } = *bus_match;
+    if msb < lsb || msb >= PA_BUS_BITS {
+        return Err(format!(
+            "bits [{}:{}] are not a valid range of the {}-bit bus",
+            msb, lsb, PA_BUS_BITS
+        ));
+    }
+    let width = msb - lsb + 1;
+    if width > 32 {
+        return Err(format!("bits [{}:{}] are wider than 32 bits", msb, lsb));
+    }
+    if width < 32 && value >> width != 0 {
+        return Err(format!(
+            "{:#x} does not fit in bits [{}:{}]",
+            value, msb, lsb
+        ));
+    }
+
+    let mut units = Vec::new();
+    for byte_num in lsb / 8..=msb / 8 {
+        let lo = lsb.max(byte_num * 8);
+        let hi = msb.min(byte_num * 8 + 7);
+        let bits = (1u32 << (hi - lo + 1)) - 1;
+        let shift = lo - byte_num * 8;
+        units.push(MaskCompareUnit {
+            byte_num: byte_num as u8,
+            mask: (bits << shift) as u8,
+            compare: (((value >> (lo - lsb)) & bits) << shift) as u8,
+        });
+    }
+    Ok(units)
+}
+
+/// A trigger compiled by `TriggerBuilder`, ready to be loaded into a
+/// Performance Analyzer.
+#[derive(Debug, Clone)]
+pub struct PATrigger {
+    mask_compare: Vec<MaskCompareUnit>,
+    mask_compare_detect: Vec<(u16, u16)>,
+    transitions: Vec<TransitionUnit>,
+    captures: Vec<CaptureUnit>,
+    counters: [Option<u32>; 2],
+    start_states: u8,
+}
+
+impl PATrigger {
+    /// Configures the trigger units of a Performance Analyzer.
+    ///
+    /// **Note!** Like the `set_*` methods, this only updates the local
+    /// configuration. The global configuration is left to the caller.
+    pub fn configure<'a>(&self, pa: PerformanceAnalyzer<'a>) -> PerformanceAnalyzer<'a> {
+        let mut pa = pa;
+        for (unit_num, unit) in self.mask_compare.iter().enumerate() {
+            pa = pa.set_mask_compare(
+                unit.byte_num,
+                unit_num as u8,
+                unit.mask,
+                unit.compare,
+                false,
+            );
+        }
+        for (unit_num, (mask, compare)) in self.mask_compare_detect.iter().enumerate() {
+            pa = pa.set_mask_compare_detect(unit_num as u8, *mask, *compare);
+        }
+        for (transition_num, unit) in self.transitions.iter().enumerate() {
+            pa = pa.set_state_transition(
+                transition_num as u8,
+                unit.state_mask,
+                unit.mcd_mask,
+                unit.mcd_compare,
+                unit.counters_zero_mask,
+                unit.counters_nonzero_mask,
+                unit.ext_mask,
+                false,
+                unit.counter_dec_mask,
+                unit.counter_inc_mask,
+                unit.counter_restart_mask,
+                unit.destination_mask,
+            );
+        }
+        for (unit_num, unit) in self.captures.iter().enumerate() {
+            pa = pa.set_capture_tcam(
+                unit_num as u8,
+                unit.capture_type,
+                unit.source,
+                unit.mask,
+                unit.mask,
+                false,
+            );
+        }
+        pa
+    }
+
+    /// Loads the trigger counter restart values and starts the trigger in its
+    /// start states. Call after `start_pa()`.
+    pub fn start(&self, pa: &mut PerformanceAnalyzer) {
+        for (counter_num, restart_value) in self.counters.iter().enumerate() {
+            if let Some(restart_value) = restart_value {
+                pa.set_trigger_counter_restart(counter_num as u8, *restart_value);
+            }
+        }
+        pa.trigger_idle();
+        pa.trigger_start(self.start_states, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(byte_num: u8, mask: u8, compare: u8) -> MaskCompareUnit {
+        MaskCompareUnit {
+            byte_num,
+            mask,
+            compare,
+        }
+    }
+
+    fn bus_match(msb: u32, lsb: u32, value: u32) -> BusMatch {
+        BusMatch { msb, lsb, value }
+    }
+
+    // A trigger that captures whenever any of `conditions` holds.
+    fn capture_conditions(conditions: &[(&str, TriggerCondition)]) -> TriggerBuilder {
+        conditions.iter().fold(
+            TriggerBuilder::new().start_in(0),
+            |builder, (name, condition)| {
+                builder
+                    .condition(name, condition.clone())
+                    .capture(TriggerSource::Condition(name.to_string()))
+            },
+        )
+    }
+
+    #[test]
+    fn compile_bus_match_single_byte() {
+        assert_eq!(
+            compile_bus_match(&bus_match(15, 8, 0x42)),
+            Ok(vec![unit(1, 0xFF, 0x42)])
+        );
+        assert_eq!(
+            compile_bus_match(&bus_match(5, 5, 1)),
+            Ok(vec![unit(0, 0x20, 0x20)])
+        );
+    }
+
+    #[test]
+    fn compile_bus_match_multi_byte() {
+        // Partial bytes at both ends of the range.
+        assert_eq!(
+            compile_bus_match(&bus_match(19, 4, 0xABCD)),
+            Ok(vec![
+                unit(0, 0xF0, 0xD0),
+                unit(1, 0xFF, 0xBC),
+                unit(2, 0x0F, 0x0A)
+            ])
+        );
+        // A full 32-bit word at the top of the bus.
+        assert_eq!(
+            compile_bus_match(&bus_match(95, 64, 0xDEAD_BEEF)),
+            Ok(vec![
+                unit(8, 0xFF, 0xEF),
+                unit(9, 0xFF, 0xBE),
+                unit(10, 0xFF, 0xAD),
+                unit(11, 0xFF, 0xDE)
+            ])
+        );
+    }
+
+    #[test]
+    fn compile_bus_match_errors() {
+        assert!(compile_bus_match(&bus_match(3, 4, 0)).is_err());
+        assert!(compile_bus_match(&bus_match(96, 90, 0)).is_err());
+        assert_eq!(
+            compile_bus_match(&bus_match(40, 8, 0)),
+            Err("bits [40:8] are wider than 32 bits".to_string())
+        );
+        assert_eq!(
+            compile_bus_match(&bus_match(11, 8, 0x10)),
+            Err("0x10 does not fit in bits [11:8]".to_string())
+        );
+    }
+
+    #[test]
+    fn conditions_share_mask_compare_units() {
+        let trigger = capture_conditions(&[
+            ("a", TriggerCondition::bits(15, 8, 0x42)),
+            (
+                "b",
+                TriggerCondition::bits(15, 8, 0x42).and_bits(23, 16, 0x01),
+            ),
+            ("c", TriggerCondition::bits(23, 16, 0x01)),
+        ])
+        .build()
+        .unwrap();
+
+        assert_eq!(
+            trigger.mask_compare,
+            [unit(1, 0xFF, 0x42), unit(2, 0xFF, 0x01)]
+        );
+        assert_eq!(
+            trigger.mask_compare_detect,
+            [(0b01, 0b01), (0b11, 0b11), (0b10, 0b10)]
+        );
+        let capture_masks: Vec<u8> = trigger.captures.iter().map(|unit| unit.mask).collect();
+        assert_eq!(capture_masks, [0b001, 0b010, 0b100]);
+    }
+
+    #[test]
+    fn out_of_mask_compare_units() {
+        // Each 32-bit match needs four mask/compare units, as the values
+        // differ in every byte.
+        let conditions: Vec<(String, TriggerCondition)> = (1..=5)
+            .map(|i| {
+                let value = i * 0x0101_0101;
+                (format!("c{}", i), TriggerCondition::bits(31, 0, value))
+            })
+            .collect();
+        let conditions: Vec<(&str, TriggerCondition)> = conditions
+            .iter()
+            .map(|(name, condition)| (name.as_str(), condition.clone()))
+            .collect();
+
+        assert!(capture_conditions(&conditions[..4]).build().is_ok());
+        assert_eq!(
+            capture_conditions(&conditions).build().unwrap_err(),
+            "The conditions need more than the 16 mask/compare units"
+        );
+    }
+
+    #[test]
+    fn out_of_mask_compare_detect_units() {
+        let names: Vec<String> = (0..9).map(|i| format!("c{}", i)).collect();
+        let conditions: Vec<(&str, TriggerCondition)> = names
+            .iter()
+            .map(|name| (name.as_str(), TriggerCondition::bit_set(0)))
+            .collect();
+
+        // The conditions all share one mask/compare unit.
+        let trigger = capture_conditions(&conditions[..8]).build().unwrap();
+        assert_eq!(trigger.mask_compare.len(), 1);
+        assert_eq!(
+            capture_conditions(&conditions).build().unwrap_err(),
+            "At most 8 conditions are supported"
+        );
+    }
+
+    #[test]
+    fn out_of_transition_and_capture_units() {
+        let transitions = (0..9).fold(TriggerBuilder::new().start_in(0), |builder, i| {
+            builder.transition(&format!("t{}", i), StateTransition::new(0, 1))
+        });
+        assert_eq!(
+            transitions
+                .capture(TriggerSource::Transition("t0".to_string()))
+                .build()
+                .unwrap_err(),
+            "At most 8 state transitions are supported"
+        );
+
+        let captures = (0..9).fold(
+            TriggerBuilder::new()
+                .start_in(0)
+                .condition("hit", TriggerCondition::bit_set(0)),
+            |builder, _| builder.capture(TriggerSource::Condition("hit".to_string())),
+        );
+        assert_eq!(
+            captures.build().unwrap_err(),
+            "At most 8 capture rules are supported"
+        );
+    }
+}
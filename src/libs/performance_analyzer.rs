@@ -5,6 +5,8 @@ use bitfield::bitfield;
 use bitfield::fmt::Debug;
 
 use crate::libs::expansion_bar::ExpansionBar;
+use crate::libs::pa_recipe::PARecipe;
+use crate::libs::pa_stream::fifo_fill;
 use crate::libs::xpb_bus::{xpb_read, xpb_write};
 
 /// Performance Analyzer XPB register MAP offsets.
@@ -36,6 +38,29 @@ const PA_CAPTURE_TCAM: [u32; 8] = [
 ];
 const PA_PERFORMANCE_COUNTER: [u32; 4] = [0x00E0, 0x00E4, 0x00E8, 0x00EC];
 
+/// Island-local XPB base addresses of the Performance Analyzer peripheral.
+/// Only the RFPC islands are listed: they share one island layout, with the
+/// PA at 0x0F0000 as in RFPC island 0. The PA bases of the PCIe, NBI and EMU
+/// islands have not been confirmed yet.
+const PA_BASE_ADDRESSES: [(CppIsland, u32); 7] = [
+    (CppIsland::Rfpc0, 0x000F0000),
+    (CppIsland::Rfpc1, 0x000F0000),
+    (CppIsland::Rfpc2, 0x000F0000),
+    (CppIsland::Rfpc3, 0x000F0000),
+    (CppIsland::Rfpc4, 0x000F0000),
+    (CppIsland::Rfpc5, 0x000F0000),
+    (CppIsland::Rfpc6, 0x000F0000),
+];
+
+/// Returns the XPB base address of the Performance Analyzer in an island, or
+/// `None` if it is not known.
+pub fn pa_base_address(cpp_island: CppIsland) -> Option<u32> {
+    PA_BASE_ADDRESSES
+        .iter()
+        .find(|(island, _)| *island == cpp_island)
+        .map(|(_, base_addr)| *base_addr)
+}
+
 // PAConfig bitfields (see High Speed Performance Analyzer Peripheral EAS v0.3,
 // section 2.3)
 bitfield! {
@@ -365,16 +390,16 @@ impl<'a> PerformanceAnalyzer<'a> {
         let mut state_transitions: Vec<(PATriggerTransitionConfig0, PATriggerTransitionConfig1)> =
             Vec::new();
 
-        for _ in 0..7 {
+        // One entry per trigger state transition register pair, addressed by
+        // the 3-bit transition_num of `set_state_transition()`.
+        for _ in 0..PA_TRIGGER_TRANSITION_CONFIG.len() {
             let config0 = PATriggerTransitionConfig0(0);
             let config1 = PATriggerTransitionConfig1(0);
             state_transitions.push((config0, config1));
         }
 
-        let pa_base_addr: u32 = match cpp_island {
-            CppIsland::Rfpc0 => 0x000F0000,
-            _ => panic!("Island not supported yet"),
-        };
+        let pa_base_addr = pa_base_address(cpp_island)
+            .unwrap_or_else(|| panic!("Island {} not supported yet", cpp_island));
 
         PerformanceAnalyzer {
             exp_bar,
@@ -395,6 +420,39 @@ impl<'a> PerformanceAnalyzer<'a> {
         self
     }
 
+    /// Stops the Performance Analyzer by clearing the active bit of its
+    /// configuration. The rest of the configuration is kept, so the captured
+    /// data (FIFO, histogram and performance counters) can be read back.
+    pub fn stop_pa(&mut self) {
+        self.pa_configuration.set_active(false);
+        xpb_write(
+            self.exp_bar,
+            &self.cpp_island,
+            self.pa_base_addr + PA_CONFIG,
+            vec![self.pa_configuration.0],
+            false,
+        );
+    }
+
+    /// Switches FIFO journalling on or off while the Performance Analyzer is
+    /// running. When journalling, a full FIFO keeps capturing and overwrites
+    /// its oldest entries (setting the overflow flag) instead of stopping.
+    pub fn set_journalling(&mut self, journalling: bool) {
+        self.pa_configuration.set_journalling(journalling);
+        xpb_write(
+            self.exp_bar,
+            &self.cpp_island,
+            self.pa_base_addr + PA_CONFIG,
+            vec![self.pa_configuration.0],
+            false,
+        );
+    }
+
+    /// Returns a copy of the local global configuration.
+    pub fn global_config(&self) -> PAConfig {
+        PAConfig(self.pa_configuration.0)
+    }
+
     /// Configures the Performance Analyzer for a specific mode of operation.
     ///
     /// **Note!** This method does not configure the `PAConfig` register
@@ -767,6 +825,53 @@ impl<'a> PerformanceAnalyzer<'a> {
         self
     }
 
+    /// Returns the local configuration as a named recipe, which can be saved
+    /// to a file and replayed later with `apply_recipe()`.
+    ///
+    /// As most of the Performance Analyzer configuration registers are
+    /// write-only, the recipe is built from the local configuration and not
+    /// read back from the peripheral.
+    pub fn recipe(&self, name: &str) -> PARecipe {
+        let mut recipe = PARecipe::new(name);
+        recipe.config = PAConfig(self.pa_configuration.0);
+        for (unit, mc_val) in self.mask_compare_units.iter().enumerate() {
+            recipe.mask_compare[unit] = PAMaskCompare(mc_val.0);
+            recipe.mask_compare[unit].set_mask_compare(unit as u32);
+        }
+        recipe.mask_compare_detect = self.mask_compare_detect_units.clone();
+        recipe.capture_tcam = self.tcam_capture_units.clone();
+        recipe.state_transitions = self.state_transitions.clone();
+        recipe
+    }
+
+    /// Replaces the local configuration with the configuration of a recipe.
+    ///
+    /// **Note!** This method does not configure the registers on the
+    /// Performance Analyzer Peripheral itself. It only updates the local
+    /// configuration. The registers are only updated when the
+    /// `_apply_configuration()` method is called when starting the Performance
+    /// Analyzer.
+    ///
+    /// # Parameters
+    ///
+    /// - `recipe`: `&PARecipe` - Recipe to configure the Performance Analyzer with.
+    ///
+    /// # Returns
+    ///
+    /// Returns a mutable reference to `self`.
+    pub fn apply_recipe(mut self, recipe: &PARecipe) -> Self {
+        self.pa_configuration = PAConfig(recipe.config.0);
+        for (unit, mc_val) in recipe.mask_compare.iter().enumerate() {
+            self.mask_compare_units[unit] = PAMaskCompare(mc_val.0);
+            self.mask_compare_units[unit].set_mask_compare(unit as u32);
+        }
+        self.mask_compare_detect_units = recipe.mask_compare_detect.clone();
+        self.tcam_capture_units = recipe.capture_tcam.clone();
+        self.state_transitions = recipe.state_transitions.clone();
+
+        self
+    }
+
     /// Applies the local configuration to the Performance Analyzer XPB registers.
     ///
     /// This method ensures that any local configuration changes are written to
@@ -950,7 +1055,7 @@ impl<'a> PerformanceAnalyzer<'a> {
         let entries_in_fifo = if fifo_control.overflow() && !self.pa_configuration.journalling() {
             4096
         } else {
-            fifo_control.write_ptr() - fifo_control.read_ptr()
+            fifo_fill(&fifo_control)
         };
 
         // Check if the FIFO is empty
@@ -981,6 +1086,43 @@ impl<'a> PerformanceAnalyzer<'a> {
         fifo_words
     }
 
+    /// Reads exactly `num_words` words from the FIFO data register, without
+    /// checking the FIFO fill level first. Use with `read_fifo_control()` to
+    /// drain a FIFO that is being written to.
+    pub fn read_fifo_data(&mut self, num_words: u32) -> Vec<u32> {
+        (0..num_words)
+            .map(|_| {
+                xpb_read(
+                    self.exp_bar,
+                    &self.cpp_island,
+                    self.pa_base_addr + PA_FIFO_DATA,
+                    1,
+                    false,
+                )[0]
+            })
+            .collect()
+    }
+
+    /// Reads the FIFO control register of the Performance Analyzer.
+    ///
+    /// Useful to check whether the FIFO holds any samples before calling
+    /// `read_fifo()`, which panics on an empty FIFO.
+    ///
+    /// # Returns
+    ///
+    /// A `PAFifoControl` instance with the current read/write pointers and
+    /// the empty/overflow flags.
+    pub fn read_fifo_control(&mut self) -> PAFifoControl {
+        let raw_val = xpb_read(
+            self.exp_bar,
+            &self.cpp_island,
+            self.pa_base_addr + PA_FIFO_CONTROL,
+            1,
+            false,
+        );
+        PAFifoControl(raw_val[0])
+    }
+
     /// Reads the current status of the trigger.
     ///
     /// # Returns
@@ -4,6 +4,8 @@ use crate::libs::expansion_bar::ExpansionBar;
 use crate::libs::rfpc::{Rfpc, RfpcCsr, RfpcReg};
 use crate::libs::xpb_bus::{xpb_read, xpb_write};
 
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -120,25 +122,155 @@ const RISCV_DBG_DCSR_NMIP: u32 = 0x1 << 3;
 const RISCV_DBG_DCSR_STEP: u32 = 0x1 << 2;
 const RISCV_DBG_DCSR_PRV: u32 = 0x3 << 0;
 
-pub fn read_rfpc_reg(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, reg: &Box<dyn RfpcReg>) -> u64 {
+/// Polling and timeout settings for the RFPC debugger.
+///
+/// All waits on the debug module go through a common poll loop. The loop
+/// first reads the status register back-to-back `spin_polls` times, since
+/// most halts, steps and abstract commands complete within a few XPB reads.
+/// After that it sleeps between polls, starting at `min_poll_interval` and
+/// doubling up to `max_poll_interval`, so long waits (e.g. continuing to a
+/// breakpoint) don't hammer the bus.
+///
+/// # Fields
+///
+/// * `halt_timeout`: Time to wait for the hart to halt after a halt request.
+/// * `resume_timeout`: Time to wait for the hart to acknowledge a resume.
+/// * `step_timeout`: Time to wait for the hart to halt after a single step.
+/// * `continue_timeout`: Time to wait for a breakpoint after continuing, or
+///   `None` to wait until cancelled.
+/// * `abstract_cmd_timeout`: Time to wait for an abstract command to finish.
+/// * `spin_polls`: Number of polls issued before sleeping between polls.
+/// * `min_poll_interval`: First sleep duration once spinning is done.
+/// * `max_poll_interval`: Upper bound of the sleep between polls.
+/// * `running`: Server run flag. Clearing it cancels a pending continue.
+#[derive(Clone, Debug)]
+pub struct DebugConfig {
+    pub halt_timeout: Duration,
+    pub resume_timeout: Duration,
+    pub step_timeout: Duration,
+    pub continue_timeout: Option<Duration>,
+    pub abstract_cmd_timeout: Duration,
+    pub spin_polls: u32,
+    pub min_poll_interval: Duration,
+    pub max_poll_interval: Duration,
+    pub running: Arc<AtomicBool>,
+}
+
+impl DebugConfig {
+    /// Creates a debugger configuration with the default timeouts, tied to
+    /// the given run flag.
+    pub fn new(running: Arc<AtomicBool>) -> Self {
+        DebugConfig {
+            halt_timeout: Duration::from_secs(10),
+            resume_timeout: Duration::from_secs(10),
+            step_timeout: Duration::from_secs(10),
+            continue_timeout: Some(Duration::from_secs(40)),
+            abstract_cmd_timeout: Duration::from_secs(10),
+            spin_polls: 64,
+            min_poll_interval: Duration::from_micros(50),
+            max_poll_interval: Duration::from_millis(100),
+            running,
+        }
+    }
+}
+
+impl Default for DebugConfig {
+    fn default() -> Self {
+        DebugConfig::new(Arc::new(AtomicBool::new(true)))
+    }
+}
+
+/// Polls a condition until it holds, with adaptive backoff.
+///
+/// The condition is evaluated back-to-back `config.spin_polls` times before
+/// the loop starts sleeping between evaluations. The sleep starts at
+/// `config.min_poll_interval` and doubles up to `config.max_poll_interval`.
+///
+/// # Parameters
+///
+/// * `config`: Polling configuration.
+/// * `timeout`: Time after which the wait panics, or `None` to wait forever.
+/// * `cancellable`: Stop waiting when `config.running` is cleared.
+/// * `timeout_msg`: Panic message used when the timeout is reached.
+/// * `poll`: Closure evaluating the condition (usually a DM register read).
+///
+/// # Returns
+///
+/// `true` once the condition holds, `false` if the wait was cancelled.
+///
+/// # Panics
+///
+/// Panics with `timeout_msg` if the timeout is reached.
+pub fn poll_until<F>(
+    config: &DebugConfig,
+    timeout: Option<Duration>,
+    cancellable: bool,
+    timeout_msg: &str,
+    mut poll: F,
+) -> bool
+where
+    F: FnMut() -> bool,
+{
+    let start_time = Instant::now();
+    let mut polls: u32 = 0;
+    let mut interval = config.min_poll_interval;
+    loop {
+        if poll() {
+            return true;
+        }
+        if cancellable && !config.running.load(Ordering::SeqCst) {
+            return false;
+        }
+        if let Some(timeout) = timeout {
+            if start_time.elapsed() > timeout {
+                panic!("{}", timeout_msg);
+            }
+        }
+
+        // Spin first, then back off exponentially.
+        polls = polls.saturating_add(1);
+        if polls > config.spin_polls {
+            thread::sleep(interval);
+            interval = (interval * 2).min(config.max_poll_interval);
+        }
+    }
+}
+
+/// Reads a debug module register of the cluster the RFPC core belongs to.
+fn dm_read(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, dm_reg: u32) -> u32 {
+    xpb_read(exp_bar, &rfpc.island, rfpc.dm_xpb_base() + dm_reg, 1, true)[0]
+}
+
+pub fn read_rfpc_reg(
+    exp_bar: &mut ExpansionBar,
+    rfpc: &Rfpc,
+    config: &DebugConfig,
+    reg: &Box<dyn RfpcReg>,
+) -> u64 {
     let reg_addr = reg.reg_addr();
 
-    rfpc_dbg_halt(exp_bar, rfpc);
-    let val = rfpc_dbg_read_reg(exp_bar, rfpc, reg_addr);
-    rfpc_dbg_resume(exp_bar, rfpc);
+    rfpc_dbg_halt(exp_bar, rfpc, config);
+    let val = rfpc_dbg_read_reg(exp_bar, rfpc, config, reg_addr);
+    rfpc_dbg_resume(exp_bar, rfpc, config);
 
     val
 }
 
-pub fn write_rfpc_reg(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, reg: &Box<dyn RfpcReg>, value: u64) {
+pub fn write_rfpc_reg(
+    exp_bar: &mut ExpansionBar,
+    rfpc: &Rfpc,
+    config: &DebugConfig,
+    reg: &Box<dyn RfpcReg>,
+    value: u64,
+) {
     let reg_addr = reg.reg_addr();
 
-    rfpc_dbg_halt(exp_bar, rfpc);
-    rfpc_dbg_write_reg(exp_bar, rfpc, reg_addr, value);
-    rfpc_dbg_resume(exp_bar, rfpc);
+    rfpc_dbg_halt(exp_bar, rfpc, config);
+    rfpc_dbg_write_reg(exp_bar, rfpc, config, reg_addr, value);
+    rfpc_dbg_resume(exp_bar, rfpc, config);
 }
 
-pub fn rfpc_dbg_halt(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) {
+pub fn rfpc_dbg_halt(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, config: &DebugConfig) {
     let (hartsello, _) = rfpc.dm_hartsel();
     let mut dmcontrol = hartsello << 16;
 
@@ -155,28 +287,16 @@ pub fn rfpc_dbg_halt(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) {
     );
 
     // Poll dmstatus until RFPC is halted.
-    let start_time = Instant::now();
-    let timeout_duration = Duration::new(10, 0);
-    loop {
-        if start_time.elapsed() > timeout_duration {
-            panic!("Timeout reached when waiting for RFPC core to halt after halt initiate!");
-        }
-
-        let dmstatus = xpb_read(
-            exp_bar,
-            &rfpc.island,
-            rfpc.dm_xpb_base() + RISCV_DBG_DMSTATUS,
-            1,
-            true,
-        )[0];
-        if dmstatus & RISCV_DBG_DMSTATUS_ALLHALTED != 0 {
-            break;
-        }
-        thread::sleep(Duration::from_millis(100));
-    }
+    poll_until(
+        config,
+        Some(config.halt_timeout),
+        false,
+        "Timeout reached when waiting for RFPC core to halt after halt initiate!",
+        || dm_read(exp_bar, rfpc, RISCV_DBG_DMSTATUS) & RISCV_DBG_DMSTATUS_ALLHALTED != 0,
+    );
 }
 
-pub fn rfpc_dbg_resume(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) {
+pub fn rfpc_dbg_resume(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, config: &DebugConfig) {
     let (hartsello, _) = rfpc.dm_hartsel();
     let mut dmcontrol = hartsello << 16;
 
@@ -193,30 +313,19 @@ pub fn rfpc_dbg_resume(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) {
     );
 
     // Poll dmstatus until RFPC has resumed.
-    let start_time = Instant::now();
-    let timeout_duration = Duration::new(10, 0);
-    loop {
-        if start_time.elapsed() > timeout_duration {
-            panic!("Timeout reached when trying to resume RFPC core after resume initiate!");
-        }
-        let dmstatus = xpb_read(
-            exp_bar,
-            &rfpc.island,
-            rfpc.dm_xpb_base() + RISCV_DBG_DMSTATUS,
-            1,
-            true,
-        )[0];
-        if dmstatus & RISCV_DBG_DMSTATUS_ALLRUNNING != 0 {
-            break;
-        }
-        thread::sleep(Duration::from_millis(100));
-    }
+    poll_until(
+        config,
+        Some(config.resume_timeout),
+        false,
+        "Timeout reached when trying to resume RFPC core after resume initiate!",
+        || dm_read(exp_bar, rfpc, RISCV_DBG_DMSTATUS) & RISCV_DBG_DMSTATUS_ALLRUNNING != 0,
+    );
 }
 
-pub fn rfpc_dbg_single_step(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) {
-    let mut dcsr_reg = rfpc_dbg_read_reg(exp_bar, rfpc, RfpcCsr::Dcsr.reg_addr());
+pub fn rfpc_dbg_single_step(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, config: &DebugConfig) {
+    let mut dcsr_reg = rfpc_dbg_read_reg(exp_bar, rfpc, config, RfpcCsr::Dcsr.reg_addr());
     dcsr_reg |= RISCV_DBG_DCSR_STEP as u64;
-    rfpc_dbg_write_reg(exp_bar, rfpc, RfpcCsr::Dcsr.reg_addr(), dcsr_reg);
+    rfpc_dbg_write_reg(exp_bar, rfpc, config, RfpcCsr::Dcsr.reg_addr(), dcsr_reg);
 
     // Write resume request to dmcontrol to initiate resume.
     let (hartsello, _) = rfpc.dm_hartsel();
@@ -232,39 +341,60 @@ pub fn rfpc_dbg_single_step(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) {
     );
 
     // Poll dmstatus until RFPC is halted.
-    let start_time = Instant::now();
-    let timeout_duration = Duration::new(10, 0);
-    loop {
-        if start_time.elapsed() > timeout_duration {
-            panic!("Timeout reached when wating for RFPC core halt after step!");
-        }
-
-        let dmstatus = xpb_read(
-            exp_bar,
-            &rfpc.island,
-            rfpc.dm_xpb_base() + RISCV_DBG_DMSTATUS,
-            1,
-            true,
-        )[0];
-        if dmstatus & RISCV_DBG_DMSTATUS_ALLHALTED != 0 {
-            break;
-        }
-        thread::sleep(Duration::from_millis(100));
-    }
+    poll_until(
+        config,
+        Some(config.step_timeout),
+        false,
+        "Timeout reached when wating for RFPC core halt after step!",
+        || dm_read(exp_bar, rfpc, RISCV_DBG_DMSTATUS) & RISCV_DBG_DMSTATUS_ALLHALTED != 0,
+    );
 
-    let mut dcsr_reg = rfpc_dbg_read_reg(exp_bar, rfpc, RfpcCsr::Dcsr.reg_addr());
+    let mut dcsr_reg = rfpc_dbg_read_reg(exp_bar, rfpc, config, RfpcCsr::Dcsr.reg_addr());
     let cause = (dcsr_reg as u32 & RISCV_DBG_DCSR_CAUSE) >> 6;
     if cause != 0x4 {
         panic!("The RFPC core did not single step!");
     }
     dcsr_reg &= !RISCV_DBG_DCSR_STEP as u64;
-    rfpc_dbg_write_reg(exp_bar, rfpc, RfpcCsr::Dcsr.reg_addr(), dcsr_reg);
+    rfpc_dbg_write_reg(exp_bar, rfpc, config, RfpcCsr::Dcsr.reg_addr(), dcsr_reg);
+}
+
+/// Resumes the RFPC core with ebreak enabled and waits for it to halt on a
+/// breakpoint.
+///
+/// The wait is bounded by `config.continue_timeout` and is abandoned when
+/// `config.running` is cleared, in which case the core is left running.
+///
+/// # Returns
+///
+/// `true` if the core halted on a breakpoint, `false` if the wait was
+/// cancelled.
+pub fn rfpc_dbg_continue(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, config: &DebugConfig) -> bool {
+    rfpc_dbg_continue_start(exp_bar, rfpc, config);
+
+    // Poll dmstatus until RFPC is halted.
+    let halted = poll_until(
+        config,
+        config.continue_timeout,
+        true,
+        "Timeout reached when wating for RFPC core halt after continue!",
+        || dm_read(exp_bar, rfpc, RISCV_DBG_DMSTATUS) & RISCV_DBG_DMSTATUS_ALLHALTED != 0,
+    );
+    if !halted {
+        return false;
+    }
+
+    rfpc_dbg_continue_finish(exp_bar, rfpc, config);
+    true
 }
 
-pub fn rfpc_dbg_continue(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) {
-    let mut dcsr_reg = rfpc_dbg_read_reg(exp_bar, rfpc, RfpcCsr::Dcsr.reg_addr());
+/// First half of `rfpc_dbg_continue`: enables ebreak and resumes the RFPC
+/// core without waiting for it to halt. Callers that share the bus with
+/// other debug sessions poll `rfpc_dbg_is_halted` themselves, then call
+/// `rfpc_dbg_continue_finish`.
+pub fn rfpc_dbg_continue_start(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, config: &DebugConfig) {
+    let mut dcsr_reg = rfpc_dbg_read_reg(exp_bar, rfpc, config, RfpcCsr::Dcsr.reg_addr());
     dcsr_reg |= (RISCV_DBG_DCSR_EBREAKM | RISCV_DBG_DCSR_EBREAKU) as u64;
-    rfpc_dbg_write_reg(exp_bar, rfpc, RfpcCsr::Dcsr.reg_addr(), dcsr_reg);
+    rfpc_dbg_write_reg(exp_bar, rfpc, config, RfpcCsr::Dcsr.reg_addr(), dcsr_reg);
 
     // Write resume request to dmcontrol to initiate resume.
     let (hartsello, _) = rfpc.dm_hartsel();
@@ -278,60 +408,150 @@ pub fn rfpc_dbg_continue(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) {
         vec![dmcontrol],
         true,
     );
+}
 
-    // Poll dmstatus until RFPC is halted.
-    let start_time = Instant::now();
-    let timeout_duration = Duration::new(40, 0);
-    loop {
-        if start_time.elapsed() > timeout_duration {
-            panic!("Timeout reached when wating for RFPC core halt after step!");
-        }
-
-        let dmstatus = xpb_read(
-            exp_bar,
-            &rfpc.island,
-            rfpc.dm_xpb_base() + RISCV_DBG_DMSTATUS,
-            1,
-            true,
-        )[0];
-        if dmstatus & RISCV_DBG_DMSTATUS_ALLHALTED != 0 {
-            break;
-        }
-        thread::sleep(Duration::from_millis(100));
-    }
-
-    let mut dcsr_reg = rfpc_dbg_read_reg(exp_bar, rfpc, RfpcCsr::Dcsr.reg_addr());
+/// Second half of `rfpc_dbg_continue`, once the core has halted: checks that
+/// it halted on a breakpoint and disables ebreak again.
+///
+/// # Panics
+///
+/// Panics if the core halted for another reason than a breakpoint.
+pub fn rfpc_dbg_continue_finish(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, config: &DebugConfig) {
+    let mut dcsr_reg = rfpc_dbg_read_reg(exp_bar, rfpc, config, RfpcCsr::Dcsr.reg_addr());
     let cause = (dcsr_reg as u32 & RISCV_DBG_DCSR_CAUSE) >> 6;
     if cause != 0x1 {
         panic!("The RFPC core did not breakpoint, cause = 0x{:x}!", cause);
     }
     dcsr_reg &= !(RISCV_DBG_DCSR_EBREAKM | RISCV_DBG_DCSR_EBREAKU) as u64;
-    rfpc_dbg_write_reg(exp_bar, rfpc, RfpcCsr::Dcsr.reg_addr(), dcsr_reg);
+    rfpc_dbg_write_reg(exp_bar, rfpc, config, RfpcCsr::Dcsr.reg_addr(), dcsr_reg);
 }
 
-fn abstract_cmd_busy_wait(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) {
-    let mut abstractcs: u32;
-    let start_time = Instant::now();
-    let timeout_duration = Duration::new(10, 0);
-    loop {
-        if start_time.elapsed() > timeout_duration {
-            panic!("Timeout reached in rfpc_dbg_abstractcmd()!");
-        }
-        abstractcs = xpb_read(
-            exp_bar,
-            &rfpc.island,
-            rfpc.dm_xpb_base() + RISCV_DBG_ABSTRACTCS,
-            1,
-            true,
-        )[0];
-        if (abstractcs & RISCV_DBG_ABSTRACTCS_BUSY) == 0 {
-            break;
-        }
-        thread::sleep(Duration::from_millis(100));
+/// Clears the ebreak bits of dcsr that `rfpc_dbg_continue` sets, so `ebreak`
+/// instructions no longer enter debug mode. The core must be halted.
+pub fn rfpc_dbg_clear_ebreak(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, config: &DebugConfig) {
+    let mut dcsr_reg = rfpc_dbg_read_reg(exp_bar, rfpc, config, RfpcCsr::Dcsr.reg_addr());
+    dcsr_reg &= !(RISCV_DBG_DCSR_EBREAKM | RISCV_DBG_DCSR_EBREAKS | RISCV_DBG_DCSR_EBREAKU) as u64;
+    rfpc_dbg_write_reg(exp_bar, rfpc, config, RfpcCsr::Dcsr.reg_addr(), dcsr_reg);
+}
+
+/// Reset issued by `rfpc_dbg_reset`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ResetType {
+    Hart,     // dmcontrol.hartreset: reset only the selected hart.
+    NdmReset, // dmcontrol.ndmreset: reset everything except the debug module.
+}
+
+/// Writes dmcontrol for the RFPC core with the given request bits set.
+fn dm_control_write(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, request: u32) {
+    let (hartsello, _) = rfpc.dm_hartsel();
+    let dmcontrol = (hartsello << 16) | RISCV_DBG_DMCONTROL_DMACTIVE | request;
+    xpb_write(
+        exp_bar,
+        &rfpc.island,
+        rfpc.dm_xpb_base() + RISCV_DBG_DMCONTROL,
+        vec![dmcontrol],
+        true,
+    );
+}
+
+/// Acknowledges the havereset status of the RFPC core.
+///
+/// The debug module keeps dmstatus.allhavereset/anyhavereset set after a
+/// reset until the debugger acknowledges it.
+pub fn rfpc_dbg_ack_havereset(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) {
+    dm_control_write(exp_bar, rfpc, RISCV_DBG_DMCONTROL_ACKHAVERESET);
+}
+
+/// Resets the RFPC core and optionally halts it at the reset vector.
+///
+/// When `halt` is set, a halt-on-reset request is armed before the reset is
+/// asserted, so the core halts before executing its first instruction. The
+/// request is cleared again afterwards so later resets run freely.
+///
+/// **Note!** `ResetType::NdmReset` resets every hart behind the debug module,
+/// not only the selected core. Memory contents (including any inserted
+/// breakpoints) are not affected by either reset.
+///
+/// # Parameters
+///
+/// * `exp_bar`: Expansion BAR used for the XPB accesses.
+/// * `rfpc`: RFPC core to reset.
+/// * `config`: Polling configuration. The halt timeout bounds the reset waits.
+/// * `reset_type`: Whether to issue a hart reset or a non-debug-module reset.
+/// * `halt`: Halt the core at the reset vector instead of letting it run.
+///
+/// # Panics
+///
+/// Panics if the core does not report havereset, or does not halt/run after
+/// the reset, within the halt timeout.
+pub fn rfpc_dbg_reset(
+    exp_bar: &mut ExpansionBar,
+    rfpc: &Rfpc,
+    config: &DebugConfig,
+    reset_type: ResetType,
+    halt: bool,
+) {
+    // Arm (or disarm) halt-on-reset before asserting the reset.
+    if halt {
+        dm_control_write(exp_bar, rfpc, RISCV_DBG_DMCONTROL_SETRESETHALTREQ);
+    } else {
+        dm_control_write(exp_bar, rfpc, RISCV_DBG_DMCONTROL_CLRRESETHALTREQ);
     }
+
+    // Pulse the reset.
+    let reset_req = match reset_type {
+        ResetType::Hart => RISCV_DBG_DMCONTROL_HARTRESET,
+        ResetType::NdmReset => RISCV_DBG_DMCONTROL_NDMRESET,
+    };
+    dm_control_write(exp_bar, rfpc, reset_req);
+    dm_control_write(exp_bar, rfpc, 0);
+
+    poll_until(
+        config,
+        Some(config.halt_timeout),
+        false,
+        "Timeout reached when waiting for RFPC core to report havereset!",
+        || dm_read(exp_bar, rfpc, RISCV_DBG_DMSTATUS) & RISCV_DBG_DMSTATUS_ALLHAVERESET != 0,
+    );
+
+    if halt {
+        poll_until(
+            config,
+            Some(config.halt_timeout),
+            false,
+            "Timeout reached when waiting for RFPC core to halt after reset!",
+            || dm_read(exp_bar, rfpc, RISCV_DBG_DMSTATUS) & RISCV_DBG_DMSTATUS_ALLHALTED != 0,
+        );
+        dm_control_write(exp_bar, rfpc, RISCV_DBG_DMCONTROL_CLRRESETHALTREQ);
+    } else {
+        poll_until(
+            config,
+            Some(config.halt_timeout),
+            false,
+            "Timeout reached when waiting for RFPC core to run after reset!",
+            || dm_read(exp_bar, rfpc, RISCV_DBG_DMSTATUS) & RISCV_DBG_DMSTATUS_ALLRUNNING != 0,
+        );
+    }
+
+    rfpc_dbg_ack_havereset(exp_bar, rfpc);
 }
 
-pub fn rfpc_dbg_read_reg(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, reg_addr: u64) -> u64 {
+fn abstract_cmd_busy_wait(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, config: &DebugConfig) {
+    poll_until(
+        config,
+        Some(config.abstract_cmd_timeout),
+        false,
+        "Timeout reached in rfpc_dbg_abstractcmd()!",
+        || dm_read(exp_bar, rfpc, RISCV_DBG_ABSTRACTCS) & RISCV_DBG_ABSTRACTCS_BUSY == 0,
+    );
+}
+
+pub fn rfpc_dbg_read_reg(
+    exp_bar: &mut ExpansionBar,
+    rfpc: &Rfpc,
+    config: &DebugConfig,
+    reg_addr: u64,
+) -> u64 {
     let (hartsello, _) = rfpc.dm_hartsel();
     let mut dmcontrol = hartsello << 16;
 
@@ -354,7 +574,7 @@ pub fn rfpc_dbg_read_reg(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, reg_addr: u64)
         true,
     );
 
-    abstract_cmd_busy_wait(exp_bar, rfpc);
+    abstract_cmd_busy_wait(exp_bar, rfpc, config);
 
     // Read the lower 32 bits of the register value.
     let mut reg_val: u64 = xpb_read(
@@ -378,7 +598,13 @@ pub fn rfpc_dbg_read_reg(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, reg_addr: u64)
     reg_val
 }
 
-pub fn rfpc_dbg_write_reg(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, reg_addr: u64, value: u64) {
+pub fn rfpc_dbg_write_reg(
+    exp_bar: &mut ExpansionBar,
+    rfpc: &Rfpc,
+    config: &DebugConfig,
+    reg_addr: u64,
+    value: u64,
+) {
     let reg_gpr: bool = ((reg_addr >> 12) & 0xF) == 0x1;
     let (hartsello, _) = rfpc.dm_hartsel();
     let mut dmcontrol = hartsello << 16;
@@ -421,7 +647,7 @@ pub fn rfpc_dbg_write_reg(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, reg_addr: u64
             vec![gpr],
             true,
         );
-        abstract_cmd_busy_wait(exp_bar, rfpc);
+        abstract_cmd_busy_wait(exp_bar, rfpc, config);
         return;
     } else {
         // Execute ABSTRACT CMD (write values in DATA0 and DATA1 to X11 for CSR write).
@@ -434,7 +660,7 @@ pub fn rfpc_dbg_write_reg(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, reg_addr: u64
         );
     }
 
-    abstract_cmd_busy_wait(exp_bar, rfpc);
+    abstract_cmd_busy_wait(exp_bar, rfpc, config);
 
     // Write csrw instruction to progbuf0.
     let csr_write_instr: u32 = 0x00059073 | ((reg_addr as u32 & 0xFFF) << 20);
@@ -455,12 +681,13 @@ pub fn rfpc_dbg_write_reg(exp_bar: &mut ExpansionBar, rfpc: &Rfpc, reg_addr: u64
         true,
     );
 
-    abstract_cmd_busy_wait(exp_bar, rfpc);
+    abstract_cmd_busy_wait(exp_bar, rfpc, config);
 }
 
 pub fn rfpc_dbg_read_memory(
     exp_bar: &mut ExpansionBar,
     rfpc: &Rfpc,
+    config: &DebugConfig,
     address: u64,
     length: u64,
 ) -> Vec<u64> {
@@ -477,7 +704,7 @@ pub fn rfpc_dbg_read_memory(
 
     // Save RFPC GPR a0 (X10) temporarily, as it will be overwritten for
     // the memory read process.
-    let temp_a0 = rfpc_dbg_read_reg(exp_bar, rfpc, 0x100A);
+    let temp_a0 = rfpc_dbg_read_reg(exp_bar, rfpc, config, 0x100A);
 
     // Read from memory one 64-bit word at a time.
     let mut mem_words: Vec<u64> = Vec::new();
@@ -516,7 +743,7 @@ pub fn rfpc_dbg_read_memory(
             vec![0x37100A],
             true,
         );
-        abstract_cmd_busy_wait(exp_bar, rfpc);
+        abstract_cmd_busy_wait(exp_bar, rfpc, config);
 
         xpb_write(
             exp_bar,
@@ -525,7 +752,7 @@ pub fn rfpc_dbg_read_memory(
             vec![0x32100A],
             true,
         );
-        abstract_cmd_busy_wait(exp_bar, rfpc);
+        abstract_cmd_busy_wait(exp_bar, rfpc, config);
 
         // Read the lower 32 bits of the register value.
         let mut reg_val: u64 = xpb_read(
@@ -551,7 +778,7 @@ pub fn rfpc_dbg_read_memory(
     }
 
     // Restore RFPC GPR a0.
-    rfpc_dbg_write_reg(exp_bar, rfpc, 0x100A, temp_a0);
+    rfpc_dbg_write_reg(exp_bar, rfpc, config, 0x100A, temp_a0);
 
     mem_words
 }
@@ -559,6 +786,7 @@ pub fn rfpc_dbg_read_memory(
 pub fn rfpc_dbg_write_memory(
     exp_bar: &mut ExpansionBar,
     rfpc: &Rfpc,
+    config: &DebugConfig,
     address: u64,
     data: Vec<u64>,
 ) {
@@ -574,8 +802,8 @@ pub fn rfpc_dbg_write_memory(
     );
 
     // Save RFPC GPRs a0 and a1 temporarily.
-    let temp_a0 = rfpc_dbg_read_reg(exp_bar, rfpc, 0x100A);
-    let temp_a1 = rfpc_dbg_read_reg(exp_bar, rfpc, 0x100B);
+    let temp_a0 = rfpc_dbg_read_reg(exp_bar, rfpc, config, 0x100A);
+    let temp_a1 = rfpc_dbg_read_reg(exp_bar, rfpc, config, 0x100B);
 
     for (word_idx, data_word) in data.iter().enumerate() {
         let byte_addr = address + (8u64 * word_idx as u64);
@@ -604,7 +832,7 @@ pub fn rfpc_dbg_write_memory(
             vec![0x33100B],
             true,
         );
-        abstract_cmd_busy_wait(exp_bar, rfpc);
+        abstract_cmd_busy_wait(exp_bar, rfpc, config);
 
         // Write 64-bit word address to debug module data0/1 registers.
         xpb_write(
@@ -639,10 +867,73 @@ pub fn rfpc_dbg_write_memory(
             vec![0x37100A],
             true,
         );
-        abstract_cmd_busy_wait(exp_bar, rfpc);
+        abstract_cmd_busy_wait(exp_bar, rfpc, config);
     }
 
     // Restore RFPC GPRs a0 and a1.
-    rfpc_dbg_write_reg(exp_bar, rfpc, 0x100A, temp_a0);
-    rfpc_dbg_write_reg(exp_bar, rfpc, 0x100B, temp_a1);
+    rfpc_dbg_write_reg(exp_bar, rfpc, config, 0x100A, temp_a0);
+    rfpc_dbg_write_reg(exp_bar, rfpc, config, 0x100B, temp_a1);
+}
+
+/// Reads dmstatus with the RFPC core selected in dmcontrol.
+///
+/// # Returns
+///
+/// The raw 32-bit dmstatus value for the selected hart.
+pub fn rfpc_dbg_status(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) -> u32 {
+    dm_control_write(exp_bar, rfpc, 0);
+    dm_read(exp_bar, rfpc, RISCV_DBG_DMSTATUS)
+}
+
+/// Checks whether the selected hart is halted.
+pub fn rfpc_dbg_is_halted(exp_bar: &mut ExpansionBar, rfpc: &Rfpc) -> bool {
+    rfpc_dbg_status(exp_bar, rfpc) & RISCV_DBG_DMSTATUS_ALLHALTED != 0
+}
+
+/// Decodes the single-bit flags of a dmstatus value.
+///
+/// # Returns
+///
+/// The names of the flags that are set, in register bit order (MSB first).
+pub fn dmstatus_flags(dmstatus: u32) -> Vec<&'static str> {
+    let flags = [
+        (RISCV_DBG_DMSTATUS_IMPEBREAK, "impebreak"),
+        (RISCV_DBG_DMSTATUS_ALLHAVERESET, "allhavereset"),
+        (RISCV_DBG_DMSTATUS_ANYHAVERESET, "anyhavereset"),
+        (RISCV_DBG_DMSTATUS_ALLRESUMEACK, "allresumeack"),
+        (RISCV_DBG_DMSTATUS_ANYRESUMEACK, "anyresumeack"),
+        (RISCV_DBG_DMSTATUS_ALLNONEXISTENT, "allnonexistent"),
+        (RISCV_DBG_DMSTATUS_ANYNONEXISTENT, "anynonexistent"),
+        (RISCV_DBG_DMSTATUS_ALLUNAVAIL, "allunavail"),
+        (RISCV_DBG_DMSTATUS_ANYUNAVAIL, "anyunavail"),
+        (RISCV_DBG_DMSTATUS_ALLRUNNING, "allrunning"),
+        (RISCV_DBG_DMSTATUS_ANYRUNNING, "anyrunning"),
+        (RISCV_DBG_DMSTATUS_ALLHALTED, "allhalted"),
+        (RISCV_DBG_DMSTATUS_ANYHALTED, "anyhalted"),
+        (RISCV_DBG_DMSTATUS_AUTHENTICATED, "authenticated"),
+        (RISCV_DBG_DMSTATUS_AUTHBUSY, "authbusy"),
+        (RISCV_DBG_DMSTATUS_HASRESETHALTREQ, "hasresethaltreq"),
+        (RISCV_DBG_DMSTATUS_CONFSTRPTRVALID, "confstrptrvalid"),
+    ];
+
+    flags
+        .iter()
+        .filter(|(mask, _)| dmstatus & mask != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// Summarizes the run state of a hart from its dmstatus value.
+pub fn dmstatus_state(dmstatus: u32) -> &'static str {
+    if dmstatus & RISCV_DBG_DMSTATUS_ALLNONEXISTENT != 0 {
+        "nonexistent"
+    } else if dmstatus & RISCV_DBG_DMSTATUS_ALLUNAVAIL != 0 {
+        "unavailable"
+    } else if dmstatus & RISCV_DBG_DMSTATUS_ALLHALTED != 0 {
+        "halted"
+    } else if dmstatus & RISCV_DBG_DMSTATUS_ALLRUNNING != 0 {
+        "running"
+    } else {
+        "unknown"
+    }
 }
@@ -1,6 +1,8 @@
 #![allow(dead_code)]
 
 use crate::libs::expansion_bar::ExpansionBar;
+use crate::libs::pa_histogram::clear_histogram;
+use crate::libs::pa_recipe::PARecipe;
 use crate::libs::performance_analyzer::{
     CaptureMethod, CaptureMode, CaptureStart, EventMethod, HistogramSource, PerfCounterAction,
     PerformanceAnalyzer, TcamCaptureSource, TcamCaptureType,
@@ -8,17 +10,27 @@ use crate::libs::performance_analyzer::{
 use crate::libs::rfpc::Rfpc;
 use crate::libs::xpb_bus::xpb_write;
 use bitfield::bitfield;
+use std::fmt;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 
+// Maximum number of words read from the PA FIFO in one go.
+const PA_FIFO_SIZE: u32 = 4096;
+
+// The PA control registers are group control registers, addressed from the
+// group control block of the core's cluster and group.
 macro_rules! rfpc_pa_control {
-    ($cluster:expr, $group:expr) => {
-        0x280000 + (($cluster as u32) * 0xE) + (($group as u32) * 0x100) + 0x0020
-    };
+    ($rfpc:expr) => {{
+        let (cluster_base, group_offset) = $rfpc.group_ctl_xpb_base();
+        cluster_base + group_offset + 0x0020
+    }};
 }
 
 macro_rules! rfpc_perf_mux_config {
-    ($cluster:expr, $group:expr) => {
-        0x280000 + (($cluster as u32) * 0xE) + (($group as u32) * 0x100) + 0x0024
-    };
+    ($rfpc:expr) => {{
+        let (cluster_base, group_offset) = $rfpc.group_ctl_xpb_base();
+        cluster_base + group_offset + 0x0024
+    }};
 }
 
 // PAControl bitfields (see High Speed Performance Analyzer
@@ -56,6 +68,69 @@ bitfield! {
     pub reserved, set_reserved: 31, 21;
 }
 
+// RFPC trace status bitfields, carried in the low performance bus word (see
+// High Speed Performance Analyzer Peripheral EAS v0.3, section 2.3). The mid
+// and high bus words carry the 64-bit payload (PC or register write value) of
+// the event. The breakpoint and PC valid bits are the ones matched by the
+// mask/compare units in `pa_trigger_on_uncomp_trace()`.
+bitfield! {
+    pub struct TraceStatus(u32);
+    impl Debug;
+    u32;
+    pub reserved1, set_reserved1: 2, 0;
+    pub bkpt, set_bkpt: 3;
+    pub reserved2, set_reserved2: 6, 4;
+    pub pc_valid, set_pc_valid: 7;
+    pub seq_count, set_seq_count: 14, 8;
+    pub seq_valid, set_seq_valid: 15;
+    pub rfw_valid, set_rfw_valid: 16;
+    pub rfw_reg, set_rfw_reg: 21, 17;
+    pub reserved3, set_reserved3: 31, 22;
+}
+
+/// An RFPC execution event decoded from the trace bus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// The core jumped to a new, non-sequential PC.
+    PcChange { pc: u64 },
+    /// The core executed `count` instructions sequentially.
+    SequentialRun { count: u32 },
+    /// The core wrote `value` to GPR `x<reg>`.
+    RegisterWrite { reg: u8, value: u64 },
+    /// The core hit a breakpoint.
+    Breakpoint { pc: Option<u64> },
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceEvent::PcChange { pc } => write!(f, "pc       {:#014x}", pc),
+            TraceEvent::SequentialRun { count } => write!(f, "seq      {} instruction(s)", count),
+            TraceEvent::RegisterWrite { reg, value } => {
+                write!(f, "write    x{:<2} = {:#018x}", reg, value)
+            }
+            TraceEvent::Breakpoint { pc: Some(pc) } => write!(f, "bkpt     {:#014x}", pc),
+            TraceEvent::Breakpoint { pc: None } => write!(f, "bkpt"),
+        }
+    }
+}
+
+/// A decoded trace event together with the timestamp of its sample.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub timestamp: Option<u32>,
+    pub event: TraceEvent,
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.timestamp {
+            Some(ts) => write!(f, "{:>10}  {}", ts, self.event),
+            None => write!(f, "{:>10}  {}", "-", self.event),
+        }
+    }
+}
+
 /// Configures the Performance Analyzer for tracing based on specified parameters.
 ///
 /// # Parameters
@@ -102,8 +177,164 @@ pub fn pa_trigger_on_uncomp_trace<'a>(
         _ => panic!("Invalid word index!"),
     };
 
-    // Build up the Performance Analyzer configuration and start it up.
-    let pa = PerformanceAnalyzer::new(exp_bar, rfpc.island)
+    let mut pa = start_trace_pa(exp_bar, rfpc, capture_method, capture_start);
+
+    let pa_control = trace_control(rfpc, trace_pc, trace_seq, trace_bp, trace_reg);
+    enable_rfpc_trace(&mut pa, rfpc, pa_control);
+
+    pa
+}
+
+/// Configures the Performance Analyzer for tracing with a saved recipe
+/// instead of the built-in trace configuration.
+///
+/// # Parameters
+/// - `exp_bar`: Reference to the expansion bar for configuration.
+/// - `rfpc`: Reference to the RFPC structure holding core parameters.
+/// - `recipe`: Performance Analyzer recipe to capture with.
+/// - `trace_pc`: Flag indicating whether to trace program counter.
+/// - `trace_seq`: Flag indicating whether to trace sequential instructions.
+/// - `trace_bp`: Flag indicating whether to trace breakpoints.
+/// - `trace_reg`: Flag indicating whether to trace registers.
+///
+/// # Returns
+/// A configured `PerformanceAnalyzer`.
+pub fn pa_trigger_on_recipe_trace<'a>(
+    exp_bar: &'a mut ExpansionBar,
+    rfpc: &'a Rfpc,
+    recipe: &PARecipe,
+    trace_pc: bool,
+    trace_seq: bool,
+    trace_bp: bool,
+    trace_reg: bool,
+) -> PerformanceAnalyzer<'a> {
+    let mut pa = PerformanceAnalyzer::new(exp_bar, rfpc.island)
+        .apply_recipe(recipe)
+        .start_pa();
+
+    let pa_control = trace_control(rfpc, trace_pc, trace_seq, trace_bp, trace_reg);
+    enable_rfpc_trace(&mut pa, rfpc, pa_control);
+
+    pa
+}
+
+/// Derives the trace sample layout captured by a recipe from its capture
+/// method and capture start, the inverse of the mapping used by
+/// `pa_trigger_on_uncomp_trace()`.
+///
+/// # Parameters
+/// - `recipe`: Performance Analyzer recipe the trace was captured with.
+///
+/// # Returns
+/// The number of bus words, the bus word sampled first and whether a
+/// timestamp is captured, or an error if the recipe captures compressed
+/// traces, which cannot be decoded, or has an invalid capture start.
+pub fn recipe_trace_format(recipe: &PARecipe) -> Result<(u32, u32, bool), String> {
+    let config = &recipe.config;
+    if config.rv_decompress() || config.rv_capture_decomp() {
+        return Err(format!(
+            "Recipe {} captures compressed traces, which cannot be decoded",
+            recipe.name
+        ));
+    }
+
+    let (bus_words, timestamp) = match config.capture_method() {
+        0 => (1, false),
+        1 => (1, true),
+        2 => (2, false),
+        _ => (3, true),
+    };
+
+    let word_index = match config.capture_start() {
+        word_index @ 0..=2 => word_index,
+        capture_start => {
+            return Err(format!(
+                "Recipe {} has an invalid capture_start {}",
+                recipe.name, capture_start
+            ))
+        }
+    };
+
+    Ok((bus_words, word_index, timestamp))
+}
+
+/// Configures the Performance Analyzer for a PC histogram of an RFPC core.
+///
+/// The core traces its PC onto the performance bus, and every sample with a
+/// valid PC increments performance counter 0 in the histogram bucket of the
+/// PC. The histogram source is the mid bus word, which carries the low 32
+/// bits of the PC when `TraceStatus::pc_valid` is set (see `TraceStatus`),
+/// and only samples with a valid PC are counted. The bucket starts of the
+/// histogram are therefore PCs, and the histogram covers the PCs below
+/// `num_buckets << histogram_shift`. The histogram buckets are cleared before
+/// the Performance Analyzer is started. Read the histogram with
+/// `pa_histogram::capture_histogram()`.
+///
+/// # Parameters
+/// - `exp_bar`: Reference to the expansion bar for configuration.
+/// - `rfpc`: Reference to the RFPC structure holding core parameters.
+/// - `histogram_shift`: Each bucket covers `1 << histogram_shift` bytes of
+///   code (0-7).
+/// - `num_buckets`: Number of histogram buckets to clear.
+///
+/// # Returns
+/// A configured `PerformanceAnalyzer`.
+pub fn pa_histogram_on_rfpc_pc<'a>(
+    exp_bar: &'a mut ExpansionBar,
+    rfpc: &'a Rfpc,
+    histogram_shift: u8,
+    num_buckets: u32,
+) -> PerformanceAnalyzer<'a> {
+    let mut pa = PerformanceAnalyzer::new(exp_bar, rfpc.island)
+        .set_pa_global_config(
+            false,
+            false,
+            false,
+            false,
+            HistogramSource::MidCaptureSource,
+            CaptureMode::HistogramAndPerfCounters,
+            false,
+            PerfCounterAction::IncPerfCounter,
+            CaptureStart::LowBusInFifoFirst,
+            CaptureMethod::PerfBus32orTs,
+            0,
+            EventMethod::NoEvents,
+            false,
+            histogram_shift,
+            false,
+            false,
+            false,
+            true,
+        )
+        .set_mask_compare(0, 0, 0x80, 0x80, false)
+        .set_mask_compare_detect(0, 0x0001, 0x0001)
+        .set_capture_tcam(
+            0,
+            TcamCaptureType::PerfCounting,
+            TcamCaptureSource::MaskCompareDetectors,
+            0x01,
+            0x01,
+            false,
+        );
+    clear_histogram(&mut pa, num_buckets);
+    let mut pa = pa.start_pa();
+
+    let pa_control = trace_control(rfpc, true, false, false, false);
+    enable_rfpc_trace(&mut pa, rfpc, pa_control);
+
+    pa
+}
+
+/// Builds the Performance Analyzer configuration used for RFPC tracing and
+/// starts it up. Samples are captured whenever the trace bus carries a
+/// breakpoint, PC, sequential run or register write event.
+fn start_trace_pa<'a>(
+    exp_bar: &'a mut ExpansionBar,
+    rfpc: &Rfpc,
+    capture_method: CaptureMethod,
+    capture_start: CaptureStart,
+) -> PerformanceAnalyzer<'a> {
+    PerformanceAnalyzer::new(exp_bar, rfpc.island)
         .set_pa_global_config(
             false,
             false,
@@ -137,8 +368,32 @@ pub fn pa_trigger_on_uncomp_trace<'a>(
             0x01,
             false,
         )
-        .start_pa();
+        .start_pa()
+}
 
+/// Builds the `PAControl` value that enables tracing of the selected RFPC
+/// core with the given trace sources.
+fn trace_control(
+    rfpc: &Rfpc,
+    trace_pc: bool,
+    trace_seq: bool,
+    trace_bp: bool,
+    trace_reg: bool,
+) -> PAControl {
+    let mut pa_control = PAControl(0);
+    pa_control.set_enable(true);
+    pa_control.set_select(rfpc.core as u32);
+    pa_control.set_trace_en(true);
+    pa_control.set_trace_ctl(trace_seq);
+    pa_control.set_trace_pc(trace_pc);
+    pa_control.set_trace_rfw(trace_reg);
+    pa_control.set_trace_bkpt(trace_bp);
+    pa_control
+}
+
+/// Routes the trace output of the RFPC group onto the performance bus and
+/// writes the trace control register.
+fn enable_rfpc_trace(pa: &mut PerformanceAnalyzer, rfpc: &Rfpc, pa_control: PAControl) {
     // Set up and enable trace output for specified RFPC core.
     let mut pa_mux = PerfMuxConfig(0);
     pa_mux.set_lane_select_lo(1);
@@ -148,29 +403,48 @@ pub fn pa_trigger_on_uncomp_trace<'a>(
     xpb_write(
         pa.exp_bar,
         &pa.cpp_island,
-        rfpc_perf_mux_config!(rfpc.cluster, rfpc.group),
+        rfpc_perf_mux_config!(rfpc),
         vec![pa_mux.0],
         false,
     );
 
-    let mut pa_control = PAControl(0);
-    pa_control.set_enable(true);
-    pa_control.set_select(rfpc.core as u32);
-    pa_control.set_trace_en(true);
-    pa_control.set_trace_ctl(trace_seq);
-    pa_control.set_trace_pc(trace_pc);
-    pa_control.set_trace_rfw(trace_reg);
-    pa_control.set_trace_bkpt(trace_bp);
-
     xpb_write(
         pa.exp_bar,
         &pa.cpp_island,
-        rfpc_pa_control!(rfpc.cluster, rfpc.group),
+        rfpc_pa_control!(rfpc),
         vec![pa_control.0],
         false,
     );
+}
 
-    pa
+/// Checks whether trace samples carry a timestamp for the given capture
+/// settings. Only 32-bit captures make the timestamp optional; 64-bit captures
+/// never include one and 96-bit captures always do.
+///
+/// # Parameters
+///
+/// - `bus_words`: Number of bus words sampled.
+/// - `timestamp`: Whether a timestamp was requested.
+pub fn trace_has_timestamp(bus_words: u32, timestamp: bool) -> bool {
+    match bus_words {
+        1 => timestamp,
+        2 => false,
+        _ => true,
+    }
+}
+
+/// Returns the number of 32-bit FIFO words captured per trace sample.
+///
+/// # Parameters
+///
+/// - `bus_words`: Number of bus words sampled.
+/// - `timestamp`: Whether a timestamp was requested.
+pub fn trace_words_per_sample(bus_words: u32, timestamp: bool) -> usize {
+    match bus_words {
+        1 => 1 + timestamp as usize,
+        2 => 2,
+        _ => 4,
+    }
 }
 
 /// Applies the Performance Analyzer settings, initiates the Performance Analyzer trigger,
@@ -181,11 +455,19 @@ pub fn pa_trigger_on_uncomp_trace<'a>(
 ///
 /// - `pa`: The `PerformanceAnalyzer` instance to read from.
 /// - `num_words`: The number of 32-bit words to read from the FIFO.
+/// - `timeout`: Time to wait for the words, or `None` to wait forever.
 ///
 /// # Returns
 ///
 /// A `Vec<u32>` containing the 32-bit samples read from the Performance Analyzer's FIFO.
-pub fn read_trace(pa: &mut PerformanceAnalyzer, num_words: u32) -> Vec<u32> {
+/// It holds fewer than `num_words` words if the timeout expired first.
+pub fn read_trace(
+    pa: &mut PerformanceAnalyzer,
+    num_words: u32,
+    timeout: Option<Duration>,
+) -> Vec<u32> {
+    let start = Instant::now();
+
     // Set the trigger to an idle state to ensure it's ready for sampling.
     pa.trigger_idle();
     // Start the trigger without any active states.
@@ -196,9 +478,17 @@ pub fn read_trace(pa: &mut PerformanceAnalyzer, num_words: u32) -> Vec<u32> {
 
     // Continue reading samples until we have the requested number of words.
     while fifo_samples.len() < num_words as usize {
+        // Wait for the core to produce trace samples.
+        if pa.read_fifo_control().empty() {
+            if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+                break;
+            }
+            sleep(Duration::from_millis(1));
+            continue;
+        }
         let remaining_words: u32 = num_words - fifo_samples.len() as u32;
         // Read FIFO samples from the Performance Analyzer.
-        let samples = pa.read_fifo(remaining_words);
+        let samples = pa.read_fifo(remaining_words.min(PA_FIFO_SIZE));
         fifo_samples.extend(samples);
     }
 
@@ -234,6 +524,8 @@ pub fn read_trace(pa: &mut PerformanceAnalyzer, num_words: u32) -> Vec<u32> {
 /// # Example
 ///
 /// ```
+/// use nfp_debug_tools::libs::rfpc_trace::format_uncomp_trace;
+///
 /// let samples = vec![0xDEADBEEF, 0xCAFEBABE, 0xB16B00B5];
 /// let formatted_lines = format_uncomp_trace(samples, 3, 0, true, 3);
 /// for line in formatted_lines {
@@ -279,7 +571,11 @@ pub fn format_uncomp_trace(
     formatted_lines.push(format!("| {} |", header_line.join(" | ")));
 
     // Determine timestamp index if applicable
-    let ts_index: Option<usize> = if bus_words == 1 { Some(0) } else { None };
+    let ts_index: Option<usize> = if bus_words == 1 && timestamp {
+        Some(0)
+    } else {
+        None
+    };
 
     // Process each sample
     for chunk in samples.chunks(words_per_sample) {
@@ -310,3 +606,257 @@ pub fn format_uncomp_trace(
 
     formatted_lines
 }
+
+/// Formats uncompressed RFPC trace samples as CSV.
+///
+/// The header names the columns the same way as `format_uncomp_trace()`, and
+/// every following line holds one sample with the timestamp in decimal and
+/// the bus words in hexadecimal.
+///
+/// # Parameters
+///
+/// * `samples`: The 32-bit FIFO words to be formatted.
+/// * `bus_words`: The number of words per sample from the performance bus.
+/// * `timestamp`: Whether the samples start with a timestamp word.
+///
+/// # Returns
+///
+/// Returns a `Vec<String>` with the header line followed by one line per sample.
+pub fn format_uncomp_trace_csv(samples: &[u32], bus_words: u32, timestamp: bool) -> Vec<String> {
+    let words_per_sample = trace_words_per_sample(bus_words, timestamp);
+    let has_ts = trace_has_timestamp(bus_words, timestamp);
+
+    let mut header: Vec<String> = (0..bus_words.min(3))
+        .map(|i| format!("word{}", i))
+        .collect();
+    if has_ts {
+        header.insert(0, "timestamp".to_string());
+    }
+
+    let mut lines = vec![header.join(",")];
+    for chunk in samples.chunks(words_per_sample) {
+        let fields: Vec<String> = chunk
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                if has_ts && i == 0 {
+                    format!("{}", word)
+                } else {
+                    format!("{:#010x}", word)
+                }
+            })
+            .collect();
+        lines.push(fields.join(","));
+    }
+
+    lines
+}
+
+/// Decodes uncompressed RFPC trace samples into execution events.
+///
+/// Each sample is split into its timestamp and bus words. The status bits in
+/// the low bus word select the events of the sample, which are emitted in the
+/// order breakpoint, PC change, sequential run and register write. The core
+/// drives a single payload per sample: it holds the PC when `pc_valid` is set
+/// and the register write value otherwise.
+///
+/// Captures with fewer than three bus words only decode the events whose
+/// words were sampled; samples without the low bus word decode to nothing.
+///
+/// # Parameters
+///
+/// * `samples`: The 32-bit FIFO words captured by `read_trace()`.
+/// * `bus_words`: The number of bus words per sample.
+/// * `word_index`: The bus word that was sampled first.
+/// * `timestamp`: Whether a timestamp was requested for the capture.
+///
+/// # Returns
+///
+/// Returns a `Vec<TraceRecord>` with the decoded events in capture order.
+pub fn decode_uncomp_trace(
+    samples: &[u32],
+    bus_words: u32,
+    word_index: u32,
+    timestamp: bool,
+) -> Vec<TraceRecord> {
+    let words_per_sample = trace_words_per_sample(bus_words, timestamp);
+    let has_ts = trace_has_timestamp(bus_words, timestamp);
+    let mut records = Vec::new();
+
+    for chunk in samples.chunks_exact(words_per_sample) {
+        let (ts, data) = if has_ts {
+            (Some(chunk[0]), &chunk[1..])
+        } else {
+            (None, chunk)
+        };
+
+        // Place the sampled words at their bus positions (low, mid, high).
+        let mut bus: [Option<u32>; 3] = [None; 3];
+        for (i, word) in data.iter().enumerate() {
+            let position = if bus_words == 3 {
+                i
+            } else {
+                (word_index as usize + i) % 3
+            };
+            bus[position] = Some(*word);
+        }
+
+        let status = match bus[0] {
+            Some(word) => TraceStatus(word),
+            None => continue,
+        };
+        let payload = match (bus[1], bus[2]) {
+            (Some(mid), Some(high)) => Some(((high as u64) << 32) | mid as u64),
+            (Some(mid), None) => Some(mid as u64),
+            _ => None,
+        };
+
+        let mut push = |event: TraceEvent| {
+            records.push(TraceRecord {
+                timestamp: ts,
+                event,
+            })
+        };
+
+        if status.bkpt() {
+            push(TraceEvent::Breakpoint {
+                pc: payload.filter(|_| status.pc_valid()),
+            });
+        }
+        if status.pc_valid() {
+            if let Some(pc) = payload {
+                push(TraceEvent::PcChange { pc });
+            }
+        }
+        if status.seq_valid() {
+            push(TraceEvent::SequentialRun {
+                count: status.seq_count(),
+            });
+        }
+        if status.rfw_valid() && !status.pc_valid() {
+            if let Some(value) = payload {
+                push(TraceEvent::RegisterWrite {
+                    reg: status.rfw_reg() as u8,
+                    value,
+                });
+            }
+        }
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(f: impl FnOnce(&mut TraceStatus)) -> u32 {
+        let mut status = TraceStatus(0);
+        f(&mut status);
+        status.0
+    }
+
+    // A 96-bit capture with timestamp: a jump, a run of sequential
+    // instructions, a register write and a breakpoint.
+    fn capture_96() -> Vec<u32> {
+        vec![
+            // Jump to 0x1_8000_0010, followed by 4 sequential instructions.
+            100,
+            status(|s| {
+                s.set_pc_valid(true);
+                s.set_seq_valid(true);
+                s.set_seq_count(4);
+            }),
+            0x8000_0010,
+            0x1,
+            // x5 = 0x1234_5678_9abc_def0.
+            104,
+            status(|s| {
+                s.set_rfw_valid(true);
+                s.set_rfw_reg(5);
+            }),
+            0x9abc_def0,
+            0x1234_5678,
+            // Breakpoint at 0x8000_0040.
+            110,
+            status(|s| {
+                s.set_bkpt(true);
+                s.set_pc_valid(true);
+            }),
+            0x8000_0040,
+            0,
+        ]
+    }
+
+    #[test]
+    fn decode_uncomp_trace_96_bit() {
+        let records = decode_uncomp_trace(&capture_96(), 3, 0, true);
+        let expected = [
+            (100, TraceEvent::PcChange { pc: 0x1_8000_0010 }),
+            (100, TraceEvent::SequentialRun { count: 4 }),
+            (
+                104,
+                TraceEvent::RegisterWrite {
+                    reg: 5,
+                    value: 0x1234_5678_9abc_def0,
+                },
+            ),
+            (
+                110,
+                TraceEvent::Breakpoint {
+                    pc: Some(0x8000_0040),
+                },
+            ),
+            (110, TraceEvent::PcChange { pc: 0x8000_0040 }),
+        ];
+
+        assert_eq!(records.len(), expected.len());
+        for (record, (ts, event)) in records.iter().zip(expected) {
+            assert_eq!(record.timestamp, Some(ts));
+            assert_eq!(record.event, event);
+        }
+    }
+
+    #[test]
+    fn decode_uncomp_trace_ignores_rfw_with_pc() {
+        let samples = [
+            0,
+            status(|s| {
+                s.set_pc_valid(true);
+                s.set_rfw_valid(true);
+                s.set_rfw_reg(1);
+            }),
+            0x8000_0000,
+            0,
+        ];
+        let records = decode_uncomp_trace(&samples, 3, 0, true);
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].event, TraceEvent::PcChange { pc: 0x8000_0000 });
+    }
+
+    #[test]
+    fn decode_uncomp_trace_32_bit() {
+        // Only the status word is sampled, so no payload is available.
+        let samples = [
+            status(|s| s.set_bkpt(true)),
+            status(|s| {
+                s.set_seq_valid(true);
+                s.set_seq_count(7);
+            }),
+        ];
+        let records = decode_uncomp_trace(&samples, 1, 0, false);
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].timestamp, None);
+        assert_eq!(records[0].event, TraceEvent::Breakpoint { pc: None });
+        assert_eq!(records[1].event, TraceEvent::SequentialRun { count: 7 });
+    }
+
+    #[test]
+    fn decode_uncomp_trace_without_status_word() {
+        // A 64-bit capture of the mid and high words carries no status.
+        let samples = [0x8000_0010, 0x1];
+        assert!(decode_uncomp_trace(&samples, 2, 1, false).is_empty());
+    }
+}
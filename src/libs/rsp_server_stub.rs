@@ -1,24 +1,46 @@
 #![allow(dead_code)]
 
+use crate::libs::common::{hex_parser, hex_parser_u64, hexdump_lines};
 use crate::libs::cpp_bus::CppIsland;
 use crate::libs::expansion_bar::ExpansionBar;
 use crate::libs::mem_access::{mem_read, mem_write, MemoryType, MuMemoryEngine};
+use crate::libs::performance_analyzer::PerformanceAnalyzer;
 use crate::libs::rfpc::{Rfpc, RfpcCsr, RfpcGpr, RfpcReg};
 use crate::libs::rfpc_debugger::{
-    rfpc_dbg_continue, rfpc_dbg_halt, rfpc_dbg_read_memory, rfpc_dbg_read_reg,
-    rfpc_dbg_single_step, rfpc_dbg_write_memory, rfpc_dbg_write_reg,
+    dmstatus_flags, dmstatus_state, poll_until, rfpc_dbg_clear_ebreak, rfpc_dbg_continue,
+    rfpc_dbg_continue_finish, rfpc_dbg_continue_start, rfpc_dbg_halt, rfpc_dbg_is_halted,
+    rfpc_dbg_read_memory, rfpc_dbg_read_reg, rfpc_dbg_reset, rfpc_dbg_resume, rfpc_dbg_single_step,
+    rfpc_dbg_status, rfpc_dbg_write_memory, rfpc_dbg_write_reg, DebugConfig, ResetType,
 };
+use crate::libs::rfpc_trace::{format_uncomp_trace, pa_trigger_on_uncomp_trace};
+use crate::libs::xpb_bus::{xpb_read, xpb_write};
 use bytemuck::cast_slice;
+use clap::ValueEnum;
 use std::collections::HashMap;
+use std::fmt;
+use std::fs;
 use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
+use std::net::TcpListener;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use std::thread::sleep;
 use std::time::Duration;
 
-const LOCAL_HOST_IP: &str = "127.0.0.1";
-const PORT: u16 = 12727;
+pub const LOCAL_HOST_IP: &str = "127.0.0.1";
+pub const PORT: u16 = 12727;
+
+// Maximum number of hex encoded console bytes sent in a single `O` packet.
+const CONSOLE_CHUNK_SIZE: usize = 512;
+
+// Time a session waits for data from the client before checking whether the
+// server was stopped.
+const CLIENT_READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Trace samples captured by `monitor trace start` hold a timestamp and the
+// three performance bus words.
+const TRACE_WORDS_PER_SAMPLE: u32 = 4;
 
 // Define the function type enum.
 #[derive(Clone)]
@@ -26,19 +48,164 @@ enum FuncType<'a> {
     Ascii(String),
     NoArg(fn(&mut RspServer<'a>) -> String),
     WithArg(fn(&mut RspServer<'a>, Vec<u8>) -> String),
+    NoReply(fn(&mut RspServer<'a>, Vec<u8>)),
+}
+
+// A GDB `monitor` command, dispatched from `qRcmd` packets.
+#[derive(Clone)]
+struct MonitorCmd<'a> {
+    // Command words, e.g. "xpb read".
+    name: &'static str,
+    // Argument synopsis shown by `monitor help`.
+    args: &'static str,
+    // Description shown by `monitor help`.
+    help: &'static str,
+    // Handler called with the arguments following the command words. The
+    // returned text is shown on the GDB console.
+    func: fn(&mut RspServer<'a>, &[&str]) -> Result<String, String>,
+}
+
+/// What the server does with the core when GDB kills the program (`k`).
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum KillPolicy {
+    /// Leave the core as it is.
+    None,
+    /// Resume the core if it is halted.
+    Resume,
+    /// Reset the core and let it run from the reset vector.
+    Reset,
+}
+
+// Why a GDB session ended.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum SessionEnd {
+    // The client detached (`D`).
+    Detached,
+    // The client killed the program (`k`).
+    Killed,
+    // The client closed the connection.
+    Disconnected,
+    // The server is shutting down.
+    Shutdown,
+}
+
+// The expansion BAR of a server: borrowed exclusively, or shared with the
+// other sessions of a supervisor. A shared BAR is locked on first use while a
+// packet is handled and unlocked once the packet is done, so the debug module
+// accesses of one packet are never interleaved with those of another session.
+enum RspBus<'a> {
+    Owned(&'a mut ExpansionBar),
+    Shared(
+        &'a Mutex<ExpansionBar>,
+        Option<MutexGuard<'a, ExpansionBar>>,
+    ),
+}
+
+impl RspBus<'_> {
+    // Returns the expansion BAR, locking a shared BAR if needed.
+    fn bar(&mut self) -> &mut ExpansionBar {
+        match self {
+            RspBus::Owned(bar) => bar,
+            RspBus::Shared(mutex, guard) => {
+                guard.get_or_insert_with(|| mutex.lock().unwrap_or_else(PoisonError::into_inner))
+            }
+        }
+    }
+
+    // Lets other sessions use a shared BAR again.
+    fn unlock(&mut self) {
+        if let RspBus::Shared(_, guard) = self {
+            *guard = None;
+        }
+    }
+
+    fn is_shared(&self) -> bool {
+        matches!(self, RspBus::Shared(..))
+    }
+}
+
+/// An address the RSP server listens on for GDB connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RspListenAddr {
+    /// A TCP `host:port` address. Port 0 lets the OS pick a free port.
+    Tcp(String),
+    /// A Unix domain socket path.
+    Unix(PathBuf),
+}
+
+impl Default for RspListenAddr {
+    fn default() -> Self {
+        RspListenAddr::Tcp(format!("{}:{}", LOCAL_HOST_IP, PORT))
+    }
+}
+
+/// A bound RSP listener. The socket file of a Unix listener is removed when
+/// the listener is dropped.
+enum RspListener {
+    Tcp(TcpListener),
+    Unix(UnixListener, PathBuf),
+}
+
+impl RspListener {
+    /// Binds a non-blocking listener to an address. A Unix socket file left
+    /// behind by a server that is no longer running is replaced.
+    fn bind(addr: &RspListenAddr) -> std::io::Result<Self> {
+        let listener = match addr {
+            RspListenAddr::Tcp(addr) => {
+                let listener = TcpListener::bind(addr.as_str())?;
+                listener.set_nonblocking(true)?;
+                RspListener::Tcp(listener)
+            }
+            RspListenAddr::Unix(path) => {
+                if path.exists() && UnixStream::connect(path).is_err() {
+                    fs::remove_file(path)?;
+                }
+                let listener = UnixListener::bind(path)?;
+                listener.set_nonblocking(true)?;
+                RspListener::Unix(listener, path.clone())
+            }
+        };
+        Ok(listener)
+    }
+}
+
+impl fmt::Display for RspListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RspListener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => write!(f, "{}", addr),
+                Err(_) => write!(f, "<unknown TCP address>"),
+            },
+            RspListener::Unix(_, path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+impl Drop for RspListener {
+    fn drop(&mut self) {
+        if let RspListener::Unix(_, path) = self {
+            let _ = fs::remove_file(path);
+        }
+    }
 }
 
 pub struct RspServer<'a> {
-    exp_bar: &'a mut ExpansionBar,
+    bus: RspBus<'a>,
     cmd_resp_map: HashMap<String, Option<FuncType<'a>>>,
     server_kv_support: HashMap<String, String>,
     server_v_support: Vec<String>,
     client_kv_support: HashMap<String, String>,
     client_v_support: Vec<String>,
     breakpoints: HashMap<u64, u64>,
-    thread_map: HashMap<u16, (CppIsland, u8, u8, u8)>,
     disable_ack: bool,
     rfpc: Rfpc,
+    dbg_config: DebugConfig,
+    monitor_cmds: Vec<MonitorCmd<'a>>,
+    console_output: Vec<String>,
+    kill_policy: KillPolicy,
+    session_end: Option<SessionEnd>,
+    ebreak_armed: bool,
+    shut_down: bool,
 }
 
 impl<'a> RspServer<'a> {
@@ -47,6 +214,7 @@ impl<'a> RspServer<'a> {
     /// # Parameters
     ///
     /// * `exp_bar - A mutable reference to an `ExpansionBar`.
+    /// * `dbg_config` - Polling and timeout settings for the RFPC debugger.
     ///
     /// # Returns
     ///
@@ -57,12 +225,61 @@ impl<'a> RspServer<'a> {
         cluster: u8,
         group: u8,
         core: u8,
+        dbg_config: DebugConfig,
+    ) -> Self {
+        Self::with_bus(
+            RspBus::Owned(exp_bar),
+            island,
+            cluster,
+            group,
+            core,
+            dbg_config,
+        )
+    }
+
+    /// Creates a new instance of the `RspServer` on an expansion BAR shared
+    /// with other servers, e.g. one per core of the same NFP.
+    ///
+    /// The BAR is locked while a packet is handled, and between the debug
+    /// module polls while the core runs after a continue, so the servers
+    /// never clobber each other's BAR configuration or debug module state.
+    ///
+    /// # Parameters
+    ///
+    /// * `exp_bar` - The shared `ExpansionBar`.
+    /// * `dbg_config` - Polling and timeout settings for the RFPC debugger.
+    ///
+    /// # Returns
+    ///
+    /// `RspServer` instance.
+    pub fn new_shared(
+        exp_bar: &'a Mutex<ExpansionBar>,
+        island: CppIsland,
+        cluster: u8,
+        group: u8,
+        core: u8,
+        dbg_config: DebugConfig,
+    ) -> Self {
+        Self::with_bus(
+            RspBus::Shared(exp_bar, None),
+            island,
+            cluster,
+            group,
+            core,
+            dbg_config,
+        )
+    }
+
+    fn with_bus(
+        bus: RspBus<'a>,
+        island: CppIsland,
+        cluster: u8,
+        group: u8,
+        core: u8,
+        dbg_config: DebugConfig,
     ) -> Self {
         let mut cmd_resp_map: HashMap<String, Option<FuncType>> = HashMap::new();
-        cmd_resp_map.insert(
-            "!".to_string(),
-            Some(FuncType::NoArg(RspServer::cmd_not_supported)),
-        );
+        cmd_resp_map.insert("!".to_string(), Some(FuncType::Ascii("OK".to_string())));
         cmd_resp_map.insert(
             "?".to_string(),
             Some(FuncType::Ascii(format!("S{:02x}", 18))),
@@ -117,7 +334,7 @@ impl<'a> RspServer<'a> {
             Some(FuncType::NoArg(RspServer::single_step_sig)),
         );
         cmd_resp_map.insert("c".to_string(), Some(FuncType::WithArg(RspServer::cont)));
-        cmd_resp_map.insert("D".to_string(), Some(FuncType::Ascii("detach".to_string())));
+        cmd_resp_map.insert("D".to_string(), Some(FuncType::WithArg(RspServer::detach)));
         cmd_resp_map.insert(
             "Z0".to_string(),
             Some(FuncType::WithArg(RspServer::set_breakpoint)),
@@ -127,7 +344,7 @@ impl<'a> RspServer<'a> {
             Some(FuncType::WithArg(RspServer::clear_breakpoint)),
         );
         cmd_resp_map.insert("\x03".to_string(), None);
-        cmd_resp_map.insert("k".to_string(), None);
+        cmd_resp_map.insert("k".to_string(), Some(FuncType::NoReply(RspServer::kill)));
         cmd_resp_map.insert(
             "C".to_string(),
             Some(FuncType::WithArg(RspServer::cont_with_sig)),
@@ -141,6 +358,16 @@ impl<'a> RspServer<'a> {
             Some(FuncType::WithArg(RspServer::memory_write)),
         );
 
+        cmd_resp_map.insert("R".to_string(), Some(FuncType::NoReply(RspServer::restart)));
+        cmd_resp_map.insert(
+            "vRun".to_string(),
+            Some(FuncType::WithArg(RspServer::run_program)),
+        );
+        cmd_resp_map.insert(
+            "qRcmd".to_string(),
+            Some(FuncType::WithArg(RspServer::monitor_command)),
+        );
+
         cmd_resp_map.insert(
             "vCont?".to_string(),
             Some(FuncType::NoArg(RspServer::multicore_supported_actions)),
@@ -151,6 +378,76 @@ impl<'a> RspServer<'a> {
             Some(FuncType::WithArg(RspServer::multicore_actions)),
         );
 
+        // GDB monitor commands.
+        let monitor_cmds: Vec<MonitorCmd> = vec![
+            MonitorCmd {
+                name: "help",
+                args: "",
+                help: "List the monitor commands.",
+                func: RspServer::monitor_help,
+            },
+            MonitorCmd {
+                name: "reset",
+                args: "[halt]",
+                help: "Reset the core, optionally halting it at the reset vector.",
+                func: RspServer::monitor_reset,
+            },
+            MonitorCmd {
+                name: "xpb read",
+                args: "[-g] <island> <addr> [count]",
+                help: "Read XPB registers (-g for global XPBM addresses).",
+                func: RspServer::monitor_xpb_read,
+            },
+            MonitorCmd {
+                name: "xpb write",
+                args: "[-g] <island> <addr> <value>...",
+                help: "Write XPB registers (-g for global XPBM addresses).",
+                func: RspServer::monitor_xpb_write,
+            },
+            MonitorCmd {
+                name: "mem read",
+                args: "<ctm|emem|cls> <addr> <len> [island]",
+                help: "Hexdump <len> bytes of NFP memory.",
+                func: RspServer::monitor_mem_read,
+            },
+            MonitorCmd {
+                name: "mem write",
+                args: "<ctm|emem|cls> <addr> <value>...",
+                help: "Write 32-bit words to NFP memory.",
+                func: RspServer::monitor_mem_write,
+            },
+            MonitorCmd {
+                name: "dmstatus",
+                args: "",
+                help: "Show the debug module status of the core.",
+                func: RspServer::monitor_dmstatus,
+            },
+            MonitorCmd {
+                name: "cores",
+                args: "",
+                help: "Show the run state of the cores in the cluster.",
+                func: RspServer::monitor_cores,
+            },
+            MonitorCmd {
+                name: "trace start",
+                args: "[pc] [seq] [bp] [reg]",
+                help: "Start tracing the core into the PA FIFO.",
+                func: RspServer::monitor_trace_start,
+            },
+            MonitorCmd {
+                name: "trace stop",
+                args: "",
+                help: "Stop the trace trigger.",
+                func: RspServer::monitor_trace_stop,
+            },
+            MonitorCmd {
+                name: "trace read",
+                args: "[samples]",
+                help: "Read trace samples from the PA FIFO.",
+                func: RspServer::monitor_trace_read,
+            },
+        ];
+
         // Server key->value and value support.
         let mut server_v_support: Vec<String> = Vec::new();
         server_v_support.push("qMemoryRead+".to_string());
@@ -168,19 +465,6 @@ impl<'a> RspServer<'a> {
         // Initialize breakpoint hash map.
         let breakpoints: HashMap<u64, u64> = HashMap::new();
 
-        // Initialize thread map hashmap.
-        let mut index = 0;
-        let mut thread_map: HashMap<u16, (CppIsland, u8, u8, u8)> = HashMap::new();
-
-        // Only do thread mapping for a single RFPC cluster.
-        // TODO: Expand this in future to cover all RFPC cores on the chip
-        for group in 0..4 {
-            for core in 0..8 {
-                thread_map.insert(index, (CppIsland::Rfpc0, 0, group, core));
-                index += 1;
-            }
-        }
-
         // Initialize to Rfpc island, cluster, group and core.
         let rfpc = Rfpc {
             island,
@@ -191,19 +475,62 @@ impl<'a> RspServer<'a> {
 
         // Return the server struct.
         RspServer {
-            exp_bar,
+            bus,
             cmd_resp_map,
             server_kv_support,
             server_v_support,
             client_kv_support,
             client_v_support,
             breakpoints,
-            thread_map,
             disable_ack,
             rfpc,
+            dbg_config,
+            monitor_cmds,
+            console_output: Vec::new(),
+            kill_policy: KillPolicy::None,
+            session_end: None,
+            ebreak_armed: false,
+            shut_down: false,
         }
     }
 
+    /// Sets what happens to the core when GDB kills the program (`k`).
+    /// Defaults to `KillPolicy::None`.
+    pub fn set_kill_policy(&mut self, kill_policy: KillPolicy) {
+        self.kill_policy = kill_policy;
+    }
+
+    /// Leaves the core the way it was before debugging: restores the
+    /// original instruction of every breakpoint still set, clears the ebreak
+    /// bits of dcsr and resumes the core if it is halted. A running core is
+    /// halted briefly for this if breakpoints or ebreak bits are left.
+    ///
+    /// Called when the server is dropped, so the cleanup also happens after
+    /// ctrl+C or a panic. Only runs once. A panic while cleaning up after a
+    /// panic aborts the process, so the cleanup is best effort in that case.
+    pub fn shutdown(&mut self) {
+        if self.shut_down {
+            return;
+        }
+        self.shut_down = true;
+
+        let halted = rfpc_dbg_is_halted(self.bus.bar(), &self.rfpc);
+        if !halted && self.breakpoints.is_empty() && !self.ebreak_armed {
+            self.bus.unlock();
+            return;
+        }
+
+        println!("Restoring core {} before exiting.", self.rfpc);
+        if !halted {
+            rfpc_dbg_halt(self.bus.bar(), &self.rfpc, &self.dbg_config);
+        }
+        self.clear_all_breakpoints();
+        rfpc_dbg_clear_ebreak(self.bus.bar(), &self.rfpc, &self.dbg_config);
+        self.ebreak_armed = false;
+        rfpc_dbg_resume(self.bus.bar(), &self.rfpc, &self.dbg_config);
+        self.bus.unlock();
+    }
+
     /// Method that returns an empty string if the RSP command is not
     /// supported.
     ///
@@ -225,43 +552,53 @@ impl<'a> RspServer<'a> {
 
         // Iterate over GPR addresses from X0 to X31
         for reg in RfpcGpr::X0.reg_addr()..=RfpcGpr::X31.reg_addr() {
-            let reg_val = rfpc_dbg_read_reg(self.exp_bar, &self.rfpc, reg);
+            let reg_val = rfpc_dbg_read_reg(self.bus.bar(), &self.rfpc, &self.dbg_config, reg);
             gprs.push_str(&format!("{:016x}", reg_val.swap_bytes()));
         }
 
         // Iterate over CSR addresses
         for reg in RfpcCsr::Mstatus.reg_addr()..=RfpcCsr::Mtvec.reg_addr() {
-            let reg_val = rfpc_dbg_read_reg(self.exp_bar, &self.rfpc, reg);
+            let reg_val = rfpc_dbg_read_reg(self.bus.bar(), &self.rfpc, &self.dbg_config, reg);
             gprs.push_str(&format!("{:016x}", reg_val.swap_bytes()));
         }
 
         for reg in RfpcCsr::Mscratch.reg_addr()..=RfpcCsr::Mip.reg_addr() {
-            let reg_val = rfpc_dbg_read_reg(self.exp_bar, &self.rfpc, reg);
+            let reg_val = rfpc_dbg_read_reg(self.bus.bar(), &self.rfpc, &self.dbg_config, reg);
             gprs.push_str(&format!("{:016x}", reg_val.swap_bytes()));
         }
 
         for reg in RfpcCsr::Dcsr.reg_addr()..=RfpcCsr::Dscratch1.reg_addr() {
-            let reg_val = rfpc_dbg_read_reg(self.exp_bar, &self.rfpc, reg);
+            let reg_val = rfpc_dbg_read_reg(self.bus.bar(), &self.rfpc, &self.dbg_config, reg);
             gprs.push_str(&format!("{:016x}", reg_val.swap_bytes()));
         }
 
         for reg in RfpcCsr::Mlmemprot.reg_addr()..=RfpcCsr::Mafstatus.reg_addr() {
-            let reg_val = rfpc_dbg_read_reg(self.exp_bar, &self.rfpc, reg);
+            let reg_val = rfpc_dbg_read_reg(self.bus.bar(), &self.rfpc, &self.dbg_config, reg);
             gprs.push_str(&format!("{:016x}", reg_val.swap_bytes()));
         }
 
-        let reg_val = rfpc_dbg_read_reg(self.exp_bar, &self.rfpc, RfpcCsr::Mcycle.reg_addr());
+        let reg_val = rfpc_dbg_read_reg(
+            self.bus.bar(),
+            &self.rfpc,
+            &self.dbg_config,
+            RfpcCsr::Mcycle.reg_addr(),
+        );
         gprs.push_str(&format!("{:016x}", reg_val.swap_bytes()));
-        let reg_val = rfpc_dbg_read_reg(self.exp_bar, &self.rfpc, RfpcCsr::Minstret.reg_addr());
+        let reg_val = rfpc_dbg_read_reg(
+            self.bus.bar(),
+            &self.rfpc,
+            &self.dbg_config,
+            RfpcCsr::Minstret.reg_addr(),
+        );
         gprs.push_str(&format!("{:016x}", reg_val.swap_bytes()));
 
         for reg in RfpcCsr::Cycle.reg_addr()..=RfpcCsr::Instret.reg_addr() {
-            let reg_val = rfpc_dbg_read_reg(self.exp_bar, &self.rfpc, reg);
+            let reg_val = rfpc_dbg_read_reg(self.bus.bar(), &self.rfpc, &self.dbg_config, reg);
             gprs.push_str(&format!("{:016x}", reg_val.swap_bytes()));
         }
 
         for reg in RfpcCsr::Mvendorid.reg_addr()..=RfpcCsr::Mhartid.reg_addr() {
-            let reg_val = rfpc_dbg_read_reg(self.exp_bar, &self.rfpc, reg);
+            let reg_val = rfpc_dbg_read_reg(self.bus.bar(), &self.rfpc, &self.dbg_config, reg);
             gprs.push_str(&format!("{:016x}", reg_val.swap_bytes()));
         }
 
@@ -293,8 +630,9 @@ impl<'a> RspServer<'a> {
                 .expect("Failed to parse nybble string as u64");
 
             rfpc_dbg_write_reg(
-                self.exp_bar,
+                self.bus.bar(),
                 &self.rfpc,
+                &self.dbg_config,
                 RfpcGpr::X0.reg_addr() + reg_idx as u64,
                 reg_value.swap_bytes(),
             );
@@ -343,8 +681,9 @@ impl<'a> RspServer<'a> {
 
             if let Some(csr) = csr_map.get(&reg_idx) {
                 rfpc_dbg_write_reg(
-                    self.exp_bar,
+                    self.bus.bar(),
                     &self.rfpc,
+                    &self.dbg_config,
                     csr.reg_addr() as u64,
                     reg_value.swap_bytes(),
                 );
@@ -438,14 +777,16 @@ impl<'a> RspServer<'a> {
         // Declare `reg_val` outside the conditional blocks.
         let reg_val = if (0..32).contains(&address) {
             rfpc_dbg_read_reg(
-                self.exp_bar,
+                self.bus.bar(),
                 &self.rfpc,
+                &self.dbg_config,
                 gpr_regs[address as usize].reg_addr(),
             )
         } else if (32..(32 + csr_regs.len() as u64)).contains(&address) {
             rfpc_dbg_read_reg(
-                self.exp_bar,
+                self.bus.bar(),
                 &self.rfpc,
+                &self.dbg_config,
                 csr_regs[(address - 32) as usize].reg_addr(),
             )
         } else {
@@ -546,16 +887,18 @@ impl<'a> RspServer<'a> {
         if (0..32).contains(&address) {
             // Write to the GPR register.
             rfpc_dbg_write_reg(
-                self.exp_bar,
+                self.bus.bar(),
                 &self.rfpc,
+                &self.dbg_config,
                 gpr_regs[address as usize].reg_addr(),
                 value,
             );
         } else if (32..(32 + csr_regs.len() as u64)).contains(&address) {
             // Write to the CSR register.
             rfpc_dbg_write_reg(
-                self.exp_bar,
+                self.bus.bar(),
                 &self.rfpc,
+                &self.dbg_config,
                 csr_regs[(address - 32) as usize].reg_addr(),
                 value,
             );
@@ -571,54 +914,50 @@ impl<'a> RspServer<'a> {
         "vCont;c;s;t".to_string()
     }
 
+    /// Resumes, steps or stops the core (`vCont` packet).
+    ///
+    /// A session debugs a single core, which the client sees as its only
+    /// thread. The leftmost action without a thread ID or with the ID of
+    /// that thread (`1`, `0` or `-1`, optionally with a `p<pid>.` prefix)
+    /// applies to the core; actions for other threads are ignored, so a
+    /// client can never act on the core of another session.
+    ///
+    /// # Returns
+    ///
+    /// The stop reply, or "E01" if the packet is malformed or has no
+    /// supported action for the core.
     fn multicore_actions(&mut self, packet: Vec<u8>) -> String {
         // Skip over vCont part.
-        let packet_str = String::from_utf8_lossy(&packet[6..]);
-        // Split actions for each core.
-        let actions_iter = packet_str.split(";");
-
-        // Split actions for each core.
-        for item in actions_iter {
-            let mut thread_action = item.split(":");
-            let action = thread_action.next();
-            let thread_id_str = thread_action.next();
-
-            // Convert thread_id to u16.
-            let thread_id = if let Some(str) = thread_id_str {
-                u16::from_str_radix(&str, 10).expect("Failed to parse value as u16")
-            } else {
-                panic!("Thread ID not present");
-            };
+        let packet_str = String::from_utf8_lossy(packet.get(6..).unwrap_or_default());
 
-            // Retrieve thread details from the map (maps a thread-id to an RFPC core).
-            if let Some((island, cluster, group, core)) = self.thread_map.get(&thread_id) {
-                self.rfpc.island = *island;
-                self.rfpc.cluster = *cluster;
-                self.rfpc.group = *group;
-                self.rfpc.core = *core;
-            } else {
-                panic!("Thread ID not supported!");
+        let action = packet_str.split(';').find_map(|item| {
+            let (action, thread_id) = match item.split_once(':') {
+                Some((action, thread_id)) => (action, Some(thread_id)),
+                None => (item, None),
+            };
+            let own_thread = thread_id.is_none_or(|thread_id| {
+                let thread_id = match thread_id.split_once('.') {
+                    Some((_pid, tid)) => tid,
+                    None => thread_id,
+                };
+                matches!(thread_id, "1" | "0" | "-1")
+            });
+            own_thread.then_some(action)
+        });
+
+        // Perform the action based on the parsed command.
+        match action {
+            Some("c") => self.continue_stop_reply(),
+            Some("s") => {
+                rfpc_dbg_single_step(self.bus.bar(), &self.rfpc, &self.dbg_config);
+                "S05".to_string()
             }
-
-            // Perform the action based on the parsed command.
-            match action {
-                Some("c") => {
-                    rfpc_dbg_continue(self.exp_bar, &self.rfpc);
-                }
-                Some("s") => {
-                    rfpc_dbg_single_step(self.exp_bar, &self.rfpc);
-                }
-                Some("t") => {
-                    rfpc_dbg_halt(self.exp_bar, &self.rfpc);
-                }
-                _ => {
-                    panic!("Unsupported thread command!");
-                }
+            Some("t") => {
+                rfpc_dbg_halt(self.bus.bar(), &self.rfpc, &self.dbg_config);
+                "S05".to_string()
             }
+            _ => "E01".to_string(),
         }
-
-        // Return success status.
-        "S05".to_string()
     }
 
     fn set_core(&mut self, _packet: Vec<u8>) -> String {
@@ -630,15 +969,21 @@ impl<'a> RspServer<'a> {
             let address_str = String::from_utf8_lossy(&packet[1..]);
             let address =
                 u64::from_str_radix(&address_str, 16).expect("Failed to parse address as u64");
-            rfpc_dbg_write_reg(self.exp_bar, &self.rfpc, RfpcCsr::Dpc.reg_addr(), address);
+            rfpc_dbg_write_reg(
+                self.bus.bar(),
+                &self.rfpc,
+                &self.dbg_config,
+                RfpcCsr::Dpc.reg_addr(),
+                address,
+            );
         }
 
-        rfpc_dbg_single_step(self.exp_bar, &self.rfpc);
+        rfpc_dbg_single_step(self.bus.bar(), &self.rfpc, &self.dbg_config);
         "S05".to_string()
     }
 
     fn single_step_sig(&mut self) -> String {
-        rfpc_dbg_single_step(self.exp_bar, &self.rfpc);
+        rfpc_dbg_single_step(self.bus.bar(), &self.rfpc, &self.dbg_config);
         "S05".to_string()
     }
 
@@ -647,18 +992,513 @@ impl<'a> RspServer<'a> {
             let address_str = String::from_utf8_lossy(&packet[1..]);
             let address =
                 u64::from_str_radix(&address_str, 16).expect("Failed to parse address as u64");
-            rfpc_dbg_write_reg(self.exp_bar, &self.rfpc, RfpcCsr::Dpc.reg_addr(), address);
+            rfpc_dbg_write_reg(
+                self.bus.bar(),
+                &self.rfpc,
+                &self.dbg_config,
+                RfpcCsr::Dpc.reg_addr(),
+                address,
+            );
         }
 
-        rfpc_dbg_continue(self.exp_bar, &self.rfpc);
-        "S05".to_string()
+        self.continue_stop_reply()
     }
 
     fn cont_with_sig(&mut self, _packet: Vec<u8>) -> String {
-        rfpc_dbg_continue(self.exp_bar, &self.rfpc);
+        self.continue_stop_reply()
+    }
+
+    /// Continues the core and builds the stop reply for the client.
+    ///
+    /// # Returns
+    ///
+    /// "S05" (SIGTRAP) if the core hit a breakpoint, or "S02" (SIGINT) if the
+    /// wait was cancelled because the server is shutting down.
+    fn continue_stop_reply(&mut self) -> String {
+        if self.continue_core() {
+            "S05".to_string()
+        } else {
+            "S02".to_string()
+        }
+    }
+
+    /// Continues the core and waits for it to halt on a breakpoint.
+    ///
+    /// On a shared expansion BAR, the BAR is only locked for the individual
+    /// debug module polls while the core runs, so the other sessions keep
+    /// working in the meantime.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the core halted on a breakpoint, `false` if the wait was
+    /// cancelled.
+    fn continue_core(&mut self) -> bool {
+        // The ebreak bits of dcsr stay set until the core halts on a
+        // breakpoint, so `shutdown` has to clear them otherwise.
+        self.ebreak_armed = true;
+
+        let halted = if self.bus.is_shared() {
+            rfpc_dbg_continue_start(self.bus.bar(), &self.rfpc, &self.dbg_config);
+            self.bus.unlock();
+
+            let bus = &mut self.bus;
+            let rfpc = &self.rfpc;
+            let halted = poll_until(
+                &self.dbg_config,
+                self.dbg_config.continue_timeout,
+                true,
+                "Timeout reached when wating for RFPC core halt after continue!",
+                || {
+                    let halted = rfpc_dbg_is_halted(bus.bar(), rfpc);
+                    bus.unlock();
+                    halted
+                },
+            );
+            if halted {
+                rfpc_dbg_continue_finish(self.bus.bar(), &self.rfpc, &self.dbg_config);
+            }
+            halted
+        } else {
+            rfpc_dbg_continue(self.bus.bar(), &self.rfpc, &self.dbg_config)
+        };
+
+        if halted {
+            self.ebreak_armed = false;
+        }
+        halted
+    }
+
+    /// Restarts the program (`R` packet) by resetting the core and halting
+    /// it at the reset vector. The `R` packet has no reply.
+    fn restart(&mut self, _packet: Vec<u8>) {
+        rfpc_dbg_reset(
+            self.bus.bar(),
+            &self.rfpc,
+            &self.dbg_config,
+            ResetType::Hart,
+            true,
+        );
+    }
+
+    /// Starts the program from scratch (`vRun` packet). The firmware is
+    /// already loaded, so the filename and arguments are ignored and the core
+    /// is reset and halted at the reset vector.
+    ///
+    /// # Returns
+    ///
+    /// Stop reply for the halted core.
+    fn run_program(&mut self, packet: Vec<u8>) -> String {
+        self.restart(packet);
         "S05".to_string()
     }
 
+    /// Detaches the client (`D` packet): removes all breakpoints and resumes
+    /// the core if it is halted. The session ends once the reply is sent.
+    ///
+    /// # Returns
+    ///
+    /// "OK".
+    fn detach(&mut self, _packet: Vec<u8>) -> String {
+        self.clear_all_breakpoints();
+        if rfpc_dbg_is_halted(self.bus.bar(), &self.rfpc) {
+            rfpc_dbg_resume(self.bus.bar(), &self.rfpc, &self.dbg_config);
+        }
+        self.session_end = Some(SessionEnd::Detached);
+        "OK".to_string()
+    }
+
+    /// Kills the program (`k` packet): removes all breakpoints and applies
+    /// the kill policy to the core. The `k` packet has no reply and ends the
+    /// session.
+    fn kill(&mut self, _packet: Vec<u8>) {
+        self.clear_all_breakpoints();
+        match self.kill_policy {
+            KillPolicy::None => (),
+            KillPolicy::Resume => {
+                if rfpc_dbg_is_halted(self.bus.bar(), &self.rfpc) {
+                    rfpc_dbg_resume(self.bus.bar(), &self.rfpc, &self.dbg_config);
+                }
+            }
+            KillPolicy::Reset => rfpc_dbg_reset(
+                self.bus.bar(),
+                &self.rfpc,
+                &self.dbg_config,
+                ResetType::Hart,
+                false,
+            ),
+        }
+        self.session_end = Some(SessionEnd::Killed);
+    }
+
+    /// Handles a `qRcmd` (GDB `monitor`) packet.
+    ///
+    /// The command is matched against the registered monitor commands, picking
+    /// the one with the most matching leading words, and the remaining words
+    /// are passed to it as arguments. The command output is queued in
+    /// `console_output` and sent to the client as `O` packets ahead of the
+    /// final reply.
+    ///
+    /// # Returns
+    ///
+    /// "OK" if the command succeeded, or "E01" if it failed or is unknown.
+    fn monitor_command(&mut self, packet: Vec<u8>) -> String {
+        let cmd_bytes = hex_decode(&packet["qRcmd,".len()..]);
+        let cmd = String::from_utf8_lossy(&cmd_bytes).to_string();
+        let args: Vec<&str> = cmd.split_whitespace().collect();
+
+        // A bare `monitor` lists the available commands.
+        if args.is_empty() {
+            let output = self.monitor_help(&[]).unwrap_or_default();
+            self.console_output.push(output);
+            return "OK".to_string();
+        }
+
+        let matched = self
+            .monitor_cmds
+            .iter()
+            .filter(|monitor_cmd| {
+                let words: Vec<&str> = monitor_cmd.name.split(' ').collect();
+                args.len() >= words.len() && args[..words.len()] == words[..]
+            })
+            .max_by_key(|monitor_cmd| monitor_cmd.name.split(' ').count())
+            .map(|monitor_cmd| (monitor_cmd.name.split(' ').count(), monitor_cmd.func));
+
+        match matched {
+            Some((num_words, func)) => match func(self, &args[num_words..]) {
+                Ok(output) => {
+                    self.console_output.push(output);
+                    "OK".to_string()
+                }
+                Err(msg) => {
+                    self.console_output.push(format!("{}\n", msg));
+                    "E01".to_string()
+                }
+            },
+            None => {
+                println!("Unknown monitor command {}", cmd);
+                self.console_output.push(format!(
+                    "Unknown monitor command '{}'. Try 'monitor help'.\n",
+                    cmd
+                ));
+                "E01".to_string()
+            }
+        }
+    }
+
+    /// Lists the registered monitor commands (`monitor help`).
+    fn monitor_help(&mut self, _args: &[&str]) -> Result<String, String> {
+        let mut output = String::from("Available monitor commands:\n");
+        for monitor_cmd in &self.monitor_cmds {
+            let usage = format!("{} {}", monitor_cmd.name, monitor_cmd.args);
+            output.push_str(&format!(
+                "  {:<44} {}\n",
+                usage.trim_end(),
+                monitor_cmd.help
+            ));
+        }
+        Ok(output)
+    }
+
+    /// Resets the core (`monitor reset [halt]`).
+    fn monitor_reset(&mut self, args: &[&str]) -> Result<String, String> {
+        let halt = match args {
+            [] => false,
+            ["halt"] => true,
+            _ => return Err("Usage: reset [halt]".to_string()),
+        };
+
+        rfpc_dbg_reset(
+            self.bus.bar(),
+            &self.rfpc,
+            &self.dbg_config,
+            ResetType::Hart,
+            halt,
+        );
+
+        if halt {
+            Ok(format!(
+                "Core {} reset and halted at the reset vector.\n",
+                self.rfpc
+            ))
+        } else {
+            Ok(format!("Core {} reset and running.\n", self.rfpc))
+        }
+    }
+
+    /// Reads XPB registers (`monitor xpb read [-g] <island> <addr> [count]`).
+    fn monitor_xpb_read(&mut self, args: &[&str]) -> Result<String, String> {
+        let usage = "Usage: xpb read [-g] <island> <addr> [count]";
+        let (global, args) = match args {
+            ["-g", rest @ ..] => (true, rest),
+            _ => (false, args),
+        };
+        let (island, address, count) = match args {
+            [island, address] => (*island, *address, "1"),
+            [island, address, count] => (*island, *address, *count),
+            _ => return Err(usage.to_string()),
+        };
+
+        let island = parse_island(island)?;
+        let address =
+            hex_parser(address).map_err(|e| format!("Invalid address '{}': {}", address, e))?;
+        let count = hex_parser(count).map_err(|e| format!("Invalid count '{}': {}", count, e))?;
+
+        let values = xpb_read(self.bus.bar(), &island, address, count as u64, global);
+
+        Ok(values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("{:#010x}: {:#010x}\n", address + 4 * i as u32, value))
+            .collect())
+    }
+
+    /// Writes XPB registers (`monitor xpb write [-g] <island> <addr> <value>...`).
+    fn monitor_xpb_write(&mut self, args: &[&str]) -> Result<String, String> {
+        let usage = "Usage: xpb write [-g] <island> <addr> <value>...";
+        let (global, args) = match args {
+            ["-g", rest @ ..] => (true, rest),
+            _ => (false, args),
+        };
+        if args.len() < 3 {
+            return Err(usage.to_string());
+        }
+
+        let island = parse_island(args[0])?;
+        let address =
+            hex_parser(args[1]).map_err(|e| format!("Invalid address '{}': {}", args[1], e))?;
+        let values = args[2..]
+            .iter()
+            .map(|value| hex_parser(value).map_err(|e| format!("Invalid value '{}': {}", value, e)))
+            .collect::<Result<Vec<u32>, String>>()?;
+        let num_values = values.len();
+
+        xpb_write(self.bus.bar(), &island, address, values, global);
+
+        Ok(format!(
+            "Wrote {} word(s) to {}:{:#010x}\n",
+            num_values,
+            island.id(),
+            address
+        ))
+    }
+
+    /// Reads NFP memory (`monitor mem read <ctm|emem|cls> <addr> <len> [island]`).
+    ///
+    /// The length is in bytes and is rounded up to whole 32-bit words. CTM and
+    /// CLS default to the island of the debugged core and EMEM to `emu0`.
+    fn monitor_mem_read(&mut self, args: &[&str]) -> Result<String, String> {
+        let usage = "Usage: mem read <ctm|emem|cls> <addr> <len> [island]";
+        if args.len() < 3 || args.len() > 4 {
+            return Err(usage.to_string());
+        }
+
+        let (mem_type, island) = self.parse_mem_target(args[0], args.get(3).copied())?;
+        let address =
+            hex_parser_u64(args[1]).map_err(|e| format!("Invalid address '{}': {}", args[1], e))?;
+        let length =
+            hex_parser(args[2]).map_err(|e| format!("Invalid length '{}': {}", args[2], e))?;
+
+        let num_words = (length as u64).div_ceil(4);
+        let words = mem_read(
+            self.bus.bar(),
+            island,
+            mem_type,
+            MuMemoryEngine::Bulk32,
+            address,
+            num_words,
+        );
+        let bytes: &[u8] = cast_slice(&words);
+
+        let mut output = String::new();
+        for line in hexdump_lines(address, &bytes[..length as usize]) {
+            output.push_str(&line);
+            output.push('\n');
+        }
+        Ok(output)
+    }
+
+    /// Writes NFP memory (`monitor mem write <ctm|emem|cls> <addr> <value>...`).
+    ///
+    /// Each value is written as a 32-bit word to the island of the debugged
+    /// core (CTM, CLS) or to `emu0` (EMEM).
+    fn monitor_mem_write(&mut self, args: &[&str]) -> Result<String, String> {
+        let usage = "Usage: mem write <ctm|emem|cls> <addr> <value>...";
+        if args.len() < 3 {
+            return Err(usage.to_string());
+        }
+
+        let (mem_type, island) = self.parse_mem_target(args[0], None)?;
+        let address =
+            hex_parser_u64(args[1]).map_err(|e| format!("Invalid address '{}': {}", args[1], e))?;
+        let values = args[2..]
+            .iter()
+            .map(|value| hex_parser(value).map_err(|e| format!("Invalid value '{}': {}", value, e)))
+            .collect::<Result<Vec<u32>, String>>()?;
+        let num_values = values.len();
+
+        mem_write(
+            self.bus.bar(),
+            island,
+            mem_type,
+            MuMemoryEngine::Bulk32,
+            address,
+            values,
+        );
+
+        Ok(format!(
+            "Wrote {} word(s) to {} {:#x}\n",
+            num_values, mem_type, address
+        ))
+    }
+
+    /// Resolves the memory type and target island for the `mem` commands.
+    fn parse_mem_target(
+        &self,
+        mem_type: &str,
+        island: Option<&str>,
+    ) -> Result<(MemoryType, CppIsland), String> {
+        let mem_type = MemoryType::from_str(mem_type, true)
+            .map_err(|_| format!("Invalid memory type '{}' (ctm, emem or cls)", mem_type))?;
+        let island = match island {
+            Some(island) => parse_island(island)?,
+            None => match mem_type {
+                MemoryType::Emem => CppIsland::Emu0,
+                MemoryType::Ctm | MemoryType::Cls => self.rfpc.island,
+            },
+        };
+        Ok((mem_type, island))
+    }
+
+    /// Shows the debug module status of the core (`monitor dmstatus`).
+    fn monitor_dmstatus(&mut self, args: &[&str]) -> Result<String, String> {
+        if !args.is_empty() {
+            return Err("Usage: dmstatus".to_string());
+        }
+
+        let dmstatus = rfpc_dbg_status(self.bus.bar(), &self.rfpc);
+        Ok(format!(
+            "{} dmstatus = {:#010x} (version {})\n  {}\n",
+            self.rfpc,
+            dmstatus,
+            dmstatus & 0xF,
+            dmstatus_flags(dmstatus).join(" ")
+        ))
+    }
+
+    /// Shows the run state of every core in the cluster of the debugged core
+    /// (`monitor cores`). The debugged core is marked with `*`.
+    fn monitor_cores(&mut self, args: &[&str]) -> Result<String, String> {
+        if !args.is_empty() {
+            return Err("Usage: cores".to_string());
+        }
+
+        let mut output = String::new();
+        for group in 0..4 {
+            for core in 0..8 {
+                let rfpc = Rfpc {
+                    island: self.rfpc.island,
+                    cluster: self.rfpc.cluster,
+                    group,
+                    core,
+                };
+                let dmstatus = rfpc_dbg_status(self.bus.bar(), &rfpc);
+                let marker = if group == self.rfpc.group && core == self.rfpc.core {
+                    '*'
+                } else {
+                    ' '
+                };
+                output.push_str(&format!(
+                    "{} {:<16} {}\n",
+                    marker,
+                    rfpc.to_string(),
+                    dmstatus_state(dmstatus)
+                ));
+            }
+        }
+
+        // Leave the debug module pointing at the debugged core again.
+        rfpc_dbg_status(self.bus.bar(), &self.rfpc);
+
+        Ok(output)
+    }
+
+    /// Starts an uncompressed trace of the core into the Performance Analyzer
+    /// FIFO (`monitor trace start [pc] [seq] [bp] [reg]`).
+    ///
+    /// Without arguments all trace sources are enabled. Each sample holds a
+    /// timestamp and all three performance bus words.
+    fn monitor_trace_start(&mut self, args: &[&str]) -> Result<String, String> {
+        let (mut trace_pc, mut trace_seq, mut trace_bp, mut trace_reg) =
+            (false, false, false, false);
+        for arg in args {
+            match *arg {
+                "pc" => trace_pc = true,
+                "seq" => trace_seq = true,
+                "bp" => trace_bp = true,
+                "reg" => trace_reg = true,
+                _ => return Err("Usage: trace start [pc] [seq] [bp] [reg]".to_string()),
+            }
+        }
+        if args.is_empty() {
+            (trace_pc, trace_seq, trace_bp, trace_reg) = (true, true, true, true);
+        }
+
+        let rfpc = self.rfpc.clone();
+        let mut pa = pa_trigger_on_uncomp_trace(
+            self.bus.bar(),
+            &rfpc,
+            trace_pc,
+            trace_seq,
+            trace_bp,
+            trace_reg,
+            3,
+            0,
+            true,
+        );
+        pa.trigger_idle();
+        pa.trigger_start(0, 0);
+
+        Ok(format!("Tracing {} into the PA FIFO.\n", self.rfpc))
+    }
+
+    /// Stops the trace trigger (`monitor trace stop`).
+    fn monitor_trace_stop(&mut self, args: &[&str]) -> Result<String, String> {
+        if !args.is_empty() {
+            return Err("Usage: trace stop".to_string());
+        }
+
+        let mut pa = PerformanceAnalyzer::new(self.bus.bar(), self.rfpc.island);
+        pa.trigger_halt();
+
+        Ok("Trace stopped.\n".to_string())
+    }
+
+    /// Reads trace samples from the Performance Analyzer FIFO
+    /// (`monitor trace read [samples]`). Reads all buffered samples by
+    /// default.
+    fn monitor_trace_read(&mut self, args: &[&str]) -> Result<String, String> {
+        let num_samples = match args {
+            [] => 0,
+            [samples] => {
+                hex_parser(samples).map_err(|e| format!("Invalid count '{}': {}", samples, e))?
+            }
+            _ => return Err("Usage: trace read [samples]".to_string()),
+        };
+
+        let mut pa = PerformanceAnalyzer::new(self.bus.bar(), self.rfpc.island);
+        if pa.read_fifo_control().empty() {
+            return Ok("Trace FIFO is empty.\n".to_string());
+        }
+        let samples = pa.read_fifo(num_samples.saturating_mul(TRACE_WORDS_PER_SAMPLE).min(4096));
+
+        let mut output = String::new();
+        for line in format_uncomp_trace(samples, 3, 0, true, TRACE_WORDS_PER_SAMPLE as usize) {
+            output.push_str(&line);
+            output.push('\n');
+        }
+        Ok(output)
+    }
+
     fn set_breakpoint(&mut self, packet: Vec<u8>) -> String {
         // Extract the address and kind.
         let buffer_info = String::from_utf8_lossy(&packet[3..]);
@@ -678,7 +1518,7 @@ impl<'a> RspServer<'a> {
             let breakpoint_instr: Vec<u32> = vec![0x00100073];
             // Read the RISC-V instruction at the breakpoint location.
             let riscv_instr = mem_read(
-                self.exp_bar,
+                self.bus.bar(),
                 CppIsland::Rfpc0,
                 MemoryType::Ctm,
                 MuMemoryEngine::Atomic32,
@@ -691,7 +1531,7 @@ impl<'a> RspServer<'a> {
 
             // Write breakpoint instruction to memory.
             mem_write(
-                self.exp_bar,
+                self.bus.bar(),
                 CppIsland::Rfpc0,
                 MemoryType::Ctm,
                 MuMemoryEngine::Atomic32,
@@ -700,13 +1540,25 @@ impl<'a> RspServer<'a> {
             );
         } else {
             // Non-CTM case.
-            let riscv_instr = rfpc_dbg_read_memory(self.exp_bar, &self.rfpc, masked_address, 1);
+            let riscv_instr = rfpc_dbg_read_memory(
+                self.bus.bar(),
+                &self.rfpc,
+                &self.dbg_config,
+                masked_address,
+                1,
+            );
 
             // Cache the RISC-V instruction and location.
             self.breakpoints.insert(address, riscv_instr[0]);
             let bp_instr = (riscv_instr[0] & 0xFFFF_FFFF_0000_0000) | 0x0000_0000_0010_0073;
 
-            rfpc_dbg_write_memory(self.exp_bar, &self.rfpc, masked_address, vec![bp_instr]);
+            rfpc_dbg_write_memory(
+                self.bus.bar(),
+                &self.rfpc,
+                &self.dbg_config,
+                masked_address,
+                vec![bp_instr],
+            );
         }
 
         "OK".to_string()
@@ -731,14 +1583,22 @@ impl<'a> RspServer<'a> {
         // Remove address from hashmap.
         self.breakpoints.remove(&address);
 
+        self.restore_instruction(address, riscv_instr[0]);
+
+        "OK".to_string()
+    }
+
+    /// Writes the original instruction cached by `set_breakpoint` back to
+    /// the breakpoint address.
+    fn restore_instruction(&mut self, address: u64, instruction: u64) {
         // Check if the write is to CTM.
         let write_ctm: bool = ((address >> 48) & 0xF) == 0x1;
         let masked_address = address & 0x00000000FFFFFFFF;
         if write_ctm {
-            let riscv_instr: Vec<u32> = vec![riscv_instr[0] as u32];
+            let riscv_instr: Vec<u32> = vec![instruction as u32];
             // Write riscv instruction back to CTM (clear breakpoint).
             mem_write(
-                self.exp_bar,
+                self.bus.bar(),
                 CppIsland::Rfpc0,
                 MemoryType::Ctm,
                 MuMemoryEngine::Atomic32,
@@ -747,10 +1607,24 @@ impl<'a> RspServer<'a> {
             );
         } else {
             // Write riscv instruction back to LMEM (clear breakpoint).
-            rfpc_dbg_write_memory(self.exp_bar, &self.rfpc, masked_address, riscv_instr);
+            rfpc_dbg_write_memory(
+                self.bus.bar(),
+                &self.rfpc,
+                &self.dbg_config,
+                masked_address,
+                vec![instruction],
+            );
         }
+    }
 
-        "OK".to_string()
+    /// Removes every breakpoint that is still set, restoring the original
+    /// instructions.
+    fn clear_all_breakpoints(&mut self) {
+        let breakpoints: Vec<(u64, u64)> = self.breakpoints.drain().collect();
+        for (address, instruction) in breakpoints {
+            println!("Removing breakpoint at {:#x}", address);
+            self.restore_instruction(address, instruction);
+        }
     }
 
     /// Write memory at a specific target address.
@@ -807,7 +1681,7 @@ impl<'a> RspServer<'a> {
 
             // Write program segment to memory.
             mem_write(
-                self.exp_bar,
+                self.bus.bar(),
                 CppIsland::Rfpc0,
                 MemoryType::Ctm,
                 MuMemoryEngine::Bulk32,
@@ -818,7 +1692,13 @@ impl<'a> RspServer<'a> {
             // Cast the byte slice to u64 vec safely.
             let program_data: Vec<u64> = cast_slice(&packet_data).to_vec();
 
-            rfpc_dbg_write_memory(self.exp_bar, &self.rfpc, address, program_data);
+            rfpc_dbg_write_memory(
+                self.bus.bar(),
+                &self.rfpc,
+                &self.dbg_config,
+                address,
+                program_data,
+            );
         }
 
         "OK".to_string()
@@ -861,7 +1741,7 @@ impl<'a> RspServer<'a> {
         if read_ctm {
             let word_len = (length + 3) / 4; // Calculate 32-bit word length
             let read_words: Vec<u32> = mem_read(
-                self.exp_bar,
+                self.bus.bar(),
                 CppIsland::Rfpc0,
                 MemoryType::Ctm,
                 MuMemoryEngine::Bulk32,
@@ -887,8 +1767,13 @@ impl<'a> RspServer<'a> {
             }
         } else {
             let word_len = (length + 7) / 8; // Calculate 64-bit word length
-            let read_qwords: Vec<u64> =
-                rfpc_dbg_read_memory(self.exp_bar, &self.rfpc, address, word_len);
+            let read_qwords: Vec<u64> = rfpc_dbg_read_memory(
+                self.bus.bar(),
+                &self.rfpc,
+                &self.dbg_config,
+                address,
+                word_len,
+            );
 
             // Truncate to requested length.
             let mut read_bytes: Vec<u8> = cast_slice(&read_qwords).to_vec();
@@ -995,6 +1880,10 @@ impl<'a> RspServer<'a> {
                 Some(FuncType::Ascii(resp)) => Some(resp.to_string()),
                 Some(FuncType::NoArg(func)) => Some(func(self)),
                 Some(FuncType::WithArg(func)) => Some(func(self, packet)),
+                Some(FuncType::NoReply(func)) => {
+                    func(self, packet);
+                    None
+                }
                 None => None,
             };
         }
@@ -1006,6 +1895,10 @@ impl<'a> RspServer<'a> {
                     Some(FuncType::Ascii(resp)) => Some(resp.to_string()),
                     Some(FuncType::NoArg(func)) => Some(func(self)),
                     Some(FuncType::WithArg(func)) => Some(func(self, packet)),
+                    Some(FuncType::NoReply(func)) => {
+                        func(self, packet);
+                        None
+                    }
                     None => None,
                 };
             }
@@ -1033,9 +1926,9 @@ impl<'a> RspServer<'a> {
         data.iter().fold(0, |acc, &b| acc.wrapping_add(b))
     }
 
-    /// Parses an incoming RSP packet from a TCP stream.
+    /// Parses an incoming RSP packet from a client stream.
     ///
-    /// This function reads the raw bytes from the provided `TcpStream`
+    /// This function reads the raw bytes from the provided stream
     /// one byte at a time, looking for the start of an RSP packet
     /// (indicated by `$`), then reads the packet contents until it
     /// encounters the end of the packet (indicated by `#`). After
@@ -1043,20 +1936,34 @@ impl<'a> RspServer<'a> {
     ///
     /// # Parameters
     ///
-    /// * `stream: &mut TcpStream` - Mutable reference to the `TcpStream`.
+    /// * `stream: &mut S` - Mutable reference to the TCP or Unix socket stream,
+    ///   with a read timeout.
+    /// * `running: &AtomicBool` - Server run flag. A read timeout inside a
+    ///   packet is retried until it is cleared.
     ///
     /// # Returns
     ///
     /// * `Ok(Vec<u8>)` - If there are no errors during packet parsing.
-    /// * `Ok(None)` - If the stream is closed by the client.
-    /// * `Err(std::io::Error)` - IO error during packet reading.
-    fn parse_rsp_packet(&self, stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+    /// * `Err(std::io::Error)` - IO error during packet reading, of kind
+    ///   `UnexpectedEof` if the stream is closed by the client, or a timeout
+    ///   error (see `is_timeout`) if no packet arrived in time.
+    fn parse_rsp_packet<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        running: &AtomicBool,
+    ) -> std::io::Result<Vec<u8>> {
         let mut buffer_orig: Vec<u8> = Vec::new();
         let mut buffer: Vec<u8> = Vec::new();
         let mut byte: [u8; 1] = [0; 1];
 
         // Read 1 byte at a time until we find a starting '$'.
-        while stream.read(&mut byte)? > 0 {
+        loop {
+            if stream.read(&mut byte)? == 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "Connection closed by the client",
+                ));
+            }
             if byte[0] == b'$' {
                 break;
             }
@@ -1064,7 +1971,7 @@ impl<'a> RspServer<'a> {
 
         // Read the rest of the packet until we hit '#', handling escaped characters.
         let mut escaped = false;
-        while stream.read(&mut byte)? > 0 && byte[0] != b'#' {
+        while read_packet_byte(stream, &mut byte, running)? > 0 && byte[0] != b'#' {
             buffer_orig.push(byte[0]);
 
             if escaped {
@@ -1082,7 +1989,15 @@ impl<'a> RspServer<'a> {
 
         // Read the checksum (two hex characters) after the '#'.
         let mut checksum: [u8; 2] = [0; 2];
-        stream.read_exact(&mut checksum)?;
+        for checksum_byte in checksum.iter_mut() {
+            if read_packet_byte(stream, &mut byte, running)? == 0 {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "Connection closed by the client",
+                ));
+            }
+            *checksum_byte = byte[0];
+        }
 
         // Calculate checksum and validate.
         let expected_checksum = self.calculate_rsp_checksum(&buffer_orig);
@@ -1132,77 +2047,287 @@ impl<'a> RspServer<'a> {
         packet
     }
 
+    /// Drains the queued console output into `O` packets.
+    ///
+    /// # Returns
+    ///
+    /// `Vec<String>` - Packet payloads, each holding at most
+    /// `CONSOLE_CHUNK_SIZE` hex characters of console text.
+    fn take_console_packets(&mut self) -> Vec<String> {
+        let text: String = self.console_output.drain(..).collect();
+        let hex: String = text.bytes().map(|b| format!("{:02x}", b)).collect();
+        hex.as_bytes()
+            .chunks(CONSOLE_CHUNK_SIZE)
+            .map(|chunk| format!("O{}", String::from_utf8_lossy(chunk)))
+            .collect()
+    }
+
     /// Runs the RSP server, accepting and handling client connections.
     ///
     /// # Parameters
     ///
+    /// * `listen_addrs: &[RspListenAddr]` - Addresses to accept GDB
+    ///   connections on. The actual address of every listener is printed,
+    ///   which shows the port picked by the OS for port 0.
     /// * `running : Arc<AtomicBool>` - An atomic boolean flag
     ///   indicating whether the server should continue running. When
     ///   this flag is set to `false`, the server will gracefully shut
     ///   down.
-    pub fn run(&mut self, running: Arc<AtomicBool>) {
-        // Bind to an address and port.
-        let listener =
-            TcpListener::bind((LOCAL_HOST_IP, PORT)).expect("Failed to bind to local host!");
-
-        // Set the listener to non-blocking mode.
-        listener
-            .set_nonblocking(true)
-            .expect("Cannot set non-blocking");
+    pub fn run(&mut self, listen_addrs: &[RspListenAddr], running: Arc<AtomicBool>) {
+        // Bind to the listen addresses.
+        let listeners: Vec<RspListener> = listen_addrs
+            .iter()
+            .map(|addr| {
+                RspListener::bind(addr)
+                    .unwrap_or_else(|e| panic!("Failed to listen on {:?}: {}", addr, e))
+            })
+            .collect();
 
+        for listener in &listeners {
+            println!("Listening for GDB connections on {}", listener);
+        }
         println!("Waiting for GDB connection");
 
         // Main loop: wait for a connection or check if the server should stop.
+        // Every client gets its own session; the server keeps accepting new
+        // clients after a session ends until it is stopped.
         while running.load(Ordering::SeqCst) {
-            match listener.accept() {
-                Ok((mut stream, addr)) => {
-                    println!("Connected to {:?}", addr);
-                    // Handle message from the client.
-                    while running.load(Ordering::SeqCst) {
-                        match self.parse_rsp_packet(&mut stream) {
-                            Ok(packet) => {
-                                // Handle the packet based on its content.
-                                match self.handle_packet(packet) {
-                                    Some(resp_data) => {
-                                        let resp_send: String;
-                                        if resp_data == "detach" {
-                                            let ack: String = "OK".to_string();
-                                            resp_send = self.format_rsp_packet(&ack);
-                                            stream.write_all(resp_send.as_bytes()).unwrap();
-                                            sleep(Duration::from_millis(100));
-                                            // Set running to false to break out of all loops
-                                            running.store(false, Ordering::SeqCst);
-                                            break;
-                                        } else {
-                                            resp_send = self.format_rsp_packet(&resp_data);
-                                            println!("Reply: {}", resp_send);
-                                            stream.write_all(resp_send.as_bytes()).unwrap();
-                                        }
-                                    }
-                                    None => (), // Do nothing.
-                                };
-                            }
-                            Err(e) => {
-                                if !self.disable_ack {
-                                    stream.write_all(b"-").unwrap();
-                                }
-                                println!("Failed to read packet: {}", e);
-                            }
+            let mut accepted = false;
+            for listener in &listeners {
+                let result = match listener {
+                    RspListener::Tcp(listener) => listener.accept().and_then(|(stream, addr)| {
+                        stream.set_nonblocking(false)?;
+                        stream.set_read_timeout(Some(CLIENT_READ_TIMEOUT))?;
+                        println!("Connected to {:?}", addr);
+                        Ok(self.serve_client(stream, &running))
+                    }),
+                    RspListener::Unix(listener, path) => {
+                        listener.accept().and_then(|(stream, _)| {
+                            stream.set_nonblocking(false)?;
+                            stream.set_read_timeout(Some(CLIENT_READ_TIMEOUT))?;
+                            println!("Connected to unix:{}", path.display());
+                            Ok(self.serve_client(stream, &running))
+                        })
+                    }
+                };
+                match result {
+                    Ok(end) => {
+                        accepted = true;
+                        match end {
+                            SessionEnd::Detached => println!("GDB detached."),
+                            SessionEnd::Killed => println!("GDB killed the program."),
+                            SessionEnd::Disconnected => println!("GDB disconnected."),
+                            SessionEnd::Shutdown => break,
                         }
+                        println!("Waiting for GDB connection");
+                    }
+                    Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => (),
+                    Err(e) => {
+                        // Unexpected error.
+                        println!("Error accepting connection: {}", e);
+                        running.store(false, Ordering::SeqCst);
                     }
                 }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    // No connection, sleep for a short duration to avoid busy waiting.
-                    sleep(Duration::from_millis(100));
-                }
+            }
+
+            if !accepted {
+                // No connection, sleep for a short duration to avoid busy waiting.
+                sleep(Duration::from_millis(100));
+            }
+        }
+
+        println!("Server shutting down gracefully.");
+    }
+
+    /// Resets the per-client protocol state at the start of a session.
+    fn start_session(&mut self) {
+        self.disable_ack = false;
+        self.client_kv_support.clear();
+        self.client_v_support.clear();
+        self.console_output.clear();
+        self.session_end = None;
+    }
+
+    /// Sends a reply to the client, preceded by any queued console output.
+    fn send_reply<S: Write>(&mut self, stream: &mut S, resp_data: &str) -> std::io::Result<()> {
+        // Console output goes out ahead of the reply.
+        for output in self.take_console_packets() {
+            let output_send = self.format_rsp_packet(&output);
+            stream.write_all(output_send.as_bytes())?;
+        }
+        let resp_send = self.format_rsp_packet(resp_data);
+        println!("Reply: {}", resp_send);
+        stream.write_all(resp_send.as_bytes())
+    }
+
+    /// Handles the packets of a connected GDB client until the session ends.
+    ///
+    /// If the client goes away without detaching, all breakpoints are
+    /// removed so the next client finds the original code in memory. The
+    /// stream must have a read timeout, so a stopped server ends the session
+    /// even while the client is idle; `shutdown()` then restores the core.
+    ///
+    /// # Returns
+    ///
+    /// Why the session ended.
+    fn serve_client<S: Read + Write>(
+        &mut self,
+        mut stream: S,
+        running: &Arc<AtomicBool>,
+    ) -> SessionEnd {
+        self.start_session();
+
+        let end = loop {
+            // Let the other sessions on a shared BAR in between packets.
+            self.bus.unlock();
+
+            if !running.load(Ordering::SeqCst) {
+                break SessionEnd::Shutdown;
+            }
+
+            let packet = match self.parse_rsp_packet(&mut stream, running) {
+                Ok(packet) => packet,
+                // No packet yet, check whether the server was stopped.
+                Err(e) if is_timeout(&e) => continue,
+                Err(e) if is_disconnect(&e) => break SessionEnd::Disconnected,
                 Err(e) => {
-                    // Unexpected error.
-                    println!("Error accepting connection: {}", e);
-                    break;
+                    println!("Failed to read packet: {}", e);
+                    if !self.disable_ack && stream.write_all(b"-").is_err() {
+                        break SessionEnd::Disconnected;
+                    }
+                    continue;
+                }
+            };
+
+            // Handle the packet based on its content.
+            if let Some(resp_data) = self.handle_packet(packet) {
+                if let Err(e) = self.send_reply(&mut stream, &resp_data) {
+                    println!("Failed to send reply: {}", e);
+                    break SessionEnd::Disconnected;
                 }
             }
+
+            if let Some(end) = self.session_end.take() {
+                break end;
+            }
+        };
+
+        if end == SessionEnd::Disconnected {
+            self.clear_all_breakpoints();
         }
+        self.bus.unlock();
+        end
+    }
+}
 
-        println!("Server shutting down gracefully.");
+/// A core debugged by one session of `run_supervisor`, and the address its
+/// GDB client connects to.
+#[derive(Clone)]
+pub struct RspSession {
+    pub rfpc: Rfpc,
+    pub listen_addr: RspListenAddr,
+}
+
+/// Runs one RSP server per core, each in its own thread and with its own
+/// listen address, so several GDB clients can debug different cores of the
+/// same NFP at the same time. The servers share one expansion BAR, see
+/// `RspServer::new_shared`.
+///
+/// # Parameters
+///
+/// * `exp_bar: &Mutex<ExpansionBar>` - The expansion BAR shared by all
+///   sessions.
+/// * `sessions: &[RspSession]` - The cores to debug and their listen
+///   addresses.
+/// * `dbg_config: &DebugConfig` - Polling and timeout settings for the RFPC
+///   debugger, used by every session.
+/// * `kill_policy: KillPolicy` - What `k` does to the core of a session.
+/// * `running: Arc<AtomicBool>` - Server run flag. Clearing it stops all
+///   sessions.
+pub fn run_supervisor(
+    exp_bar: &Mutex<ExpansionBar>,
+    sessions: &[RspSession],
+    dbg_config: &DebugConfig,
+    kill_policy: KillPolicy,
+    running: Arc<AtomicBool>,
+) {
+    std::thread::scope(|scope| {
+        for session in sessions {
+            let running = running.clone();
+            std::thread::Builder::new()
+                .name(session.rfpc.to_string())
+                .spawn_scoped(scope, move || {
+                    let rfpc = &session.rfpc;
+                    let mut rsp_server = RspServer::new_shared(
+                        exp_bar,
+                        rfpc.island,
+                        rfpc.cluster,
+                        rfpc.group,
+                        rfpc.core,
+                        dbg_config.clone(),
+                    );
+                    rsp_server.set_kill_policy(kill_policy);
+                    println!("Serving core {}", rfpc);
+                    rsp_server.run(std::slice::from_ref(&session.listen_addr), running);
+                })
+                .expect("Failed to start the RSP session thread");
+        }
+    });
+}
+
+impl Drop for RspServer<'_> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+// Checks whether an IO error is the read timeout of a client stream.
+fn is_timeout(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+    )
+}
+
+// Reads a byte of a packet from a client stream, retrying on read timeouts
+// until the server is stopped.
+fn read_packet_byte<S: Read>(
+    stream: &mut S,
+    byte: &mut [u8; 1],
+    running: &AtomicBool,
+) -> std::io::Result<usize> {
+    loop {
+        match stream.read(byte) {
+            Err(e) if is_timeout(&e) && running.load(Ordering::SeqCst) => continue,
+            result => return result,
+        }
+    }
+}
+
+// Checks whether an IO error means that the client connection is gone.
+fn is_disconnect(e: &std::io::Error) -> bool {
+    matches!(
+        e.kind(),
+        std::io::ErrorKind::UnexpectedEof
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::BrokenPipe
+    )
+}
+
+/// Decodes a string of hex digit pairs into bytes, ignoring invalid pairs.
+fn hex_decode(hex: &[u8]) -> Vec<u8> {
+    hex.chunks_exact(2)
+        .filter_map(|pair| u8::from_str_radix(&String::from_utf8_lossy(pair), 16).ok())
+        .collect()
+}
+
+/// Parses a CPP island given by name (e.g. `rfpc0`) or numeric ID.
+fn parse_island(island: &str) -> Result<CppIsland, String> {
+    if let Ok(id) = hex_parser(island) {
+        if id <= 15 {
+            return Ok(CppIsland::from_id(id as u8));
+        }
     }
+    CppIsland::from_str(island, true).map_err(|_| format!("Invalid island '{}'", island))
 }
@@ -0,0 +1,593 @@
+#![allow(dead_code)]
+
+// ABI names of the RISC-V integer registers.
+const REG_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+    "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4",
+    "t5", "t6",
+];
+
+/// Returns the ABI name of an integer register.
+fn reg(num: u32) -> &'static str {
+    REG_NAMES[(num & 0x1F) as usize]
+}
+
+/// Returns the ABI name of a compressed (3-bit) register field.
+fn creg(num: u32) -> &'static str {
+    REG_NAMES[(8 + (num & 0x7)) as usize]
+}
+
+/// Returns the name of a CSR, or its number if it has no known name.
+fn csr_name(csr: u32) -> String {
+    let name = match csr {
+        0x001 => "fflags",
+        0x002 => "frm",
+        0x003 => "fcsr",
+        0xC00 => "cycle",
+        0xC01 => "time",
+        0xC02 => "instret",
+        0x300 => "mstatus",
+        0x301 => "misa",
+        0x302 => "medeleg",
+        0x303 => "mideleg",
+        0x304 => "mie",
+        0x305 => "mtvec",
+        0x306 => "mcounteren",
+        0x340 => "mscratch",
+        0x341 => "mepc",
+        0x342 => "mcause",
+        0x343 => "mtval",
+        0x344 => "mip",
+        0x7A0 => "tselect",
+        0x7A1 => "tdata1",
+        0x7A2 => "tdata2",
+        0x7B0 => "dcsr",
+        0x7B1 => "dpc",
+        0x7B2 => "dscratch0",
+        0x7B3 => "dscratch1",
+        0xB00 => "mcycle",
+        0xB02 => "minstret",
+        0xF11 => "mvendorid",
+        0xF12 => "marchid",
+        0xF13 => "mimpid",
+        0xF14 => "mhartid",
+        _ => return format!("{:#x}", csr),
+    };
+    name.to_string()
+}
+
+/// Extracts bits `hi..=lo` of `value`.
+fn bits(value: u32, hi: u32, lo: u32) -> u32 {
+    (value >> lo) & ((1 << (hi - lo + 1)) - 1)
+}
+
+/// Sign extends the low `width` bits of `value`.
+fn sext(value: u32, width: u32) -> i64 {
+    let shift = 64 - width;
+    ((value as i64) << shift) >> shift
+}
+
+/// Formats a mnemonic and its operands.
+fn fmt_inst(mnemonic: &str, operands: &str) -> String {
+    if operands.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{:<8}{}", mnemonic, operands)
+    }
+}
+
+/// Returns the length in bytes of the instruction starting with the given
+/// 16-bit parcel. Only 16-bit (compressed) and 32-bit instructions are
+/// supported.
+pub fn instruction_length(parcel: u16) -> usize {
+    if parcel & 0x3 == 0x3 {
+        4
+    } else {
+        2
+    }
+}
+
+/// Disassembles a single RV64IMAC (plus Zicsr and Zifencei) instruction.
+///
+/// # Parameters
+///
+/// * `inst`: The instruction. Only the low 16 bits are used for compressed
+///   instructions.
+/// * `pc`: Address of the instruction, used to resolve branch and jump
+///   targets.
+///
+/// # Returns
+///
+/// The instruction in assembler syntax, using the common pseudo-instructions
+/// (`li`, `mv`, `ret`, `j`, ...). Unknown encodings are shown as `.word` or
+/// `.half` directives.
+pub fn disassemble(inst: u32, pc: u64) -> String {
+    if instruction_length(inst as u16) == 2 {
+        disassemble_compressed(inst & 0xFFFF, pc)
+    } else {
+        disassemble_base(inst, pc)
+    }
+}
+
+fn disassemble_base(inst: u32, pc: u64) -> String {
+    let opcode = bits(inst, 6, 0);
+    let rd = bits(inst, 11, 7);
+    let funct3 = bits(inst, 14, 12);
+    let rs1 = bits(inst, 19, 15);
+    let rs2 = bits(inst, 24, 20);
+    let funct7 = bits(inst, 31, 25);
+    let imm_i = sext(bits(inst, 31, 20), 12);
+    let imm_s = sext((bits(inst, 31, 25) << 5) | bits(inst, 11, 7), 12);
+    let imm_b = sext(
+        (bits(inst, 31, 31) << 12)
+            | (bits(inst, 7, 7) << 11)
+            | (bits(inst, 30, 25) << 5)
+            | (bits(inst, 11, 8) << 1),
+        13,
+    );
+    let imm_j = sext(
+        (bits(inst, 31, 31) << 20)
+            | (bits(inst, 19, 12) << 12)
+            | (bits(inst, 20, 20) << 11)
+            | (bits(inst, 30, 21) << 1),
+        21,
+    );
+    let target = |offset: i64| format!("{:#x}", pc.wrapping_add(offset as u64));
+    let unknown = format!("{:<8}{:#010x}", ".word", inst);
+
+    match opcode {
+        // LUI / AUIPC
+        0x37 => fmt_inst("lui", &format!("{},{:#x}", reg(rd), inst >> 12)),
+        0x17 => fmt_inst("auipc", &format!("{},{:#x}", reg(rd), inst >> 12)),
+        // JAL
+        0x6F => match rd {
+            0 => fmt_inst("j", &target(imm_j)),
+            1 => fmt_inst("jal", &target(imm_j)),
+            _ => fmt_inst("jal", &format!("{},{}", reg(rd), target(imm_j))),
+        },
+        // JALR
+        0x67 if funct3 == 0 => match (rd, rs1, imm_i) {
+            (0, 1, 0) => "ret".to_string(),
+            (0, _, 0) => fmt_inst("jr", reg(rs1)),
+            (1, _, 0) => fmt_inst("jalr", reg(rs1)),
+            (0, _, _) => fmt_inst("jr", &format!("{}({})", imm_i, reg(rs1))),
+            (1, _, _) => fmt_inst("jalr", &format!("{}({})", imm_i, reg(rs1))),
+            _ => fmt_inst("jalr", &format!("{},{}({})", reg(rd), imm_i, reg(rs1))),
+        },
+        // Branches
+        0x63 => {
+            let mnemonic = match funct3 {
+                0 => "beq",
+                1 => "bne",
+                4 => "blt",
+                5 => "bge",
+                6 => "bltu",
+                7 => "bgeu",
+                _ => return unknown,
+            };
+            match (funct3, rs1, rs2) {
+                (0, _, 0) => fmt_inst("beqz", &format!("{},{}", reg(rs1), target(imm_b))),
+                (1, _, 0) => fmt_inst("bnez", &format!("{},{}", reg(rs1), target(imm_b))),
+                (4, _, 0) => fmt_inst("bltz", &format!("{},{}", reg(rs1), target(imm_b))),
+                (5, _, 0) => fmt_inst("bgez", &format!("{},{}", reg(rs1), target(imm_b))),
+                (4, 0, _) => fmt_inst("bgtz", &format!("{},{}", reg(rs2), target(imm_b))),
+                (5, 0, _) => fmt_inst("blez", &format!("{},{}", reg(rs2), target(imm_b))),
+                _ => fmt_inst(
+                    mnemonic,
+                    &format!("{},{},{}", reg(rs1), reg(rs2), target(imm_b)),
+                ),
+            }
+        }
+        // Loads
+        0x03 => {
+            let mnemonic = match funct3 {
+                0 => "lb",
+                1 => "lh",
+                2 => "lw",
+                3 => "ld",
+                4 => "lbu",
+                5 => "lhu",
+                6 => "lwu",
+                _ => return unknown,
+            };
+            fmt_inst(mnemonic, &format!("{},{}({})", reg(rd), imm_i, reg(rs1)))
+        }
+        // Stores
+        0x23 => {
+            let mnemonic = match funct3 {
+                0 => "sb",
+                1 => "sh",
+                2 => "sw",
+                3 => "sd",
+                _ => return unknown,
+            };
+            fmt_inst(mnemonic, &format!("{},{}({})", reg(rs2), imm_s, reg(rs1)))
+        }
+        // OP-IMM
+        0x13 => {
+            let shamt = bits(inst, 25, 20);
+            match funct3 {
+                0 => match (rd, rs1, imm_i) {
+                    (0, 0, 0) => "nop".to_string(),
+                    (_, 0, _) => fmt_inst("li", &format!("{},{}", reg(rd), imm_i)),
+                    (_, _, 0) => fmt_inst("mv", &format!("{},{}", reg(rd), reg(rs1))),
+                    _ => fmt_inst("addi", &format!("{},{},{}", reg(rd), reg(rs1), imm_i)),
+                },
+                1 if bits(inst, 31, 26) == 0 => {
+                    fmt_inst("slli", &format!("{},{},{}", reg(rd), reg(rs1), shamt))
+                }
+                2 => fmt_inst("slti", &format!("{},{},{}", reg(rd), reg(rs1), imm_i)),
+                3 if imm_i == 1 => fmt_inst("seqz", &format!("{},{}", reg(rd), reg(rs1))),
+                3 => fmt_inst("sltiu", &format!("{},{},{}", reg(rd), reg(rs1), imm_i)),
+                4 if imm_i == -1 => fmt_inst("not", &format!("{},{}", reg(rd), reg(rs1))),
+                4 => fmt_inst("xori", &format!("{},{},{}", reg(rd), reg(rs1), imm_i)),
+                5 => match bits(inst, 31, 26) {
+                    0x00 => fmt_inst("srli", &format!("{},{},{}", reg(rd), reg(rs1), shamt)),
+                    0x10 => fmt_inst("srai", &format!("{},{},{}", reg(rd), reg(rs1), shamt)),
+                    _ => unknown,
+                },
+                6 => fmt_inst("ori", &format!("{},{},{}", reg(rd), reg(rs1), imm_i)),
+                7 => fmt_inst("andi", &format!("{},{},{}", reg(rd), reg(rs1), imm_i)),
+                _ => unknown,
+            }
+        }
+        // OP-IMM-32
+        0x1B => {
+            let shamt = bits(inst, 24, 20);
+            match (funct3, funct7) {
+                (0, _) if imm_i == 0 => fmt_inst("sext.w", &format!("{},{}", reg(rd), reg(rs1))),
+                (0, _) => fmt_inst("addiw", &format!("{},{},{}", reg(rd), reg(rs1), imm_i)),
+                (1, 0x00) => fmt_inst("slliw", &format!("{},{},{}", reg(rd), reg(rs1), shamt)),
+                (5, 0x00) => fmt_inst("srliw", &format!("{},{},{}", reg(rd), reg(rs1), shamt)),
+                (5, 0x20) => fmt_inst("sraiw", &format!("{},{},{}", reg(rd), reg(rs1), shamt)),
+                _ => unknown,
+            }
+        }
+        // OP
+        0x33 => {
+            let mnemonic = match (funct7, funct3) {
+                (0x00, 0) => "add",
+                (0x20, 0) => "sub",
+                (0x00, 1) => "sll",
+                (0x00, 2) => "slt",
+                (0x00, 3) => "sltu",
+                (0x00, 4) => "xor",
+                (0x00, 5) => "srl",
+                (0x20, 5) => "sra",
+                (0x00, 6) => "or",
+                (0x00, 7) => "and",
+                (0x01, 0) => "mul",
+                (0x01, 1) => "mulh",
+                (0x01, 2) => "mulhsu",
+                (0x01, 3) => "mulhu",
+                (0x01, 4) => "div",
+                (0x01, 5) => "divu",
+                (0x01, 6) => "rem",
+                (0x01, 7) => "remu",
+                _ => return unknown,
+            };
+            match (mnemonic, rs1) {
+                ("sub", 0) => fmt_inst("neg", &format!("{},{}", reg(rd), reg(rs2))),
+                ("sltu", 0) => fmt_inst("snez", &format!("{},{}", reg(rd), reg(rs2))),
+                _ => fmt_inst(mnemonic, &format!("{},{},{}", reg(rd), reg(rs1), reg(rs2))),
+            }
+        }
+        // OP-32
+        0x3B => {
+            let mnemonic = match (funct7, funct3) {
+                (0x00, 0) => "addw",
+                (0x20, 0) => "subw",
+                (0x00, 1) => "sllw",
+                (0x00, 5) => "srlw",
+                (0x20, 5) => "sraw",
+                (0x01, 0) => "mulw",
+                (0x01, 4) => "divw",
+                (0x01, 5) => "divuw",
+                (0x01, 6) => "remw",
+                (0x01, 7) => "remuw",
+                _ => return unknown,
+            };
+            fmt_inst(mnemonic, &format!("{},{},{}", reg(rd), reg(rs1), reg(rs2)))
+        }
+        // MISC-MEM
+        0x0F => match funct3 {
+            0 => "fence".to_string(),
+            1 => "fence.i".to_string(),
+            _ => unknown,
+        },
+        // SYSTEM
+        0x73 => {
+            let csr = bits(inst, 31, 20);
+            match funct3 {
+                0 => match inst {
+                    0x0000_0073 => "ecall".to_string(),
+                    0x0010_0073 => "ebreak".to_string(),
+                    0x3020_0073 => "mret".to_string(),
+                    0x1050_0073 => "wfi".to_string(),
+                    0x7B20_0073 => "dret".to_string(),
+                    _ => unknown,
+                },
+                1..=3 => {
+                    let (mnemonic, pseudo) = match funct3 {
+                        1 => ("csrrw", "csrw"),
+                        2 => ("csrrs", "csrs"),
+                        _ => ("csrrc", "csrc"),
+                    };
+                    if funct3 == 2 && rs1 == 0 {
+                        fmt_inst("csrr", &format!("{},{}", reg(rd), csr_name(csr)))
+                    } else if rd == 0 {
+                        fmt_inst(pseudo, &format!("{},{}", csr_name(csr), reg(rs1)))
+                    } else {
+                        fmt_inst(
+                            mnemonic,
+                            &format!("{},{},{}", reg(rd), csr_name(csr), reg(rs1)),
+                        )
+                    }
+                }
+                5..=7 => {
+                    let mnemonic = match funct3 {
+                        5 => "csrrwi",
+                        6 => "csrrsi",
+                        _ => "csrrci",
+                    };
+                    fmt_inst(mnemonic, &format!("{},{},{}", reg(rd), csr_name(csr), rs1))
+                }
+                _ => unknown,
+            }
+        }
+        // AMO
+        0x2F if funct3 == 2 || funct3 == 3 => {
+            let width = if funct3 == 2 { "w" } else { "d" };
+            let funct5 = bits(inst, 31, 27);
+            let op = match funct5 {
+                0x02 if rs2 == 0 => {
+                    return fmt_inst(
+                        &format!("lr.{}", width),
+                        &format!("{},({})", reg(rd), reg(rs1)),
+                    )
+                }
+                0x03 => "sc",
+                0x01 => "amoswap",
+                0x00 => "amoadd",
+                0x04 => "amoxor",
+                0x0C => "amoand",
+                0x08 => "amoor",
+                0x10 => "amomin",
+                0x14 => "amomax",
+                0x18 => "amominu",
+                0x1C => "amomaxu",
+                _ => return unknown,
+            };
+            fmt_inst(
+                &format!("{}.{}", op, width),
+                &format!("{},{},({})", reg(rd), reg(rs2), reg(rs1)),
+            )
+        }
+        _ => unknown,
+    }
+}
+
+fn disassemble_compressed(inst: u32, pc: u64) -> String {
+    let quadrant = bits(inst, 1, 0);
+    let funct3 = bits(inst, 15, 13);
+    let rd = bits(inst, 11, 7);
+    let rs2 = bits(inst, 6, 2);
+    let imm6 = sext((bits(inst, 12, 12) << 5) | bits(inst, 6, 2), 6);
+    let target = |offset: i64| format!("{:#x}", pc.wrapping_add(offset as u64));
+    let unknown = format!("{:<8}{:#06x}", ".half", inst);
+
+    match (quadrant, funct3) {
+        // C.ADDI4SPN
+        (0, 0) => {
+            let imm = (bits(inst, 12, 11) << 4)
+                | (bits(inst, 10, 7) << 6)
+                | (bits(inst, 6, 6) << 2)
+                | (bits(inst, 5, 5) << 3);
+            if imm == 0 {
+                return unknown;
+            }
+            fmt_inst("addi", &format!("{},sp,{}", creg(bits(inst, 4, 2)), imm))
+        }
+        // C.LW / C.SW
+        (0, 2) | (0, 6) => {
+            let imm = (bits(inst, 12, 10) << 3) | (bits(inst, 6, 6) << 2) | (bits(inst, 5, 5) << 6);
+            let mnemonic = if funct3 == 2 { "lw" } else { "sw" };
+            fmt_inst(
+                mnemonic,
+                &format!(
+                    "{},{}({})",
+                    creg(bits(inst, 4, 2)),
+                    imm,
+                    creg(bits(inst, 9, 7))
+                ),
+            )
+        }
+        // C.LD / C.SD
+        (0, 3) | (0, 7) => {
+            let imm = (bits(inst, 12, 10) << 3) | (bits(inst, 6, 5) << 6);
+            let mnemonic = if funct3 == 3 { "ld" } else { "sd" };
+            fmt_inst(
+                mnemonic,
+                &format!(
+                    "{},{}({})",
+                    creg(bits(inst, 4, 2)),
+                    imm,
+                    creg(bits(inst, 9, 7))
+                ),
+            )
+        }
+        // C.ADDI / C.NOP
+        (1, 0) => {
+            if rd == 0 {
+                "nop".to_string()
+            } else {
+                fmt_inst("addi", &format!("{},{},{}", reg(rd), reg(rd), imm6))
+            }
+        }
+        // C.ADDIW
+        (1, 1) if rd != 0 => {
+            if imm6 == 0 {
+                fmt_inst("sext.w", &format!("{},{}", reg(rd), reg(rd)))
+            } else {
+                fmt_inst("addiw", &format!("{},{},{}", reg(rd), reg(rd), imm6))
+            }
+        }
+        // C.LI
+        (1, 2) => fmt_inst("li", &format!("{},{}", reg(rd), imm6)),
+        // C.ADDI16SP / C.LUI
+        (1, 3) => {
+            if rd == 2 {
+                let imm = sext(
+                    (bits(inst, 12, 12) << 9)
+                        | (bits(inst, 6, 6) << 4)
+                        | (bits(inst, 5, 5) << 6)
+                        | (bits(inst, 4, 3) << 7)
+                        | (bits(inst, 2, 2) << 5),
+                    10,
+                );
+                fmt_inst("addi", &format!("sp,sp,{}", imm))
+            } else {
+                fmt_inst(
+                    "lui",
+                    &format!("{},{:#x}", reg(rd), (imm6 as u32) & 0xFFFFF),
+                )
+            }
+        }
+        // C.SRLI / C.SRAI / C.ANDI / C.SUB / C.XOR / C.OR / C.AND / C.SUBW / C.ADDW
+        (1, 4) => {
+            let rd_p = creg(bits(inst, 9, 7));
+            let shamt = (bits(inst, 12, 12) << 5) | bits(inst, 6, 2);
+            match bits(inst, 11, 10) {
+                0 => fmt_inst("srli", &format!("{},{},{}", rd_p, rd_p, shamt)),
+                1 => fmt_inst("srai", &format!("{},{},{}", rd_p, rd_p, shamt)),
+                2 => fmt_inst("andi", &format!("{},{},{}", rd_p, rd_p, imm6)),
+                _ => {
+                    let mnemonic = match (bits(inst, 12, 12), bits(inst, 6, 5)) {
+                        (0, 0) => "sub",
+                        (0, 1) => "xor",
+                        (0, 2) => "or",
+                        (0, 3) => "and",
+                        (1, 0) => "subw",
+                        (1, 1) => "addw",
+                        _ => return unknown,
+                    };
+                    fmt_inst(
+                        mnemonic,
+                        &format!("{},{},{}", rd_p, rd_p, creg(bits(inst, 4, 2))),
+                    )
+                }
+            }
+        }
+        // C.J
+        (1, 5) => {
+            let imm = sext(
+                (bits(inst, 12, 12) << 11)
+                    | (bits(inst, 11, 11) << 4)
+                    | (bits(inst, 10, 9) << 8)
+                    | (bits(inst, 8, 8) << 10)
+                    | (bits(inst, 7, 7) << 6)
+                    | (bits(inst, 6, 6) << 7)
+                    | (bits(inst, 5, 3) << 1)
+                    | (bits(inst, 2, 2) << 5),
+                12,
+            );
+            fmt_inst("j", &target(imm))
+        }
+        // C.BEQZ / C.BNEZ
+        (1, 6) | (1, 7) => {
+            let imm = sext(
+                (bits(inst, 12, 12) << 8)
+                    | (bits(inst, 11, 10) << 3)
+                    | (bits(inst, 6, 5) << 6)
+                    | (bits(inst, 4, 3) << 1)
+                    | (bits(inst, 2, 2) << 5),
+                9,
+            );
+            let mnemonic = if funct3 == 6 { "beqz" } else { "bnez" };
+            fmt_inst(
+                mnemonic,
+                &format!("{},{}", creg(bits(inst, 9, 7)), target(imm)),
+            )
+        }
+        // C.SLLI
+        (2, 0) => {
+            let shamt = (bits(inst, 12, 12) << 5) | bits(inst, 6, 2);
+            fmt_inst("slli", &format!("{},{},{}", reg(rd), reg(rd), shamt))
+        }
+        // C.LWSP
+        (2, 2) if rd != 0 => {
+            let imm = (bits(inst, 12, 12) << 5) | (bits(inst, 6, 4) << 2) | (bits(inst, 3, 2) << 6);
+            fmt_inst("lw", &format!("{},{}(sp)", reg(rd), imm))
+        }
+        // C.LDSP
+        (2, 3) if rd != 0 => {
+            let imm = (bits(inst, 12, 12) << 5) | (bits(inst, 6, 5) << 3) | (bits(inst, 4, 2) << 6);
+            fmt_inst("ld", &format!("{},{}(sp)", reg(rd), imm))
+        }
+        // C.JR / C.MV / C.EBREAK / C.JALR / C.ADD
+        (2, 4) => match (bits(inst, 12, 12), rd, rs2) {
+            (0, 1, 0) => "ret".to_string(),
+            (0, 0, 0) => unknown,
+            (0, _, 0) => fmt_inst("jr", reg(rd)),
+            (0, _, _) => fmt_inst("mv", &format!("{},{}", reg(rd), reg(rs2))),
+            (_, 0, 0) => "ebreak".to_string(),
+            (_, _, 0) => fmt_inst("jalr", reg(rd)),
+            _ => fmt_inst("add", &format!("{},{},{}", reg(rd), reg(rd), reg(rs2))),
+        },
+        // C.SWSP
+        (2, 6) => {
+            let imm = (bits(inst, 12, 9) << 2) | (bits(inst, 8, 7) << 6);
+            fmt_inst("sw", &format!("{},{}(sp)", reg(rs2), imm))
+        }
+        // C.SDSP
+        (2, 7) => {
+            let imm = (bits(inst, 12, 10) << 3) | (bits(inst, 9, 7) << 6);
+            fmt_inst("sd", &format!("{},{}(sp)", reg(rs2), imm))
+        }
+        _ => unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instruction_lengths() {
+        assert_eq!(instruction_length(0x4501), 2);
+        assert_eq!(instruction_length(0x8082), 2);
+        assert_eq!(instruction_length(0x0001), 2);
+        assert_eq!(instruction_length(0x1297), 4);
+        assert_eq!(instruction_length(0x0533), 4);
+    }
+
+    // Expected text cross-checked against llvm-mc.
+    #[test]
+    fn disassemble_base() {
+        let pc = 0x1000;
+        assert_eq!(disassemble(0x0000_1297, pc), "auipc   t0,0x1");
+        assert_eq!(disassemble(0xc602_82e7, pc), "jalr    t0,-928(t0)");
+        assert_eq!(disassemble(0x0006_8a13, pc), "mv      s4,a3");
+        assert_eq!(disassemble(0x0000_0513, pc), "li      a0,0");
+        assert_eq!(disassemble(0x02b5_0533, pc), "mul     a0,a0,a1");
+        assert_eq!(disassemble(0x0005_0463, pc), "beqz    a0,0x1008");
+        assert_eq!(disassemble(0x0100_00ef, pc), "jal     0x1010");
+        assert_eq!(disassemble(0x0000_0073, pc), "ecall");
+        assert_eq!(disassemble(0x0010_0073, pc), "ebreak");
+        assert_eq!(disassemble(0x0826_151b, pc), ".word   0x0826151b");
+    }
+
+    #[test]
+    fn disassemble_compressed() {
+        let pc = 0x1000;
+        assert_eq!(disassemble(0x4501, pc), "li      a0,0");
+        assert_eq!(disassemble(0x8082, pc), "ret");
+        assert_eq!(disassemble(0x852e, pc), "mv      a0,a1");
+        assert_eq!(disassemble(0x0001, pc), "nop");
+        assert_eq!(disassemble(0xa001, pc), "j       0x1000");
+        assert_eq!(disassemble(0x1141, pc), "addi    sp,sp,-16");
+        assert_eq!(disassemble(0xe406, pc), "sd      ra,8(sp)");
+        // Only the low parcel of a compressed instruction is used.
+        assert_eq!(disassemble(0xffff_8082, pc), "ret");
+    }
+}